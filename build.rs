@@ -1,6 +1,12 @@
 
 fn main() {
+	// The host build (script runner) has no UI and no linker script
+	#[cfg(feature = "firmware")]
+	firmware();
+}
 
+#[cfg(feature = "firmware")]
+fn firmware() {
 	let config = slint_build::CompilerConfiguration::new()
 		.embed_resources(slint_build::EmbedResourcesKind::EmbedForSoftwareRenderer);
 	slint_build::compile_with_config("./autoplant_userinterface/main.slint", config).unwrap();
@@ -9,12 +15,13 @@ fn main() {
 }
 
 
-use std::env;
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
-
+#[cfg(feature = "firmware")]
 fn mem() {
+	use std::env;
+	use std::fs::File;
+	use std::io::Write;
+	use std::path::PathBuf;
+
 	// Put `memory.x` in our output directory and ensure it's
 	// on the linker search path.
 	let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
@@ -0,0 +1,3 @@
+; Lettuce, a single expression: 20 C and 65 % humidity, lights 07:00-19:00.
+; Above 24 C the vent runs regardless of the lights.
+'(20 65 (if (or (> temp 24) (and (>= hour 7) (< hour 19))) 1 0) (if (and (>= hour 7) (< hour 19)) 200 0))
@@ -0,0 +1,11 @@
+; Tomato, seedling to flowering over six weeks.
+; Day temperature 24 C falling to 21 C by day 42, nights 3 C cooler.
+; Leaf VPD 0.8 kPa for seedlings, rising to 1.0 by day 14 and 1.2 by day 28.
+; Lights 06:00-22:00 with a 30 minute sunrise and sunset, vent only while they are on.
+(export targets (temp humidity soil ec co2 days hour minute weekday state) (Pure (-> (Int Int Int Int Int Int Int Int Int '([String Int])) '([String Int])))
+  (let ((light (daylight (+ (* hour 60) minute) 360 1320 30 255))
+        (day_temp (ramp days 240 210 42)))
+    (Cons ["temp10" (if (> light 0) day_temp (- day_temp 30))]
+      (Cons ["vpd100" (interp days '([0 80] [14 100] [28 120]))]
+        (Cons ["light" light]
+          (Cons ["vent" (if (> light 0) 1 0)] Nil))))))
@@ -0,0 +1,115 @@
+//! Sensirion SCD40 / SCD41 CO2 sensor on I2C.
+//!
+//! Runs in periodic measurement mode, a new reading every 5 s. Every word the sensor sends
+//! or receives carries a CRC-8.
+
+#![no_std]
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+
+const ADDRESS: u8 = 0x62;
+
+const CMD_START_PERIODIC_MEASUREMENT: u16 = 0x21b1;
+const CMD_STOP_PERIODIC_MEASUREMENT: u16 = 0x3f86;
+const CMD_READ_MEASUREMENT: u16 = 0xec05;
+const CMD_GET_DATA_READY_STATUS: u16 = 0xe4b8;
+const CMD_SET_AMBIENT_PRESSURE: u16 = 0xe000;
+const CMD_SET_AUTOMATIC_SELF_CALIBRATION: u16 = 0x2416;
+
+/// Time the sensor needs to stop a periodic measurement, it takes no command before
+pub const STOP_DELAY_MS: u32 = 500;
+/// Execution time of the other commands
+const COMMAND_DELAY_MS: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// No acknowledge or a bus error
+    I2c,
+    /// A word came back with a wrong CRC
+    Crc,
+}
+
+pub struct Scd4x<I2C> {
+    i2c: I2C,
+}
+
+impl<I2C: I2c> Scd4x<I2C> {
+    pub fn new(i2c: I2C) -> Self {
+        Self { i2c }
+    }
+
+    pub async fn start_periodic_measurement(&mut self) -> Result<(), Error> {
+        self.i2c.write(ADDRESS, &CMD_START_PERIODIC_MEASUREMENT.to_be_bytes()).await.map_err(|_| Error::I2c)?;
+        Ok(())
+    }
+
+    /// Needed before changing settings other than the ambient pressure. Returns right away,
+    /// the caller waits `STOP_DELAY_MS` before the next command, e.g. over its next polls.
+    pub async fn stop_periodic_measurement(&mut self) -> Result<(), Error> {
+        self.i2c.write(ADDRESS, &CMD_STOP_PERIODIC_MEASUREMENT.to_be_bytes()).await.map_err(|_| Error::I2c)?;
+        Ok(())
+    }
+
+    /// Whether a new measurement is waiting to be read.
+    pub async fn data_ready(&mut self, delay: &mut impl DelayNs) -> Result<bool, Error> {
+        let [status] = self.read_words::<1>(CMD_GET_DATA_READY_STATUS, delay).await?;
+        Ok(status & 0x07ff != 0)
+    }
+
+    /// CO2 in ppm. The temperature and humidity words that follow are skipped, the sensor
+    /// warms itself and the air sensors read the chamber better.
+    pub async fn read_co2(&mut self, delay: &mut impl DelayNs) -> Result<u16, Error> {
+        let [co2, _temp, _hum] = self.read_words::<3>(CMD_READ_MEASUREMENT, delay).await?;
+        Ok(co2)
+    }
+
+    /// Pressure compensation, 700-1200 hPa. Allowed during periodic measurement, not kept
+    /// over a power cycle.
+    pub async fn set_ambient_pressure(&mut self, hpa: u16, delay: &mut impl DelayNs) -> Result<(), Error> {
+        self.write_word(CMD_SET_AMBIENT_PRESSURE, hpa, delay).await
+    }
+
+    /// Only while the periodic measurement is stopped.
+    pub async fn set_automatic_self_calibration(&mut self, enabled: bool, delay: &mut impl DelayNs) -> Result<(), Error> {
+        self.write_word(CMD_SET_AUTOMATIC_SELF_CALIBRATION, enabled as u16, delay).await
+    }
+
+    async fn write_word(&mut self, cmd: u16, word: u16, delay: &mut impl DelayNs) -> Result<(), Error> {
+        let [c0, c1] = cmd.to_be_bytes();
+        let [w0, w1] = word.to_be_bytes();
+        self.i2c.write(ADDRESS, &[c0, c1, w0, w1, crc8(&[w0, w1])]).await.map_err(|_| Error::I2c)?;
+        delay.delay_ms(COMMAND_DELAY_MS).await;
+        Ok(())
+    }
+
+    async fn read_words<const N: usize>(&mut self, cmd: u16, delay: &mut impl DelayNs) -> Result<[u16; N], Error> {
+        self.i2c.write(ADDRESS, &cmd.to_be_bytes()).await.map_err(|_| Error::I2c)?;
+        delay.delay_ms(COMMAND_DELAY_MS).await;
+        let mut buf = [0u8; 9]; // Longest response, 3 words
+        let buf = &mut buf[..N * 3];
+        self.i2c.read(ADDRESS, buf).await.map_err(|_| Error::I2c)?;
+
+        let mut words = [0u16; N];
+        for (word, chunk) in words.iter_mut().zip(buf.chunks_exact(3)) {
+            if crc8(&chunk[..2]) != chunk[2] {
+                return Err(Error::Crc);
+            }
+            *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+        }
+        Ok(words)
+    }
+}
+
+/// CRC-8, polynomial 0x31, init 0xff
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xffu8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x31 } else { crc << 1 };
+        }
+    }
+    crc
+}
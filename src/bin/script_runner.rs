@@ -0,0 +1,98 @@
+//! Runs a grow recipe script against a CSV of sensor values and prints the targets the
+//! controller would get, one CSV row per sample.
+//!
+//! ```text
+//! script_runner <script.lisp> <sensors.csv> [--days N] [--target-temp C] [--light START-END]
+//! ```
+//!
+//! See `userscript::recipe::parse_sensor_csv` for the input format. Exits with 1 if the
+//! script does not compile, 2 if any sample fell back to the schedule.
+
+use std::process::ExitCode;
+
+use rp2040_plant_automation::config_types::PlantConfiguration;
+use rp2040_plant_automation::userscript::recipe::{parse_sensor_csv, RecipeRun};
+
+const USAGE: &str = "usage: script_runner <script.lisp> <sensors.csv> [--days N] [--target-temp C] [--light START-END]";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, String> {
+    let mut args = std::env::args().skip(1);
+    let mut paths = Vec::new();
+    let mut days = None;
+    let mut plant = PlantConfiguration::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--days" => days = Some(parse_arg(&arg, args.next())?),
+            "--target-temp" => plant.target_temp = parse_arg(&arg, args.next())?,
+            "--light" => {
+                let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+                let (start, end) = value.split_once('-').ok_or_else(|| format!("{} expects START-END", arg))?;
+                plant.light_start_hour = parse_arg(&arg, Some(start.into()))?;
+                plant.light_end_hour = parse_arg(&arg, Some(end.into()))?;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(ExitCode::SUCCESS);
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let [script_path, csv_path] = paths.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    let source = std::fs::read_to_string(script_path).map_err(|e| format!("{}: {}", script_path, e))?;
+    let csv = std::fs::read_to_string(csv_path).map_err(|e| format!("{}: {}", csv_path, e))?;
+    let samples = parse_sensor_csv(&csv).map_err(|e| format!("{}: {}", csv_path, e))?;
+
+    let mut recipe = RecipeRun::new(&source, plant).map_err(|e| format!("{}: {}", script_path, e))?;
+    let steps = recipe.run(&samples, days);
+
+    println!("day,hour,minute,temp,humidity,vpd,vent,light,source,error");
+    let mut fallbacks = 0;
+    let mut max_steps = 0;
+    let mut max_heap = 0;
+    for step in &steps {
+        let t = &step.targets;
+        let vpd = t.vpd.map(|v| format!("{:.2}", v.to_num::<f32>())).unwrap_or_default();
+        let error = step.error.as_ref().map(|e| format!("\"{}\"", e.to_string().replace('"', "'"))).unwrap_or_default();
+        println!(
+            "{},{},{},{:.1},{},{},{},{},{},{}",
+            step.day, step.hour, step.minute,
+            t.temp.to_num::<f32>(), t.humidity, vpd, t.vent_on as u8, t.light_intensity,
+            if step.from_script { "script" } else { "schedule" },
+            error
+        );
+        if step.error.is_some() {
+            fallbacks += 1;
+        }
+        max_steps = max_steps.max(step.stats.eval_steps);
+        max_heap = max_heap.max(step.stats.eval_heap_bytes);
+    }
+
+    eprintln!(
+        "{} samples, {} fell back, max {} steps and {} heap bytes per evaluation",
+        steps.len(), fallbacks, max_steps, max_heap
+    );
+    for (key, value) in recipe.state().entries.iter() {
+        eprintln!("state {} = {}", key, value);
+    }
+
+    Ok(if fallbacks > 0 { ExitCode::from(2) } else { ExitCode::SUCCESS })
+}
+
+fn parse_arg<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", name))?;
+    value.parse().map_err(|_| format!("{}: invalid value `{}`", name, value))
+}
@@ -6,29 +6,35 @@ use embassy_rp::peripherals::{DMA_CH1, FLASH};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex;
 use crate::persistence_manager::PersistenceManager;
-use crate::config_types::{CalibrationData, DeviceSettings, PlantConfiguration};
-use crate::control::ControlConfig;
+use crate::config_types::{CalibrationData, DeviceSettings, PlantConfiguration, PumpCounters, ScriptState};
 
 pub struct ConfigManager<'d> {
     persistence: PersistenceManager<'d>,
     calibration: CalibrationData,
     settings: DeviceSettings,
     plant_config: PlantConfiguration,
+    script_state: ScriptState,
+    pump_counters: PumpCounters,
+    // Bumped whenever `plant_config.script_source` changes, so the control loop knows to reload
+    script_revision: u32,
 }
 
 impl<'d> ConfigManager<'d> {
     pub async fn new(mut persistence: PersistenceManager<'d>) -> Self {
-        let calibration = persistence.load_calibration().await.unwrap_or_else(|| CalibrationData {
-            pid_config: ControlConfig::default(),
-        });
+        let calibration = persistence.load_calibration().await.unwrap_or_default();
         let settings = persistence.load_settings().await.unwrap_or_default();
         let plant_config = persistence.load_plant_config().await.unwrap_or_default();
+        let script_state = persistence.load_script_state().await.unwrap_or_default();
+        let pump_counters = persistence.load_pump_counters().await.unwrap_or_default();
 
         Self {
             persistence,
             calibration,
             settings,
             plant_config,
+            script_state,
+            pump_counters,
+            script_revision: 0,
         }
     }
 
@@ -44,6 +50,18 @@ impl<'d> ConfigManager<'d> {
         &self.plant_config
     }
 
+    pub fn script_state(&self) -> &ScriptState {
+        &self.script_state
+    }
+
+    pub fn pump_counters(&self) -> PumpCounters {
+        self.pump_counters
+    }
+
+    pub fn script_revision(&self) -> u32 {
+        self.script_revision
+    }
+
     pub async fn update_calibration<F>(&mut self, f: F)
     where
         F: FnOnce(&mut CalibrationData),
@@ -68,11 +86,33 @@ impl<'d> ConfigManager<'d> {
     where
         F: FnOnce(&mut PlantConfiguration),
     {
+        let old_script = self.plant_config.script_source.clone();
         f(&mut self.plant_config);
+        if self.plant_config.script_source != old_script {
+            self.script_revision = self.script_revision.wrapping_add(1);
+        }
         if self.persistence.save_plant_config(&self.plant_config).await.is_err() {
             defmt::error!("Failed to save plant config");
         }
     }
+
+    pub async fn update_script_state<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut ScriptState),
+    {
+        f(&mut self.script_state);
+        if self.persistence.save_script_state(&self.script_state).await.is_err() {
+            defmt::error!("Failed to save script state");
+        }
+    }
+
+    /// Updated every control step, written to flash only when `save` is set to spare the flash.
+    pub async fn update_pump_counters(&mut self, counters: PumpCounters, save: bool) {
+        self.pump_counters = counters;
+        if save && self.persistence.save_pump_counters(&self.pump_counters).await.is_err() {
+            defmt::error!("Failed to save pump counters");
+        }
+    }
 }
 
 
@@ -11,11 +11,279 @@ use heapless::String;
 // Actually, it's better to have ControlConfig derive Serialize/Deserialize in control.rs and use it here.
 // So we will import it.
 
-use crate::control::ControlConfig;
+use crate::control::{ControlConfig, Number, TargetState};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Humidity target used when nothing else (script, config) provides one.
+pub const DEFAULT_TARGET_HUMIDITY: u8 = 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct CalibrationData {
     pub pid_config: ControlConfig,
+    pub filter: FilterConfig,
+    pub air_sensors: AirSensorRoles,
+    pub co2: Co2SensorConfig,
+    pub light: LightSensorConfig,
+    pub ntc: NtcConfig,
+}
+
+/// Layout version of the stored `CalibrationData`, written after `CALIBRATION_MAGIC`.
+///
+/// postcard is not self-describing, a stored struct only decodes into the exact layout it was
+/// written with. Any change to `CalibrationData` or a type inside it (`ControlConfig`, ...)
+/// bumps this, keeps the previous layout as a module like `calibration_v0` and converts it in
+/// `CalibrationData::decode`.
+pub const CALIBRATION_VERSION: u8 = 1;
+const CALIBRATION_MAGIC: [u8; 2] = *b"CD";
+const CALIBRATION_HEADER: usize = CALIBRATION_MAGIC.len() + 1;
+/// Room for an encoded `CalibrationData`, header included. The flash buffers are sized from it.
+pub const CALIBRATION_MAX_BYTES: usize = 1024;
+
+impl CalibrationData {
+    /// Serializes for flash, behind the magic and `CALIBRATION_VERSION`.
+    pub fn encode<'a>(&self, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let (header, body) = buf.split_at_mut_checked(CALIBRATION_HEADER)?;
+        let len = postcard::to_slice(self, body).ok()?.len();
+        header[..CALIBRATION_MAGIC.len()].copy_from_slice(&CALIBRATION_MAGIC);
+        header[CALIBRATION_MAGIC.len()] = CALIBRATION_VERSION;
+        Some(&buf[..CALIBRATION_HEADER + len])
+    }
+
+    /// Reads what `encode` wrote, or a calibration stored before the version header existed.
+    /// None for an unknown version (written by newer firmware) or damaged data.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [m0, m1, version, body @ ..] if [*m0, *m1] == CALIBRATION_MAGIC => match *version {
+                CALIBRATION_VERSION => decode_exact(body),
+                _ => None,
+            },
+            _ => decode_exact::<calibration_v0::CalibrationData>(bytes).map(Self::from),
+        }
+    }
+}
+
+/// postcard value that uses up all of `bytes`, so a different layout is not taken for this one
+fn decode_exact<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Option<T> {
+    match postcard::take_from_bytes(bytes) {
+        Ok((value, [])) => Some(value),
+        _ => None,
+    }
+}
+
+/// Calibration as stored before `CALIBRATION_VERSION`: the controller settings only.
+mod calibration_v0 {
+    use serde::{Deserialize, Serialize};
+
+    use crate::control::{Number, PidGains};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CalibrationData {
+        pub pid_config: ControlConfig,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ControlConfig {
+        pub air_temp: PidGains,
+        pub peltier_temp_heat: PidGains,
+        pub peltier_temp_cool: PidGains,
+        pub hum_cold_side: PidGains,
+        pub hum_cold_target: Number,
+        pub k_ff_hum: Number,
+        pub k_ff_vent: Number,
+        pub fan_temp_outer: PidGains,
+        pub fan_hum_hot: PidGains,
+        pub peltier_temp_diff_target: Number,
+        pub k_fan_effort: Number,
+        pub fan_base_day: Number,
+        pub fan_base_night: Number,
+        pub max_fan_speed: u8,
+        pub soil_low_threshold: Number,
+        pub soil_high_threshold: Number,
+        pub water_cal_no_tray: Number,
+        pub water_cal_dry_tray: Number,
+        pub water_cal_wet_tray: Number,
+        pub ec_low_threshold: Number,
+        pub ec_high_threshold: Number,
+    }
+}
+
+impl From<calibration_v0::CalibrationData> for CalibrationData {
+    /// Keeps the gains and the tray calibration, everything added since starts at its default.
+    /// The EC thresholds are dropped, dosing now works towards `PlantConfiguration::nominal_ec`.
+    fn from(old: calibration_v0::CalibrationData) -> Self {
+        let old = old.pid_config;
+        let mut cal = Self::default();
+        let c = &mut cal.pid_config;
+        c.air_temp = old.air_temp;
+        c.peltier_temp_heat = old.peltier_temp_heat;
+        c.peltier_temp_cool = old.peltier_temp_cool;
+        c.hum_cold_side = old.hum_cold_side;
+        c.hum_cold_target = old.hum_cold_target;
+        c.k_ff_hum = old.k_ff_hum;
+        c.k_ff_vent = old.k_ff_vent;
+        c.fan_temp_outer = old.fan_temp_outer;
+        c.fan_hum_hot = old.fan_hum_hot;
+        c.peltier_temp_diff_target = old.peltier_temp_diff_target;
+        c.k_fan_effort = old.k_fan_effort;
+        c.fan_base_day = old.fan_base_day;
+        c.fan_base_night = old.fan_base_night;
+        c.max_fan_speed = old.max_fan_speed;
+        c.soil_low_threshold = old.soil_low_threshold;
+        c.soil_high_threshold = old.soil_high_threshold;
+        c.water_cal_no_tray = old.water_cal_no_tray;
+        c.water_cal_dry_tray = old.water_cal_dry_tray;
+        c.water_cal_wet_tray = old.water_cal_wet_tray;
+        cal
+    }
+}
+
+/// Where a temperature / humidity sensor is mounted
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorRole {
+    Internal,
+    External,
+    /// Read and health-tracked, not used
+    Spare,
+}
+
+/// Roles of the two I2C air sensors, they get swapped between chambers.
+///
+/// Failover only happens between sensors in the same role. A role whose only sensor stops
+/// working reads `None` (the chamber air raises `AirSensorLost`), it never takes the other
+/// role's sensor, which measures somewhere else. So the default, one Internal and one
+/// External, has no failover; put both in Internal for it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct AirSensorRoles {
+    pub sht20: SensorRole,
+    pub aht20: SensorRole,
+    /// Two working sensors in one role are averaged weighted by their filter variance. With
+    /// the same Q and R for both channels the variances settle to the same value, so this is
+    /// a plain average; a sensor only weighs less while its variance grows over failed reads.
+    /// Without fusion the SHT20 is used while it works, the AHT20 after.
+    pub fuse: bool,
+}
+
+impl Default for AirSensorRoles {
+    fn default() -> Self {
+        Self {
+            sht20: SensorRole::Internal,
+            aht20: SensorRole::External,
+            fuse: true,
+        }
+    }
+}
+
+/// SCD4x CO2 sensor settings, sent to the sensor at start and whenever they change.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Co2SensorConfig {
+    /// Automatic self-calibration, takes the lowest reading of a week as 400 ppm.
+    /// Only right if the chamber gets fresh air at least once a week.
+    pub asc: bool,
+    /// Ambient pressure for the pressure compensation, 700-1200 hPa
+    pub ambient_pressure_hpa: u16,
+}
+
+impl Default for Co2SensorConfig {
+    fn default() -> Self {
+        Self {
+            asc: true,
+            ambient_pressure_hpa: 1013,
+        }
+    }
+}
+
+/// BH1750 light sensor settings.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LightSensorConfig {
+    /// PPFD in µmol/m²/s per 1000 lx. Depends on the spectrum: about 14 for white LEDs,
+    /// 18 for daylight, more for red / blue grow LEDs.
+    pub ppfd_per_klux: f32,
+}
+
+impl Default for LightSensorConfig {
+    fn default() -> Self {
+        Self { ppfd_per_klux: 14.0 }
+    }
+}
+
+// NTC defaults (common 10k module)
+const NTC_R0: f32 = 10_000.0;
+const NTC_T0: f32 = 25.0;
+const NTC_BETA: f32 = 3950.0;
+const NTC_SERIES_R: f32 = 10_000.0;
+
+/// Resistance -> temperature model of a thermistor
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NtcModel {
+    /// R = r0 * exp(beta * (1/T - 1/T0)), `t0` in °C
+    Beta { r0: f32, t0: f32, beta: f32 },
+    /// 1/T = a + b ln(R) + c ln(R)^3, T in kelvin
+    SteinhartHart { a: f32, b: f32, c: f32 },
+}
+
+/// Peltier NTCs (all four use the same part), each a divider on a PCF8591 input.
+/// The divider is fed from the ADC reference, so the reading is ratiometric and independent
+/// of the supply voltage.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct NtcConfig {
+    pub model: NtcModel,
+    /// Fixed resistor of the divider, in ohms
+    pub series_resistor: f32,
+    /// True if the thermistor sits between the ADC input and ground,
+    /// false if it sits between VREF and the ADC input
+    pub ntc_to_ground: bool,
+}
+
+impl Default for NtcConfig {
+    fn default() -> Self {
+        Self {
+            model: NtcModel::Beta { r0: NTC_R0, t0: NTC_T0, beta: NTC_BETA },
+            series_resistor: NTC_SERIES_R,
+            ntc_to_ground: true,
+        }
+    }
+}
+
+impl NtcConfig {
+    /// Positive, finite parameters; a Steinhart-Hart model must have a non-zero `b`.
+    pub fn is_valid(&self) -> bool {
+        let model_ok = match self.model {
+            NtcModel::Beta { r0, t0, beta } => r0 > 0.0 && beta > 0.0 && t0.is_finite() && t0 > -273.15,
+            NtcModel::SteinhartHart { a, b, c } => a.is_finite() && b.is_finite() && c.is_finite() && b != 0.0,
+        };
+        model_ok && self.series_resistor > 0.0 && self.series_resistor.is_finite()
+    }
+}
+
+/// Sensor filter channels: SHT temp / hum, AHT temp / hum, NTC 1-4, soil, EC
+pub const FILTER_CHANNELS: usize = 10;
+
+/// Kalman noise of one sensor channel
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ChannelNoise {
+    pub q: f32, // Process noise, how fast the true value may move per sample
+    pub r: f32, // Measurement noise
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FilterConfig {
+    pub channels: [ChannelNoise; FILTER_CHANNELS],
+    pub outlier_sigma: f32, // Samples this many sigma off the prediction are dropped, 0 = off
+    pub adaptive_r: bool,   // Estimate R from the innovations, the configured R is the start value
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        let temp = ChannelNoise { q: 0.01, r: 40.0 };
+        let hum = ChannelNoise { q: 0.01, r: 5.0 };
+        let adc = ChannelNoise { q: 0.01, r: 0.2 }; // Fast response
+        Self {
+            channels: [temp, hum, temp, hum, temp, temp, temp, temp, adc, adc],
+            outlier_sigma: 4.0,
+            adaptive_r: false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -26,6 +294,13 @@ pub struct DeviceSettings {
     pub last_datetime: u64, // Unix timestamp
 }
 
+impl DeviceSettings {
+    /// Converts UTC to local time using `timezone_offset`.
+    pub fn local_time(&self, utc: chrono::DateTime<chrono::Utc>) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        chrono::FixedOffset::east_opt(self.timezone_offset).map(|offset| utc.with_timezone(&offset))
+    }
+}
+
 impl Default for DeviceSettings {
     fn default() -> Self {
         Self {
@@ -37,12 +312,44 @@ impl Default for DeviceSettings {
     }
 }
 
+/// Max size of the user script in bytes
+pub const SCRIPT_SOURCE_MAX: usize = 2048;
+
+/// Max number of entries in the script key/value store
+pub const SCRIPT_STATE_MAX_ENTRIES: usize = 16;
+/// Max key length in the script key/value store
+pub const SCRIPT_STATE_MAX_KEY: usize = 16;
+/// Room for a stored `PlantConfiguration`, a full script and the few fields around it
+pub const PLANT_CONFIG_MAX_BYTES: usize = SCRIPT_SOURCE_MAX + 128;
+/// Longest stored `ScriptState`: the entry count, then per entry the key behind its length
+/// and the value as a zigzag varint (up to 5 bytes for an i32).
+pub const SCRIPT_STATE_MAX_BYTES: usize = 1 + SCRIPT_STATE_MAX_ENTRIES * (1 + SCRIPT_STATE_MAX_KEY + 5);
+
+/// Tray pump run time today, kept across reboots for the daily budget.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct PumpCounters {
+    pub day: i32, // Local date as days since 0001-01-01, 0 until the clock was set
+    pub on_ms_today: u32,
+    pub locked_out: bool, // Budget used up, lifted at local midnight or when the fault is acknowledged
+}
+
+/// Numbers a user script keeps between evaluations (and across reboots).
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScriptState {
+    pub entries: Vec<(String<SCRIPT_STATE_MAX_KEY>, i32), SCRIPT_STATE_MAX_ENTRIES>,
+}
+
+/// `nominal_ec` used to be in mS/cm. No ppm target is this low and no mS/cm target this high.
+pub const NOMINAL_EC_MS_CM_MAX: f32 = 20.0;
+/// ppm per mS/cm, the 500 scale the EC sensor reports in
+const EC_PPM_PER_MS_CM: f32 = 500.0;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PlantConfiguration {
     pub plant_name: String<32>,
     pub start_timestamp: Option<u64>,
-    pub nominal_ec: f32,
-    pub script_source: Vec<u8, 2048>,
+    pub nominal_ec: f32, // EC dosing target, ppm as the EC sensor reads it
+    pub script_source: Vec<u8, SCRIPT_SOURCE_MAX>,
     pub target_temp: f32,
     pub light_start_hour: u8,
     pub light_end_hour: u8,
@@ -56,7 +363,7 @@ impl Default for PlantConfiguration {
         Self {
             plant_name: name,
             start_timestamp: None,
-            nominal_ec: 1.2,
+            nominal_ec: 700.0,
             script_source: Vec::new(),
             target_temp: 25.0,
             light_start_hour: 8,
@@ -65,3 +372,178 @@ impl Default for PlantConfiguration {
         }
     }
 }
+
+impl PlantConfiguration {
+    /// Whole days elapsed since `start_timestamp`, or 0 if the start date or clock is unknown.
+    pub fn days_since_start(&self, now_timestamp: Option<u64>) -> u32 {
+        match (self.start_timestamp, now_timestamp) {
+            (Some(start), Some(now)) if now > start => ((now - start) / 86_400) as u32,
+            _ => 0,
+        }
+    }
+
+    /// Whether `nominal_ec` reads as ppm: 0 (no dosing) or above the old mS/cm range.
+    pub fn nominal_ec_is_ppm(nominal_ec: f32) -> bool {
+        nominal_ec <= 0.0 || nominal_ec >= NOMINAL_EC_MS_CM_MAX
+    }
+
+    /// Converts a `nominal_ec` stored in mS/cm to ppm. Returns true if it did.
+    pub fn migrate_nominal_ec(&mut self) -> bool {
+        if Self::nominal_ec_is_ppm(self.nominal_ec) {
+            return false;
+        }
+        self.nominal_ec *= EC_PPM_PER_MS_CM;
+        true
+    }
+
+    pub fn script_str(&self) -> &str {
+        core::str::from_utf8(&self.script_source).unwrap_or("")
+    }
+
+    /// Targets from the static configuration and light schedule alone.
+    /// Used as the fallback for anything the user script does not provide.
+    pub fn scheduled_targets(&self, hour: u8) -> TargetState {
+        let is_light_on = if self.light_start_hour < self.light_end_hour {
+            hour >= self.light_start_hour && hour < self.light_end_hour
+        } else {
+            hour >= self.light_start_hour || hour < self.light_end_hour
+        };
+
+        TargetState {
+            temp: Number::from_num(self.target_temp),
+            humidity: DEFAULT_TARGET_HUMIDITY,
+            vpd: None,
+            vent_on: true,
+            light_intensity: if is_light_on { self.light_intensity } else { 0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::PidGains;
+
+    fn v0_control(air_temp: PidGains, tray: [i32; 3]) -> calibration_v0::ControlConfig {
+        let zero = Number::ZERO;
+        let gains = PidGains::new(1.0, 0.0, 0.0);
+        calibration_v0::ControlConfig {
+            air_temp,
+            peltier_temp_heat: gains,
+            peltier_temp_cool: gains,
+            hum_cold_side: gains,
+            hum_cold_target: Number::from_num(4),
+            k_ff_hum: zero,
+            k_ff_vent: zero,
+            fan_temp_outer: gains,
+            fan_hum_hot: gains,
+            peltier_temp_diff_target: Number::from_num(8),
+            k_fan_effort: zero,
+            fan_base_day: zero,
+            fan_base_night: zero,
+            max_fan_speed: 180,
+            soil_low_threshold: Number::from_num(90),
+            soil_high_threshold: Number::from_num(230),
+            water_cal_no_tray: Number::from_num(tray[0]),
+            water_cal_dry_tray: Number::from_num(tray[1]),
+            water_cal_wet_tray: Number::from_num(tray[2]),
+            ec_low_threshold: Number::from_num(1),
+            ec_high_threshold: Number::from_num(2),
+        }
+    }
+
+    #[test]
+    fn calibration_round_trips_with_header() {
+        let mut cal = CalibrationData::default();
+        cal.pid_config.air_temp = PidGains::new(3.0, 0.02, 0.5);
+        cal.ntc.series_resistor = 4700.0;
+
+        let mut buf = [0u8; 1024];
+        let bytes = cal.encode(&mut buf).unwrap();
+        assert_eq!(&bytes[..3], &[b'C', b'D', CALIBRATION_VERSION]);
+
+        let back = CalibrationData::decode(bytes).unwrap();
+        assert_eq!(back.pid_config, cal.pid_config);
+        assert_eq!(back.ntc, cal.ntc);
+        assert_eq!(back.filter, cal.filter);
+    }
+
+    #[test]
+    fn calibration_without_header_keeps_gains_and_tray() {
+        let gains = PidGains::new(7.5, 0.125, 0.25);
+        let old = calibration_v0::CalibrationData { pid_config: v0_control(gains, [3100, 2300, 1100]) };
+        let mut buf = [0u8; 1024];
+        let bytes = postcard::to_slice(&old, &mut buf).unwrap();
+
+        let cal = CalibrationData::decode(bytes).unwrap();
+        let c = &cal.pid_config;
+        assert_eq!(c.air_temp, gains);
+        assert_eq!(c.water_cal_no_tray, 3100);
+        assert_eq!(c.water_cal_dry_tray, 2300);
+        assert_eq!(c.water_cal_wet_tray, 1100);
+        assert_eq!(c.max_fan_speed, 180);
+        // Added later, so at the default
+        assert_eq!(c.pump_max_on_secs, ControlConfig::default().pump_max_on_secs);
+        assert_eq!(cal.ntc, NtcConfig::default());
+    }
+
+    #[test]
+    fn calibration_fits_its_buffer() {
+        let mut buf = [0u8; CALIBRATION_MAX_BYTES];
+        let len = CalibrationData::default().encode(&mut buf).unwrap().len();
+        // Room for fields added later and for values with longer varints than the defaults
+        assert!(2 * len <= CALIBRATION_MAX_BYTES, "{} bytes", len);
+    }
+
+    #[test]
+    fn plant_config_with_full_script_fits_its_buffer() {
+        let mut pc = PlantConfiguration { start_timestamp: Some(u64::MAX), ..Default::default() };
+        pc.plant_name.clear();
+        while pc.plant_name.push('x').is_ok() {}
+        while pc.script_source.push(b'x').is_ok() {}
+        let mut buf = [0u8; PLANT_CONFIG_MAX_BYTES];
+        assert!(postcard::to_slice(&pc, &mut buf).is_ok());
+    }
+
+    #[test]
+    fn full_script_state_fits_its_buffer() {
+        let mut state = ScriptState::default();
+        for i in 0..SCRIPT_STATE_MAX_ENTRIES {
+            let mut key = String::new();
+            for _ in 0..SCRIPT_STATE_MAX_KEY {
+                key.push((b'a' + i as u8) as char).unwrap();
+            }
+            state.entries.push((key, i32::MIN)).unwrap();
+        }
+        let mut buf = [0u8; SCRIPT_STATE_MAX_BYTES];
+        let bytes = postcard::to_slice(&state, &mut buf).unwrap();
+        assert_eq!(bytes.len(), SCRIPT_STATE_MAX_BYTES);
+    }
+
+    #[test]
+    fn nominal_ec_in_ms_cm_is_converted_to_ppm() {
+        let mut pc = PlantConfiguration { nominal_ec: 1.2, ..Default::default() };
+        assert!(pc.migrate_nominal_ec());
+        assert_eq!(pc.nominal_ec, 600.0);
+        assert!(!pc.migrate_nominal_ec());
+        assert_eq!(pc.nominal_ec, 600.0);
+
+        for ppm in [0.0, 700.0, NOMINAL_EC_MS_CM_MAX] {
+            let mut pc = PlantConfiguration { nominal_ec: ppm, ..Default::default() };
+            assert!(!pc.migrate_nominal_ec());
+            assert_eq!(pc.nominal_ec, ppm);
+        }
+    }
+
+    #[test]
+    fn calibration_of_unknown_layout_is_rejected() {
+        let mut buf = [0u8; 1024];
+        let len = CalibrationData::default().encode(&mut buf).unwrap().len();
+
+        let mut newer = buf;
+        newer[2] = CALIBRATION_VERSION + 1;
+        assert!(CalibrationData::decode(&newer[..len]).is_none());
+        assert!(CalibrationData::decode(&buf[..len - 1]).is_none());
+        assert!(CalibrationData::decode(&[]).is_none());
+    }
+}
@@ -2,154 +2,68 @@
 #![allow(unused_variables)]
 
 
-use fixed::types::I16F16;
+use alloc::vec::Vec;
 use piddiy::PidController;
-use serde::{Serialize, Deserialize};
 
-// Type alias for our fixed-point number
-pub type Number = I16F16;
-
-/// PID Gains structure for cleaner configuration
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub struct PidGains {
-    pub kp: Number,
-    pub ki: Number,
-    pub kd: Number,
-}
-
-impl PidGains {
-    pub fn new(kp: f32, ki: f32, kd: f32) -> Self {
-        Self {
-            kp: Number::from_num(kp),
-            ki: Number::from_num(ki),
-            kd: Number::from_num(kd),
-        }
-    }
-
-    /// Apply these gains to a PidController
-    pub fn apply_to(&self, pid: &mut PidController<Number, Number>) {
-        pid.kp(self.kp).ki(self.ki).kd(self.kd);
-    }
-}
-
-/// Configuration for the controller, including PID gains and limits.
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub struct ControlConfig {
-    // Temperature Control (Cascade)
-    pub air_temp: PidGains,      // Primary Loop (Air Temp -> Target Peltier Temp)
-    pub peltier_temp_heat: PidGains, // Secondary Loop (Peltier Temp -> PWM)
-    pub peltier_temp_cool: PidGains,
-
-    // Humidity Loop
-    pub hum_cold_side: PidGains,
-    pub hum_cold_target: Number,
-
-    // Feedforward Gains
-    pub k_ff_hum: Number,
-    pub k_ff_vent: Number,
-
-    // Fan Control
-    pub fan_temp_outer: PidGains,
-    pub fan_hum_hot: PidGains,
-    pub peltier_temp_diff_target: Number,
-
-    pub k_fan_effort: Number,
-    pub fan_base_day: Number,
-    pub fan_base_night: Number,
-    pub max_fan_speed: u8,
-
-    // Soil Moisture Control
-    pub soil_low_threshold: Number,
-    pub soil_high_threshold: Number,
-    
-    // Water Tray Control (Calibration Reference Values)
-    // Recorded ADC values for states
-    pub water_cal_no_tray: Number,  // ~3000
-    pub water_cal_dry_tray: Number, // ~2000-2500
-    pub water_cal_wet_tray: Number, // ~1000-1500
-    
-    // EC Control (Hysteresis)
-    pub ec_low_threshold: Number,
-    pub ec_high_threshold: Number,
-}
-
-impl Default for ControlConfig {
-    fn default() -> Self {
-        Self {
-            air_temp: PidGains::new(5.0, 0.05, 0.0), // Aggressive Kp
-            peltier_temp_heat: PidGains::new(2.0, 0.1, 0.0),
-            peltier_temp_cool: PidGains::new(5.0, 0.1, 0.0),
-            hum_cold_side: PidGains::new(2.0, 0.1, 0.0),
-            hum_cold_target: Number::from_num(5.0), // Target 5C for dehumidification
-            k_ff_hum: Number::from_num(0.2), // Scaled down for 0-255 input
-            k_ff_vent: Number::from_num(0.2),
-            fan_temp_outer: PidGains::new(5.0, 0.1, 0.0),
-            fan_hum_hot: PidGains::new(5.0, 0.1, 0.0),
-            peltier_temp_diff_target: Number::from_num(10.0), // Target 10C difference (keep it low)
-            k_fan_effort: Number::from_num(0.5),
-            fan_base_day: Number::from_num(51), // ~20% of 255
-            fan_base_night: Number::from_num(0),
-            max_fan_speed: 204, // ~80% of 255
-            soil_low_threshold: Number::from_num(100), // Wet (Stop)
-            soil_high_threshold: Number::from_num(220), // Dry (Start)
-            water_cal_no_tray: Number::from_num(3000), 
-            water_cal_dry_tray: Number::from_num(2200),
-            water_cal_wet_tray: Number::from_num(1200),
-            ec_low_threshold: Number::from_num(650.0), // ppm
-            ec_high_threshold: Number::from_num(750.0), // ppm
-        }
-    }
-}
-
-
-
-// NTC Sensor Indices
-pub const NTC_PELTIER_INNER: usize = 0;
-pub const NTC_PELTIER_OUTER: usize = 1;
-pub const NTC_PELTIER_HUM_COLD: usize = 2;
-pub const NTC_PELTIER_HUM_HOT: usize = 3;
-
-
-
-/// Target state determined by the script engine
-#[derive(Clone, Debug, Default)]
-pub struct TargetState {
-    pub temp: Number,
-    pub humidity: u8,
-    pub vent_on: bool,
-    pub light_intensity: u8,
-}
-
-impl defmt::Format for TargetState {
-    fn format(&self, f: defmt::Formatter) {
-        defmt::write!(f, "TargetState {{ temp: {}, humidity: {}, vent_on: {}, light_intensity: {} }}", 
-            self.temp.to_num::<f32>(), 
-            self.humidity, 
-            self.vent_on, 
-            self.light_intensity
-        );
-    }
-}
+mod types;
+pub use types::*;
 
 mod adaptive_tuner;
-use adaptive_tuner::AdaptiveTuner;
-
-use crate::sensor_manager::SensorData;
+use adaptive_tuner::{AdaptiveTuner, TunerEvent};
+pub use adaptive_tuner::{GainChange, SharedTunerLog, TunedLoop};
+mod pid;
+use pid::LimitedPid;
+mod overrides;
+pub use overrides::*;
+mod autotune;
+pub use autotune::*;
+mod defrost;
+use defrost::FrostMonitor;
+mod supervisor;
+pub use supervisor::*;
+mod pump_guard;
+pub use pump_guard::{BudgetEvent, PumpGuard};
+mod dosing;
+use dosing::EcDoser;
+
+/// Peltier PWM range, positive = heat
+const PELTIER_PWM_MAX: i32 = 255;
+/// LED intensity at the 75 % PWM cap in the hardware manager, more changes nothing
+const LED_INTENSITY_MAX: i32 = 191;
+
+use crate::sensor_manager::{vapor_pressure, vapor_pressure_deficit, SensorData};
 use crate::hardware_manager::{ ActuatorOutputs};
 
 /// Main Controller Struct
 pub struct PlantController {
     config: ControlConfig,
+    stored_config: ControlConfig, // Last config from update_config, `config` may hold newer tuned gains
     
     // PID Controllers (piddiy)
-    pid_air_temp: PidController<Number, Number>,
-    pid_peltier_temp: PidController<Number, Number>,
+    pid_air_temp: LimitedPid,
+    pid_peltier_temp: LimitedPid,
+    peltier_heating: bool, // Which gain set pid_peltier_temp is using
     pid_hum_cold: PidController<Number, Number>,
     pid_fan_temp_outer: PidController<Number, Number>,
     pid_fan_hum_hot: PidController<Number, Number>,
+    pid_light: LimitedPid, // LED intensity holding the target PPFD
+
+    // Adaptive Tuners
+    tuner_air_temp: AdaptiveTuner,
+    tuner_hum_cold: AdaptiveTuner,
+    gain_changes: Vec<GainChange>,
+    tuned_gains_settled: bool,
 
-    // Adaptive Tuner
-    adaptive_tuner: AdaptiveTuner,
+    // Relay autotune of the temperature Peltier, overrides temperature control while running
+    autotune: Option<RelayAutotune>,
+    autotune_status: AutotuneStatus,
+
+    // Ice on the humidity cold side
+    frost: FrostMonitor,
+
+    // EC dosing towards `PlantConfiguration.nominal_ec`
+    ec_doser: EcDoser,
+    ec_target: Option<Number>,
 
     // Slew Limiters
     fan_inner_speed: Number,
@@ -160,19 +74,20 @@ pub struct PlantController {
     pump_nutrient_active: bool,
     pump_water_active: bool,
     dehumidifier_active: bool, // Last known state
+    co2_vent_active: bool,
     prev_tray_sensor: Number,
     safety_lockout: u8,
 }
 
 impl PlantController {
     pub fn new(config: ControlConfig) -> Self {
-        let mut pid_air_temp = PidController::new();
-        config.air_temp.apply_to(&mut pid_air_temp);
-        pid_air_temp.compute_fn(PidController::default_compute);
+        // Output limits of the air loop depend on the target, set every step
+        let pid_air_temp = LimitedPid::new(config.air_temp, Number::ZERO, Number::ZERO);
+
+        let pwm_max = Number::from_num(PELTIER_PWM_MAX);
+        let pid_peltier_temp = LimitedPid::new(config.peltier_temp_heat, -pwm_max, pwm_max); // Default to heat gains
 
-        let mut pid_peltier_temp = PidController::new();
-        config.peltier_temp_heat.apply_to(&mut pid_peltier_temp); // Default to heat gains
-        pid_peltier_temp.compute_fn(PidController::default_compute);
+        let pid_light = LimitedPid::new(config.light_ppfd, Number::ZERO, Number::from_num(LED_INTENSITY_MAX));
 
         let mut pid_hum_cold = PidController::new();
         config.hum_cold_side.apply_to(&mut pid_hum_cold);
@@ -188,18 +103,31 @@ impl PlantController {
 
         Self {
             config,
+            stored_config: config,
             pid_air_temp,
             pid_peltier_temp,
+            peltier_heating: true,
             pid_hum_cold,
             pid_fan_temp_outer,
             pid_fan_hum_hot,
-            adaptive_tuner: AdaptiveTuner::new(300), // Update every 300 steps (e.g., 30 seconds at 10Hz)
+            pid_light,
+            // Update every 300 steps (e.g., 30 seconds at 10Hz)
+            tuner_air_temp: AdaptiveTuner::new(TunedLoop::AirTemp, 300),
+            tuner_hum_cold: AdaptiveTuner::new(TunedLoop::HumColdSide, 300),
+            gain_changes: Vec::new(),
+            tuned_gains_settled: false,
+            autotune: None,
+            autotune_status: AutotuneStatus::Idle,
+            frost: FrostMonitor::default(),
+            ec_doser: EcDoser::default(),
+            ec_target: None,
             fan_inner_speed: Number::from_num(0),
             fan_temp_outer_speed: Number::from_num(0),
             fan_hum_hot_speed: Number::from_num(0),
             pump_nutrient_active: false,
             pump_water_active: false,
             dehumidifier_active: true,
+            co2_vent_active: false,
             prev_tray_sensor: Number::from_num(0),
             safety_lockout: 0,
         }
@@ -209,43 +137,195 @@ impl PlantController {
         self.config
     }
 
+    /// EC to dose towards, `PlantConfiguration.nominal_ec`. Not positive = no dosing.
+    pub fn set_ec_target(&mut self, nominal_ec: f32) {
+        self.ec_target = (nominal_ec > 0.0).then(|| Number::saturating_from_num(nominal_ec));
+    }
+
     pub fn update_config(&mut self, new_config: ControlConfig) {
+        // Called every cycle with the stored calibration, only act on changes so tuned gains
+        // that are not saved yet are kept
+        if new_config == self.stored_config {
+            return;
+        }
+        self.stored_config = new_config;
+        if new_config != self.config {
+            // Edited by the user, drop what the tuners did since the last save
+            self.tuner_air_temp.discard_changes();
+            self.tuner_hum_cold.discard_changes();
+        }
+
         self.config = new_config;
-        new_config.air_temp.apply_to(&mut self.pid_air_temp);
+        self.pid_air_temp.set_gains(new_config.air_temp);
+        self.pid_peltier_temp.set_gains(if self.peltier_heating {
+            new_config.peltier_temp_heat
+        } else {
+            new_config.peltier_temp_cool
+        });
         new_config.hum_cold_side.apply_to(&mut self.pid_hum_cold);
         new_config.fan_temp_outer.apply_to(&mut self.pid_fan_temp_outer);
         new_config.fan_hum_hot.apply_to(&mut self.pid_fan_hum_hot);
+        self.pid_light.set_gains(new_config.light_ppfd);
     }
 
 
 
+    /// Gain changes the tuners made since the last call.
+    pub fn take_gain_changes(&mut self) -> Vec<GainChange> {
+        core::mem::take(&mut self.gain_changes)
+    }
+
+    /// True once after tuned gains have stopped changing, they should be saved then.
+    pub fn take_tuned_gains_settled(&mut self) -> bool {
+        core::mem::take(&mut self.tuned_gains_settled)
+    }
+
+    pub fn handle_autotune_command(&mut self, command: AutotuneCommand) {
+        match command {
+            AutotuneCommand::Start(settings) => {
+                defmt::info!("Autotune started");
+                let autotune = RelayAutotune::new(settings);
+                self.autotune_status = autotune.status();
+                self.autotune = Some(autotune);
+            }
+            AutotuneCommand::Abort => {
+                if self.autotune.take().is_some() {
+                    self.autotune_status = AutotuneStatus::Aborted { reason: "aborted by user" };
+                }
+            }
+            AutotuneCommand::Clear => {
+                if self.autotune.is_none() {
+                    self.autotune_status = AutotuneStatus::Idle;
+                }
+            }
+        }
+        self.reset_temperature_loops();
+    }
+
+    pub fn autotune_status(&self) -> AutotuneStatus {
+        self.autotune_status
+    }
+
+    /// One relay step, None once the experiment is over.
+    fn step_autotune(&mut self, sensors: &SensorData) -> Option<Number> {
+        let autotune = self.autotune.as_mut()?;
+        let air_temp = sensors.internal.map(|r| r.temp);
+        match autotune.step(sensors.ntc_temps[NTC_PELTIER_INNER], air_temp, &self.config) {
+            AutotuneStep::Output(pwm) => {
+                self.autotune_status = autotune.status();
+                return Some(pwm);
+            }
+            AutotuneStep::Done(result) => {
+                defmt::info!("Autotune done: Ku {} Pu {} steps ({} s)", result.ku, result.pu_steps, result.pu_secs);
+                self.autotune_status = AutotuneStatus::Done { result };
+            }
+            AutotuneStep::Abort(reason) => {
+                defmt::warn!("Autotune aborted: {}", reason);
+                self.autotune_status = AutotuneStatus::Aborted { reason };
+            }
+        }
+        self.autotune = None;
+        self.reset_temperature_loops();
+        None
+    }
+
+    fn tune(&mut self, control_loop: TunedLoop, error: Number) {
+        let tuner = match control_loop {
+            TunedLoop::AirTemp => &mut self.tuner_air_temp,
+            TunedLoop::HumColdSide => &mut self.tuner_hum_cold,
+        };
+        if !self.config.tuner_enabled {
+            tuner.reset();
+            return;
+        }
+
+        let mut gains = *control_loop.gains_mut(&mut self.config);
+        match tuner.update(error, &mut gains, &self.config) {
+            Some(TunerEvent::Changed(change)) => {
+                defmt::info!(
+                    "Tuner {}: kp/ki/kd {} -> {} (rms error {}, {} crossings)",
+                    control_loop, change.old, change.new, change.rms_error, change.crossings
+                );
+                *control_loop.gains_mut(&mut self.config) = gains;
+                match control_loop {
+                    TunedLoop::AirTemp => self.pid_air_temp.set_gains(gains),
+                    TunedLoop::HumColdSide => gains.apply_to(&mut self.pid_hum_cold),
+                }
+                self.gain_changes.push(change);
+            }
+            Some(TunerEvent::Settled) => self.tuned_gains_settled = true,
+            None => {}
+        }
+    }
+
     // determine_targets removed, targets passed in step
 
 
     fn control_humidity(&mut self, sensors: &SensorData, targets: &TargetState) -> (Number, Number) {
-        let current_hum = if let Some(reading) = sensors.internal {
-            Number::from_num(reading.hum)
+        let mode = self.config.modes.humidity;
+        // The tuner only learns while the cold-side PID is running
+        if mode != ControlMode::Pid || !self.dehumidifier_active {
+            self.tuner_hum_cold.reset();
+        }
+        match mode {
+            ControlMode::Off => return (Number::from_num(0), Number::from_num(0)),
+            ControlMode::Manual(pwm) => {
+                let pwm = Number::from_num(pwm.clamp(0, 255));
+                return (pwm, if pwm > 0 { Number::from_num(1) } else { Number::from_num(0) });
+            }
+            ControlMode::Hysteresis | ControlMode::Pid => {}
+        }
+
+        let internal = if let Some(reading) = sensors.internal {
+            reading
         } else {
             return (Number::from_num(0), Number::from_num(0));
         };
 
-        let hum_cold_temp = if let Some(ntc_temps) = sensors.ntc_temps {
-            ntc_temps[NTC_PELTIER_HUM_COLD]
+        let hum_cold_temp = if let Some(temp) = sensors.ntc_temps[NTC_PELTIER_HUM_COLD] {
+            temp
         } else {
             return (Number::from_num(0), Number::from_num(0));
         };
 
-        let setpoint = Number::from_num(targets.humidity);
-        
-        let hysteresis = Number::from_num(10);
-        if current_hum > setpoint + hysteresis {
-            self.dehumidifier_active = true;
-        } else if current_hum < setpoint - hysteresis {
-            self.dehumidifier_active = false;
+        let was_running = self.dehumidifier_active;
+
+        if let Some(target_vpd) = targets.vpd {
+            // Low VPD = too humid. Leaf VPD, since that is what drives transpiration
+            let air_temp: f32 = internal.temp.to_num();
+            let leaf_temp = air_temp - self.config.leaf_temp_offset.to_num::<f32>();
+            let vpd = Number::from_num(vapor_pressure_deficit(air_temp, internal.hum as f32, leaf_temp));
+            let hysteresis = self.config.vpd_hysteresis;
+            if vpd < target_vpd - hysteresis {
+                self.dehumidifier_active = true;
+            } else if vpd > target_vpd + hysteresis {
+                self.dehumidifier_active = false;
+            }
+        } else {
+            let current_hum = Number::from_num(internal.hum);
+            let setpoint = Number::from_num(targets.humidity);
+
+            let hysteresis = Number::from_num(10);
+            if current_hum > setpoint + hysteresis {
+                self.dehumidifier_active = true;
+            } else if current_hum < setpoint - hysteresis {
+                self.dehumidifier_active = false;
+            }
+        }
+
+        let hot_temp = sensors.ntc_temps[NTC_PELTIER_HUM_HOT];
+        if self.frost.update(hum_cold_temp, hot_temp, was_running, &self.config) {
+            self.tuner_hum_cold.reset();
+            return (Number::from_num(0), Number::from_num(0));
         }
 
-        if self.dehumidifier_active {
-            self.pid_hum_cold.set_point(self.config.hum_cold_target);
+        if self.dehumidifier_active && mode == ControlMode::Hysteresis {
+            (Number::from_num(255), Number::from_num(1))
+        } else if self.dehumidifier_active {
+            // Just cold enough to condense, colder only costs energy and risks ice
+            let cold_target = self.config.hum_cold_setpoint(sensors.dew_point);
+            self.tune(TunedLoop::HumColdSide, cold_target - hum_cold_temp);
+            self.pid_hum_cold.set_point(cold_target);
             
             let effort = self.pid_hum_cold.compute(hum_cold_temp);
             let pwm = (-effort).clamp(Number::from_num(0), Number::from_num(255));
@@ -255,13 +335,58 @@ impl PlantController {
         }
     }
 
-    fn control_ventilation(&self, sensors: &SensorData, targets: &TargetState) -> bool {
-        targets.vent_on
+    fn control_ventilation(&mut self, sensors: &SensorData, targets: &TargetState) -> bool {
+        // VPD too low: also bring in outside air if it carries less water than the chamber air
+        let vpd_assist = targets.vpd.is_some()
+            && matches!(self.config.modes.humidity, ControlMode::Hysteresis | ControlMode::Pid)
+            && self.dehumidifier_active
+            && match (sensors.internal, sensors.external) {
+                (Some(inside), Some(outside)) => {
+                    vapor_pressure(outside.temp.to_num(), outside.hum as f32)
+                        < vapor_pressure(inside.temp.to_num(), inside.hum as f32)
+                }
+                _ => false,
+            };
+
+        // CO2 out of its band: swap in outside air until it is back in by the hysteresis
+        let c = &self.config;
+        self.co2_vent_active = sensors.co2_level.is_some_and(|co2| {
+            let margin = if self.co2_vent_active { c.co2_vent_hysteresis } else { Number::ZERO };
+            let too_low = c.co2_vent_low > Number::ZERO && co2 < c.co2_vent_low + margin;
+            let too_high = c.co2_vent_high > Number::ZERO && co2 > c.co2_vent_high - margin;
+            too_low || too_high
+        });
+
+        targets.vent_on || vpd_assist || self.co2_vent_active
+    }
+
+    fn control_light(&mut self, sensors: &SensorData, targets: &TargetState) -> u8 {
+        match (self.config.modes.light, sensors.ppfd) {
+            (ControlMode::Off, _) => 0,
+            (ControlMode::Manual(level), _) => level.clamp(0, 255) as u8,
+            // Closed loop: the target intensity scales `light_ppfd_target`, the loop trims out
+            // LED aging and daylight. Open loop while lights are off or the sensor is not reading.
+            (ControlMode::Pid, Some(ppfd)) if targets.light_intensity > 0 => {
+                let share = Number::from_num(targets.light_intensity) / Number::from_num(255);
+                let setpoint = self.config.light_ppfd_target.saturating_mul(share);
+                self.pid_light.update(setpoint, ppfd).to_num::<i32>().clamp(0, LED_INTENSITY_MAX) as u8
+            }
+            (ControlMode::Hysteresis | ControlMode::Pid, _) => {
+                self.pid_light.reset();
+                targets.light_intensity
+            }
+        }
     }
 
     fn control_soil_moisture(&mut self, sensors: &SensorData) -> bool {
         // Renaming: This manages the Water Tray Level using what was called "soil" sensor (ADC).
         // Pump: Pump Water (GPIO22) based on user feedback.
+        let mode = self.config.modes.tray_water;
+        if mode == ControlMode::Off {
+            self.pump_water_active = false;
+            return false;
+        }
+
         let tray_sensor = if let Some(val) = sensors.soil_moisture {
             Number::from_num(val)
         } else {
@@ -309,6 +434,8 @@ impl PlantController {
         if tray_sensor > limit_safety {
              // Safety: No Tray detected (Voltage too high). Force OFF.
              self.pump_water_active = false;
+        } else if let ControlMode::Manual(on) = mode {
+             self.pump_water_active = on != 0;
         } else if tray_sensor > limit_start {
              // Dry Zone: Start Pump.
              self.pump_water_active = true;
@@ -329,19 +456,19 @@ impl PlantController {
     }
 
     fn control_ec_mode(&mut self, sensors: &SensorData) -> bool {
-        let ec = if let Some(val) = sensors.ec_level {
-            Number::from_num(val)
-        } else {
-            self.pump_nutrient_active = false;
-            return false;
+        self.pump_nutrient_active = match (self.config.modes.ec, self.ec_target) {
+            (ControlMode::Manual(on), _) => {
+                self.ec_doser.stop(&self.config);
+                on != 0
+            }
+            (ControlMode::Hysteresis | ControlMode::Pid, Some(target)) => {
+                self.ec_doser.step(sensors.ec_level, target, &self.config)
+            }
+            (ControlMode::Off, _) | (_, None) => {
+                self.ec_doser.stop(&self.config);
+                false
+            }
         };
-        
-        if ec > self.config.ec_high_threshold {
-            self.pump_nutrient_active = true; // Too salty, add water (on other pump?) Swapped logic.
-        } else if ec < self.config.ec_low_threshold {
-            self.pump_nutrient_active = false; // OK, stop water
-        }
-        
         self.pump_nutrient_active
     }
 
@@ -352,9 +479,30 @@ impl PlantController {
         hum_peltier_pwm: Number, 
         vent_on: bool
     ) -> (Number, Number) {
+        if let Some(pwm) = self.step_autotune(sensors) {
+            let fan_target = if pwm == Number::from_num(0) { self.config.fan_base_day } else { Number::from_num(255) };
+            return (pwm, fan_target);
+        }
+
+        match self.config.modes.temperature {
+            ControlMode::Off => {
+                self.reset_temperature_loops();
+                return (Number::from_num(0), self.config.fan_base_day);
+            }
+            ControlMode::Manual(pwm) => {
+                // Overheat cutoff still applies in post_process
+                self.reset_temperature_loops();
+                let pwm = Number::from_num(pwm.clamp(-PELTIER_PWM_MAX as i16, PELTIER_PWM_MAX as i16));
+                let fan_target = if pwm == Number::from_num(0) { self.config.fan_base_day } else { Number::from_num(255) };
+                return (pwm, fan_target);
+            }
+            ControlMode::Hysteresis | ControlMode::Pid => {}
+        }
+
         let internal_temp = if let Some(reading) = sensors.internal {
             reading.temp
         } else {
+            self.reset_temperature_loops();
             return (Number::from_num(0), Number::from_num(0));
         };
 
@@ -364,59 +512,120 @@ impl PlantController {
             Number::from_num(25.0) // Assume standard ambient if missing
         };
 
-        let ntc_temps = if let Some(temps) = sensors.ntc_temps {
-            temps
+        let peltier_inner_temp = if let Some(temp) = sensors.ntc_temps[NTC_PELTIER_INNER] {
+            temp
         } else {
+            self.reset_temperature_loops();
             return (Number::from_num(0), Number::from_num(0));
         };
-        let peltier_inner_temp = ntc_temps[NTC_PELTIER_INNER];
 
         let target_temp = targets.temp;
 
-        // Bang-Bang Control (Toggle Logic)
-        let error = target_temp - internal_temp;
-        
-        // Deadband Gating (0.5 C)
-        if error.abs() < Number::from_num(0.5) {
-             // Inside Deadband: OFF
-             // Reset integral not needed as we aren't using PID, but good practice if we switch back.
-             self.pid_air_temp.reset_integral(); 
-             return (Number::from_num(0), self.config.fan_base_day);
-        }
-        
-        // Active Control: Full Power
-        let peltier_pwm = if error > Number::from_num(0) {
-            Number::from_num(255) // Heat
+        let peltier_pwm = if self.config.modes.temperature == ControlMode::Hysteresis {
+            // PID state would be stale by the time it is switched back on
+            self.reset_temperature_loops();
+
+            // Bang-Bang Control (Toggle Logic)
+            let error = target_temp - internal_temp;
+
+            // Deadband Gating (0.5 C)
+            if error.abs() < Number::from_num(0.5) {
+                // Inside Deadband: OFF
+                return (Number::from_num(0), self.config.fan_base_day);
+            }
+
+            // Active Control: Full Power
+            if error > Number::from_num(0) {
+                Number::from_num(PELTIER_PWM_MAX) // Heat
+            } else {
+                Number::from_num(-PELTIER_PWM_MAX) // Cool
+            }
         } else {
-            Number::from_num(-255) // Cool
+            self.cascade_temperature(target_temp, internal_temp, external_temp, peltier_inner_temp, vent_on)
         };
-        
+
+        if peltier_pwm == Number::from_num(0) {
+            return (peltier_pwm, self.config.fan_base_day);
+        }
+
         // Fans full speed when Peltier is on
         let fan_target = Number::from_num(255);
         
         (peltier_pwm, fan_target)
     }
 
-    fn control_aux_fans(&mut self, sensors: &SensorData) -> (Number, Number) {
-        if let Some(ntc_temps) = sensors.ntc_temps {
-            let t_peltier_inner = ntc_temps[NTC_PELTIER_INNER];
-            let t_peltier_outer = ntc_temps[NTC_PELTIER_OUTER];
-            let t_hum_cold = ntc_temps[NTC_PELTIER_HUM_COLD];
-            let t_hum_hot = ntc_temps[NTC_PELTIER_HUM_HOT];
-
-            // Calculate differences (Hot - Cold)
-            let diff_temp = (t_peltier_outer - t_peltier_inner).abs();
-            let diff_hum = (t_hum_hot - t_hum_cold).abs();
+    /// Cascade: the air loop picks a Peltier inner-surface setpoint within
+    /// [peltier_temp_min, peltier_temp_max], the Peltier loop turns that into PWM.
+    fn cascade_temperature(
+        &mut self,
+        target_temp: Number,
+        internal_temp: Number,
+        external_temp: Number,
+        peltier_inner_temp: Number,
+        vent_on: bool,
+    ) -> Number {
+        let t_min = self.config.peltier_temp_min;
+        let t_max = self.config.peltier_temp_max;
+
+        // Primary: output is the surface offset from the air target
+        self.pid_air_temp.set_limits(t_min - target_temp, t_max - target_temp);
+        self.tune(TunedLoop::AirTemp, target_temp - internal_temp);
+        let mut offset = self.pid_air_temp.update(target_temp, internal_temp);
+
+        // Feedforward: pre-condition fresh air blown across the heatsink (control_algorithm.md)
+        if vent_on {
+            offset += self.config.k_ff_vent * (target_temp - external_temp);
+        }
+        let peltier_setpoint = (target_temp + offset).clamp(t_min, t_max);
+
+        // Secondary: heat and cool gains differ, restart the integral when switching
+        let heating = peltier_setpoint >= internal_temp;
+        if heating != self.peltier_heating {
+            self.peltier_heating = heating;
+            self.pid_peltier_temp.set_gains(if heating {
+                self.config.peltier_temp_heat
+            } else {
+                self.config.peltier_temp_cool
+            });
+            self.pid_peltier_temp.reset();
+        }
 
-            self.pid_fan_temp_outer.set_point(self.config.peltier_temp_diff_target);
-            
-            let effort_temp = self.pid_fan_temp_outer.compute(diff_temp - self.config.peltier_temp_diff_target);
-            let effort_hum = self.pid_fan_hum_hot.compute(diff_hum - self.config.peltier_temp_diff_target);
+        let mut pwm = self.pid_peltier_temp.update(peltier_setpoint, peltier_inner_temp);
 
-            (effort_temp, effort_hum)
-        } else {
-            (Number::from_num(0), Number::from_num(0))
+        // Never push the surface further past its limits
+        if (peltier_inner_temp >= t_max && pwm > Number::ZERO) || (peltier_inner_temp <= t_min && pwm < Number::ZERO) {
+            pwm = Number::ZERO;
         }
+        pwm
+    }
+
+    fn reset_temperature_loops(&mut self) {
+        self.pid_air_temp.reset();
+        self.pid_peltier_temp.reset();
+        self.tuner_air_temp.reset();
+    }
+
+    fn control_aux_fans(&mut self, sensors: &SensorData) -> (Number, Number) {
+        let ntc = &sensors.ntc_temps;
+
+        // Calculate differences (Hot - Cold), each pair only if both sides read
+        self.pid_fan_temp_outer.set_point(self.config.peltier_temp_diff_target);
+        let effort_temp = match (ntc[NTC_PELTIER_INNER], ntc[NTC_PELTIER_OUTER]) {
+            (Some(inner), Some(outer)) => {
+                let diff_temp = (outer - inner).abs();
+                self.pid_fan_temp_outer.compute(diff_temp - self.config.peltier_temp_diff_target)
+            }
+            _ => Number::from_num(0),
+        };
+        let effort_hum = match (ntc[NTC_PELTIER_HUM_COLD], ntc[NTC_PELTIER_HUM_HOT]) {
+            (Some(cold), Some(hot)) => {
+                let diff_hum = (hot - cold).abs();
+                self.pid_fan_hum_hot.compute(diff_hum - self.config.peltier_temp_diff_target)
+            }
+            _ => Number::from_num(0),
+        };
+
+        (effort_temp, effort_hum)
     }
 
     fn apply_slew_limits(&mut self, fan_inner_target: Number, fan_temp_outer_target: Number, fan_hum_hot_target: Number) {
@@ -441,49 +650,28 @@ impl PlantController {
         // dir=1 (Heat): PWM 100% -> Off, PWM 0% -> On (Active Low) or depends on driver.
         // Assuming Simple HardwareManager implementation: Dir Pin + PWM Pin.
         // Positive = Heat, Negative = Cool
-        
-        let mut peltier_active = false;
-
-        let (temp_dir, temp_pwm_u8) = if let Some(ntc_temps) = sensors.ntc_temps {
-            let temp = ntc_temps[NTC_PELTIER_INNER];
-            let mut magnitude = peltier_temp_pwm.abs().clamp(Number::from_num(0), Number::from_num(255)).to_num::<u8>();
-            
-            // Safety: Overheat protection
-            if temp > Number::from_num(60) {
-                magnitude = 0;
-            }
-
-            if magnitude > 0 {
-                peltier_active = true;
-            }
-
-            if peltier_temp_pwm >= Number::from_num(0) {
-                (true, magnitude)
-            } else {
-                (false, magnitude)
-            }
-        } else {
-            // Sensor Fail -> OFF
-            (false, 0)
+        let mut outputs = ActuatorOutputs {
+            peltier_temp_pwm: peltier_temp_pwm.abs().clamp(Number::from_num(0), Number::from_num(255)).to_num::<u8>(),
+            peltier_temp_dir: peltier_temp_pwm >= Number::from_num(0),
+            peltier_hum_pwm: hum_peltier_pwm.clamp(Number::from_num(0), Number::from_num(255)).to_num::<u8>(),
+            fan_vent_on: vent_on,
+            led_intensity: light_intensity,
+            pump_nutrient: pump_nutrient_active,
+            pump_water: pump_water_active,
+            ..ActuatorOutputs::default()
         };
+        Self::apply_safety_limits(&mut outputs, sensors);
 
-        let peltier_hum_u8 = if let Some(ntc_temps) = sensors.ntc_temps {
-            let temp = ntc_temps[NTC_PELTIER_HUM_HOT];
-            if temp > Number::from_num(70) {
-                0
-            } else {
-                hum_peltier_pwm.to_num::<u8>()
-            }
-        } else {
-            0
-        };
+        let peltier_active = outputs.peltier_temp_pwm > 0;
+        let peltier_hum_u8 = outputs.peltier_hum_pwm;
 
         // Fixed Fan Speed Logic: "only when peltier is on, otherwise off"
         // inner: 50% (128), outer: 100% (255)
-        let (fan_inner, fan_temp_outer_speed) = if peltier_active {
-             (255, 255)
-        } else {
-             (0, 0)
+        let (fan_inner, fan_temp_outer_speed) = match self.config.modes.fans {
+            ControlMode::Off => (0, 0),
+            ControlMode::Manual(speed) => (speed.clamp(0, 255) as u8, speed.clamp(0, 255) as u8),
+            ControlMode::Hysteresis | ControlMode::Pid if peltier_active => (255, 255),
+            ControlMode::Hysteresis | ControlMode::Pid => (0, 0),
         };
         
         // Use hum_fan_speed or similar for humidfier? 
@@ -495,21 +683,38 @@ impl PlantController {
         // Let's keep `fan_hum_hot` as calculated by PID for now, or zero it if not needed?
         // User didn't specify fixed speed for hum fan, only "inner" and "outer" which likely refers to temp peltier.
         
+        // The hardware keeps a minimum hot-side fan speed while the humidity Peltier runs
         let max_fan = Number::from_num(self.config.max_fan_speed);
-        let fan_hum_hot_speed: u8 = self.fan_hum_hot_speed.clamp(Number::from_num(0), max_fan).to_num();
+        let fan_hum_hot_speed: u8 = match self.config.modes.fans {
+            ControlMode::Off => 0,
+            ControlMode::Manual(speed) => speed.clamp(0, 255) as u8,
+            ControlMode::Hysteresis if peltier_hum_u8 > 0 => self.config.max_fan_speed,
+            ControlMode::Hysteresis => 0,
+            ControlMode::Pid => self.fan_hum_hot_speed.clamp(Number::from_num(0), max_fan).to_num(),
+        };
 
 
-        ActuatorOutputs {
-            peltier_temp_pwm: temp_pwm_u8,
-            peltier_temp_dir: temp_dir,
-            peltier_hum_pwm: peltier_hum_u8,
-            fan_inner_speed: fan_inner,
-            fan_temp_outer_speed,
-            fan_hum_hot_speed,
-            fan_vent_on: vent_on,
-            led_intensity: light_intensity,
-            pump_nutrient: pump_nutrient_active,
-            pump_water: pump_water_active,
+        outputs.fan_inner_speed = fan_inner;
+        outputs.fan_temp_outer_speed = fan_temp_outer_speed;
+        outputs.fan_hum_hot_speed = fan_hum_hot_speed;
+        outputs
+    }
+
+    /// Overheat and sensor-failure cutoffs of the Peltiers. Applied again after manual overrides.
+    pub fn apply_safety_limits(outputs: &mut ActuatorOutputs, sensors: &SensorData) {
+        // Temperature Peltier: overheat protection, sensor fail -> OFF
+        match sensors.ntc_temps[NTC_PELTIER_INNER] {
+            Some(temp) if temp <= Number::from_num(60) => {}
+            Some(_) => outputs.peltier_temp_pwm = 0,
+            None => {
+                outputs.peltier_temp_pwm = 0;
+                outputs.peltier_temp_dir = false;
+            }
+        }
+
+        // Humidity Peltier: hot side overheat, sensor fail -> OFF
+        if !matches!(sensors.ntc_temps[NTC_PELTIER_HUM_HOT], Some(temp) if temp <= Number::from_num(70)) {
+            outputs.peltier_hum_pwm = 0;
         }
     }
 
@@ -522,14 +727,15 @@ impl PlantController {
 
         let (hum_peltier_pwm, hum_fan_speed) = self.control_humidity(sensors, &targets);
         let vent_on = self.control_ventilation(sensors, &targets);
+        let light_intensity = self.control_light(sensors, &targets);
         
         // Map Tray Logic to GPIO 21 (Water Pump)
         // In HW Manager, 'pump_nutrient' is GPIO 21. 
         // control_soil_moisture returns the desired state for the tray pump.
         let pump_nutrient_active = self.control_soil_moisture(sensors);
         
-        let _ = self.control_ec_mode(sensors); 
-        let pump_water_active = false; // GPIO 22 Unused
+        // GPIO 22: EC dosing pump (water or concentrate, see `ec_dose_direction`), Off until plumbed in
+        let pump_water_active = self.control_ec_mode(sensors);
         
         let (fan_temp_outer_effort, fan_hum_hot_effort) = self.control_aux_fans(sensors);
 
@@ -546,7 +752,7 @@ impl PlantController {
             peltier_temp_pwm, 
             hum_peltier_pwm, 
             vent_on, 
-            targets.light_intensity,
+            light_intensity,
             pump_nutrient_active,
             pump_water_active,
             sensors
@@ -1,48 +1,114 @@
+use alloc::rc::Rc;
 use arraydeque::{ArrayDeque, Wrapping};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
 use fixed::types::I16F16;
-use super::PidGains;
+use heapless::Deque;
+use num_traits::Float;
+use serde::Serialize;
+use super::{ControlConfig, PidGains};
 
 type Number = I16F16;
 
+/// Evaluation windows without a gain change before the gains count as settled
+const STABLE_WINDOWS: u8 = 3;
+
+/// Loops the tuner adjusts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, defmt::Format)]
+pub enum TunedLoop {
+    AirTemp,
+    HumColdSide,
+}
+
+impl TunedLoop {
+    pub fn gains_mut(self, config: &mut ControlConfig) -> &mut PidGains {
+        match self {
+            TunedLoop::AirTemp => &mut config.air_temp,
+            TunedLoop::HumColdSide => &mut config.hum_cold_side,
+        }
+    }
+}
+
+/// One gain change made by the tuner, kept for `/api/tuner`
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct GainChange {
+    pub ts: u64, // Unix time, 0 if the clock is not set
+    pub control_loop: TunedLoop,
+    pub old: [f32; 3], // kp, ki, kd
+    pub new: [f32; 3],
+    pub rms_error: f32,
+    pub crossings: u32,
+}
+
+pub type SharedTunerLog = Rc<Mutex<CriticalSectionRawMutex, Deque<GainChange, 16>>>;
+
+/// What one `update` call did
+pub enum TunerEvent {
+    Changed(GainChange),
+    /// Gains unchanged for a while after a change, worth saving
+    Settled,
+}
+
 /// Adaptive Tuner for Online PID Adjustment
 pub struct AdaptiveTuner {
+    control_loop: TunedLoop,
+
     // History for analysis (Fixed size buffer, Wrapping behavior)
     error_history: ArrayDeque<Number, 64, Wrapping>,
-    
+
     // Tuning State
     update_counter: u32,
     update_interval: u32,
+    unsaved: bool,
+    stable_windows: u8,
 }
 
 impl AdaptiveTuner {
-    pub fn new(interval: u32) -> Self {
+    pub fn new(control_loop: TunedLoop, interval: u32) -> Self {
         Self {
+            control_loop,
             error_history: ArrayDeque::new(),
             update_counter: 0,
             update_interval: interval,
+            unsaved: false,
+            stable_windows: 0,
         }
     }
 
-    pub fn update(&mut self, current_error: Number, pid_gains: &mut PidGains) {
+    /// Forgets the error history, e.g. while the loop is not running.
+    pub fn reset(&mut self) {
+        self.error_history.clear();
+        self.update_counter = 0;
+        self.stable_windows = 0;
+    }
+
+    /// Forgets changes not yet reported as settled, e.g. when the user set new gains.
+    pub fn discard_changes(&mut self) {
+        self.reset();
+        self.unsaved = false;
+    }
+
+    pub fn update(&mut self, current_error: Number, pid_gains: &mut PidGains, config: &ControlConfig) -> Option<TunerEvent> {
         let _ = self.error_history.push_back(current_error);
-        
+
         self.update_counter += 1;
         if self.update_counter < self.update_interval {
-            return;
+            return None;
         }
         self.update_counter = 0;
 
         // Analysis
         let crossings = self.count_zero_crossings();
         let rms_error = self.calculate_rms_error();
+        let old = *pid_gains;
 
         // 1. Detect Oscillation (Zero Crossings)
         // If we cross zero frequently, we are likely oscillating -> Reduce gains
-        if crossings > 10 { 
+        if crossings > 10 {
              pid_gains.kp *= Number::from_num(0.90);
              pid_gains.kd *= Number::from_num(0.90);
         }
-        
+
         // 2. Detect Sluggishness (High RMS Error without oscillation)
         // If error is consistently high but not oscillating -> Increase gains
         else if rms_error > Number::from_num(1.0) {
@@ -50,10 +116,32 @@ impl AdaptiveTuner {
              // Optionally increase Ki if steady state error persists, but be careful
         }
 
-        // 3. Clamp gains to safe limits (Simple safety)
-        pid_gains.kp = pid_gains.kp.clamp(Number::from_num(0.1), Number::from_num(20.0));
-        pid_gains.ki = pid_gains.ki.clamp(Number::from_num(0.0), Number::from_num(10.0));
-        pid_gains.kd = pid_gains.kd.clamp(Number::from_num(0.0), Number::from_num(10.0));
+        // 3. Clamp gains to the configured limits
+        pid_gains.kp = pid_gains.kp.clamp(config.tuner_kp_min, config.tuner_kp_max);
+        pid_gains.ki = pid_gains.ki.clamp(Number::ZERO, config.tuner_ki_max);
+        pid_gains.kd = pid_gains.kd.clamp(Number::ZERO, config.tuner_kd_max);
+
+        if *pid_gains != old {
+            self.unsaved = true;
+            self.stable_windows = 0;
+            return Some(TunerEvent::Changed(GainChange {
+                ts: 0,
+                control_loop: self.control_loop,
+                old: gains_f32(&old),
+                new: gains_f32(pid_gains),
+                rms_error: rms_error.to_num(),
+                crossings: crossings as u32,
+            }));
+        }
+
+        if self.unsaved {
+            self.stable_windows += 1;
+            if self.stable_windows >= STABLE_WINDOWS {
+                self.unsaved = false;
+                return Some(TunerEvent::Settled);
+            }
+        }
+        None
     }
 
     fn count_zero_crossings(&self) -> usize {
@@ -74,9 +162,12 @@ impl AdaptiveTuner {
         if self.error_history.is_empty() {
             return Number::ZERO;
         }
-        let sum_sq: Number = self.error_history.iter().map(|&x| x * x).sum();
-        // Fallback to MAE for safety/speed on M0+ without checking features deeply
-        let sum_abs: Number = self.error_history.iter().map(|&x| x.abs()).sum();
-        sum_abs / Number::from_num(self.error_history.len())
+        // Squares of large errors overflow I16F16, sum them as f32
+        let sum_sq: f32 = self.error_history.iter().map(|x| x.to_num::<f32>() * x.to_num::<f32>()).sum();
+        Number::saturating_from_num((sum_sq / self.error_history.len() as f32).sqrt())
     }
 }
+
+fn gains_f32(gains: &PidGains) -> [f32; 3] {
+    [gains.kp.to_num(), gains.ki.to_num(), gains.kd.to_num()]
+}
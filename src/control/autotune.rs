@@ -0,0 +1,357 @@
+//! Relay-feedback (Åström–Hägglund) autotune for the temperature Peltier.
+//!
+//! The Peltier is switched between off and `amplitude` PWM around a setpoint a few degrees
+//! away from where it starts. The temperature settles into a limit cycle, from which the
+//! ultimate gain `Ku = 4h / (π·sqrt(a² - ε²))` and the ultimate period `Pu` are read.
+//! Heat and cool gains are tuned in separate runs. Results are only written to the
+//! calibration when the user confirms them.
+
+use alloc::rc::Rc;
+use core::f32::consts::PI;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+
+use super::{ControlConfig, Number, PidGains};
+
+/// Give up if the oscillation has not been measured by then (2 h at 2 steps/s)
+const AUTOTUNE_MAX_STEPS: u32 = 2 * 60 * 60 * 2;
+/// Air temperature range the experiment may push the chamber to
+const AUTOTUNE_AIR_MIN: f32 = 5.0;
+const AUTOTUNE_AIR_MAX: f32 = 40.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutotuneDirection {
+    /// Tunes `peltier_temp_heat`
+    #[default]
+    Heat,
+    /// Tunes `peltier_temp_cool`
+    Cool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutotuneSignal {
+    /// Peltier inner surface NTC, what the secondary loop controls
+    #[default]
+    PeltierNtc,
+    /// Chamber air, slower; use when the NTC is noisy
+    AirTemp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TuningRule {
+    ZieglerNichols,
+    /// Less overshoot, slower
+    TyreusLuyben,
+}
+
+/// Body of `POST /api/autotune/start`, every field optional.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutotuneSettings {
+    pub direction: AutotuneDirection,
+    pub signal: AutotuneSignal,
+    /// Setpoint offset from the starting temperature, C (up to heat, down to cool)
+    pub step: f32,
+    /// Relay output, PWM
+    pub amplitude: u8,
+    /// Relay hysteresis, C, keeps sensor noise from toggling the relay
+    pub hysteresis: f32,
+    /// Oscillation cycles to average (after one discarded settling cycle)
+    pub cycles: u8,
+}
+
+impl Default for AutotuneSettings {
+    fn default() -> Self {
+        Self {
+            direction: AutotuneDirection::Heat,
+            signal: AutotuneSignal::PeltierNtc,
+            step: 5.0,
+            amplitude: 200,
+            hysteresis: 0.2,
+            cycles: 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct AutotuneResult {
+    pub direction: AutotuneDirection,
+    /// Ultimate gain, PWM per C
+    pub ku: f32,
+    /// Ultimate period in control steps, which is what the gains are scaled to
+    pub pu_steps: f32,
+    pub pu_secs: f32,
+    /// Half peak-to-peak of the oscillation, C
+    pub amplitude: f32,
+}
+
+impl AutotuneResult {
+    /// PID gains per control step, like the other loops.
+    pub fn gains(&self, rule: TuningRule) -> PidGains {
+        let (kp, ti, td) = match rule {
+            TuningRule::ZieglerNichols => (0.6 * self.ku, self.pu_steps / 2.0, self.pu_steps / 8.0),
+            TuningRule::TyreusLuyben => (self.ku / 2.2, 2.2 * self.pu_steps, self.pu_steps / 6.3),
+        };
+        PidGains {
+            kp: Number::saturating_from_num(kp),
+            ki: Number::saturating_from_num(kp / ti),
+            kd: Number::saturating_from_num(kp * td),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AutotuneStatus {
+    #[default]
+    Idle,
+    Running {
+        direction: AutotuneDirection,
+        setpoint: f32,
+        /// Measured cycles so far, of `cycles`
+        cycle: u8,
+        cycles: u8,
+        steps: u32,
+    },
+    /// Waiting for the user to confirm or discard
+    Done { result: AutotuneResult },
+    Aborted { reason: &'static str },
+}
+
+pub enum AutotuneCommand {
+    Start(AutotuneSettings),
+    Abort,
+    /// Back to Idle once a result was saved or discarded
+    Clear,
+}
+
+/// Hand-over between the web UI and the control loop: commands in, status out.
+#[derive(Default)]
+pub struct Autotune {
+    pub command: Option<AutotuneCommand>,
+    pub status: AutotuneStatus,
+}
+
+pub type SharedAutotune = Rc<Mutex<CriticalSectionRawMutex, Autotune>>;
+
+pub enum AutotuneStep {
+    /// Peltier PWM for this step, positive heats
+    Output(Number),
+    Done(AutotuneResult),
+    Abort(&'static str),
+}
+
+/// A running relay experiment.
+pub struct RelayAutotune {
+    settings: AutotuneSettings,
+    setpoint: Option<f32>, // Picked on the first step
+    relay_on: bool,
+    steps: u32,
+    last_cycle_start: Option<(u32, Instant)>,
+    cycle_max: f32,
+    cycle_min: f32,
+    cycles_seen: u8,
+    // Sums over the measured cycles
+    period_steps: u32,
+    period_secs: f32,
+    half_swing: f32,
+}
+
+impl RelayAutotune {
+    pub fn new(settings: AutotuneSettings) -> Self {
+        let mut settings = settings;
+        settings.cycles = settings.cycles.clamp(2, 10);
+        settings.hysteresis = settings.hysteresis.max(0.0);
+        Self {
+            settings,
+            setpoint: None,
+            relay_on: true,
+            steps: 0,
+            last_cycle_start: None,
+            cycle_max: f32::MIN,
+            cycle_min: f32::MAX,
+            cycles_seen: 0,
+            period_steps: 0,
+            period_secs: 0.0,
+            half_swing: 0.0,
+        }
+    }
+
+    /// Cycles measured so far, not counting the settling cycle.
+    fn measured(&self) -> u8 {
+        self.cycles_seen.saturating_sub(1)
+    }
+
+    pub fn status(&self) -> AutotuneStatus {
+        AutotuneStatus::Running {
+            direction: self.settings.direction,
+            setpoint: self.setpoint.unwrap_or(0.0),
+            cycle: self.measured(),
+            cycles: self.settings.cycles,
+            steps: self.steps,
+        }
+    }
+
+    pub fn step(&mut self, peltier_ntc: Option<Number>, air_temp: Option<Number>, config: &ControlConfig) -> AutotuneStep {
+        self.steps += 1;
+        if self.steps > AUTOTUNE_MAX_STEPS {
+            return AutotuneStep::Abort("no steady oscillation in time");
+        }
+
+        // Safety limits, same range the cascade keeps the surface in
+        let Some(ntc) = peltier_ntc else {
+            return AutotuneStep::Abort("Peltier NTC not reading");
+        };
+        if ntc > config.peltier_temp_max || ntc < config.peltier_temp_min {
+            return AutotuneStep::Abort("Peltier surface out of range");
+        }
+        if let Some(air) = air_temp
+            && !(AUTOTUNE_AIR_MIN..=AUTOTUNE_AIR_MAX).contains(&air.to_num::<f32>())
+        {
+            return AutotuneStep::Abort("air temperature out of range");
+        }
+
+        let y: f32 = match self.settings.signal {
+            AutotuneSignal::PeltierNtc => ntc.to_num(),
+            AutotuneSignal::AirTemp => match air_temp {
+                Some(air) => air.to_num(),
+                None => return AutotuneStep::Abort("air temperature not reading"),
+            },
+        };
+
+        // Heating relays 0 / +amplitude, cooling 0 / -amplitude; `sign` folds both into one case
+        let sign = match self.settings.direction {
+            AutotuneDirection::Heat => 1.0,
+            AutotuneDirection::Cool => -1.0,
+        };
+        let setpoint = *self.setpoint.get_or_insert(y + sign * self.settings.step);
+        let hyst = self.settings.hysteresis;
+
+        self.cycle_max = self.cycle_max.max(y);
+        self.cycle_min = self.cycle_min.min(y);
+
+        if self.relay_on && sign * (y - setpoint) > hyst {
+            self.relay_on = false;
+        } else if !self.relay_on && sign * (setpoint - y) > hyst {
+            // Relay back on: one full cycle since the last time
+            self.relay_on = true;
+            let now = Instant::now();
+            if let Some((start_step, start_time)) = self.last_cycle_start {
+                self.cycles_seen += 1;
+                // The first cycle still carries the step response, skip it
+                if self.cycles_seen > 1 {
+                    self.period_steps += self.steps - start_step;
+                    self.period_secs += now.saturating_duration_since(start_time).as_millis() as f32 / 1000.0;
+                    self.half_swing += (self.cycle_max - self.cycle_min) / 2.0;
+                }
+            }
+            self.last_cycle_start = Some((self.steps, now));
+            self.cycle_max = y;
+            self.cycle_min = y;
+
+            if self.measured() >= self.settings.cycles {
+                return self.finish();
+            }
+        }
+
+        let pwm = if self.relay_on { sign * self.settings.amplitude as f32 } else { 0.0 };
+        AutotuneStep::Output(Number::from_num(pwm))
+    }
+
+    fn finish(&self) -> AutotuneStep {
+        let n = self.measured() as f32;
+        let a = self.half_swing / n;
+        let eps = self.settings.hysteresis;
+        if a <= eps {
+            return AutotuneStep::Abort("oscillation smaller than the hysteresis");
+        }
+        // Relay switches between 0 and amplitude, so its half-amplitude is amplitude / 2
+        let h = self.settings.amplitude as f32 / 2.0;
+        AutotuneStep::Done(AutotuneResult {
+            direction: self.settings.direction,
+            ku: 4.0 * h / (PI * Float::sqrt(a * a - eps * eps)),
+            pu_steps: self.period_steps as f32 / n,
+            pu_secs: self.period_secs / n,
+            amplitude: a,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(ku: f32, pu_steps: f32) -> AutotuneResult {
+        AutotuneResult { direction: AutotuneDirection::Heat, ku, pu_steps, pu_secs: pu_steps / 2.0, amplitude: 1.0 }
+    }
+
+    fn assert_near(actual: Number, expected: f32) {
+        let actual: f32 = actual.to_num();
+        assert!((actual - expected).abs() < 0.001, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn gains_follow_the_tuning_rules() {
+        let zn = result(10.0, 40.0).gains(TuningRule::ZieglerNichols);
+        assert_near(zn.kp, 6.0);
+        assert_near(zn.ki, 6.0 / 20.0);
+        assert_near(zn.kd, 6.0 * 5.0);
+
+        let tl = result(10.0, 40.0).gains(TuningRule::TyreusLuyben);
+        assert_near(tl.kp, 10.0 / 2.2);
+        assert_near(tl.ki, 10.0 / 2.2 / 88.0);
+        assert_near(tl.kd, 10.0 / 2.2 * 40.0 / 6.3);
+    }
+
+    #[test]
+    fn gains_saturate_instead_of_overflowing() {
+        let gains = result(1.0e9, 40.0).gains(TuningRule::ZieglerNichols);
+        assert_eq!(gains.kp, Number::MAX);
+        assert_eq!(gains.kd, Number::MAX);
+    }
+
+    #[test]
+    fn relay_measures_the_limit_cycle() {
+        let settings = AutotuneSettings { cycles: 3, hysteresis: 0.2, amplitude: 200, ..Default::default() };
+        let mut tune = RelayAutotune::new(settings);
+        let config = ControlConfig::default();
+
+        // Surface moves 0.125 C per step towards the relay output, setpoint ends up at 25 C
+        let mut ntc = Number::from_num(20);
+        let result = loop {
+            match tune.step(Some(ntc), None, &config) {
+                AutotuneStep::Output(pwm) if pwm > 0 => ntc += Number::from_num(0.125),
+                AutotuneStep::Output(_) => ntc -= Number::from_num(0.125),
+                AutotuneStep::Done(result) => break result,
+                AutotuneStep::Abort(reason) => panic!("aborted: {}", reason),
+            }
+        };
+
+        // Swings 24.75..25.25 with four steps up and four down
+        assert_eq!(result.pu_steps, 8.0);
+        assert_eq!(result.amplitude, 0.25);
+        let ku = 4.0 * 100.0 / (PI * (0.25f32 * 0.25 - 0.2 * 0.2).sqrt());
+        assert!((result.ku - ku).abs() < 0.01, "{} != {}", result.ku, ku);
+    }
+
+    #[test]
+    fn relay_aborts_outside_the_safe_range() {
+        let config = ControlConfig::default();
+        let mut tune = RelayAutotune::new(AutotuneSettings::default());
+        assert!(matches!(tune.step(None, None, &config), AutotuneStep::Abort(_)));
+
+        let mut tune = RelayAutotune::new(AutotuneSettings::default());
+        let hot = config.peltier_temp_max + Number::from_num(1);
+        assert!(matches!(tune.step(Some(hot), None, &config), AutotuneStep::Abort(_)));
+
+        let mut tune = RelayAutotune::new(AutotuneSettings::default());
+        let air = Some(Number::from_num(AUTOTUNE_AIR_MAX + 1.0));
+        assert!(matches!(tune.step(Some(Number::from_num(20)), air, &config), AutotuneStep::Abort(_)));
+    }
+}
@@ -0,0 +1,59 @@
+//! Frost detection on the humidity Peltier cold plate.
+//!
+//! Ice insulates the plate: the cold side sits near 0 C while less heat gets pumped to the
+//! hot side, so the hot-minus-cold delta falls although the Peltier keeps running. When that
+//! is seen the Peltier is switched off for a while and the chamber air melts the ice.
+
+use embassy_time::{Duration, Instant};
+
+use super::{ControlConfig, Number};
+
+#[derive(Default)]
+pub struct FrostMonitor {
+    // When the cold side got near freezing, and the hot-minus-cold delta at that time
+    suspect_since: Option<(Instant, Number)>,
+    defrost_until: Option<Instant>,
+}
+
+impl FrostMonitor {
+    pub fn is_defrosting(&self) -> bool {
+        self.defrost_until.is_some()
+    }
+
+    /// Returns true while defrosting, the Peltier has to stay off.
+    /// `running` is whether the Peltier has been cooling up to now.
+    pub fn update(&mut self, cold: Number, hot: Option<Number>, running: bool, config: &ControlConfig) -> bool {
+        let now = Instant::now();
+        if let Some(until) = self.defrost_until {
+            if now < until {
+                return true;
+            }
+            self.defrost_until = None;
+            defmt::info!("Humidity Peltier defrost done, cold side at {} C", cold.to_num::<f32>());
+        }
+
+        let Some(hot) = hot.filter(|_| running && cold <= config.frost_temp) else {
+            self.suspect_since = None;
+            return false;
+        };
+        let delta = hot - cold;
+        let (since, delta_start) = *self.suspect_since.get_or_insert((now, delta));
+        if now.saturating_duration_since(since) < Duration::from_secs(config.frost_detect_secs as u64) {
+            return false;
+        }
+
+        // Window over, look again from here if the delta held up
+        self.suspect_since = None;
+        if delta > delta_start - config.frost_delta_drop {
+            return false;
+        }
+        defmt::warn!(
+            "Frost on the humidity cold side (delta {} -> {} C), defrosting for {} s",
+            delta_start.to_num::<f32>(),
+            delta.to_num::<f32>(),
+            config.defrost_secs
+        );
+        self.defrost_until = Some(now + Duration::from_secs(config.defrost_secs as u64));
+        true
+    }
+}
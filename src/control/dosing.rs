@@ -0,0 +1,74 @@
+//! EC dosing with the GPIO22 pump, in pulses.
+//!
+//! Off target by more than the deadband, the pump runs for one pulse sized to the error,
+//! then stays off while the reservoir mixes. Only after that is EC read again for the next
+//! pulse, so the slow, filtered EC reading cannot make it overdose.
+
+use embassy_time::{Duration, Instant};
+
+use super::{ControlConfig, DoseDirection, Number};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DoseState {
+    Idle,
+    Dosing { until: Instant },
+    Mixing { until: Instant },
+}
+
+pub struct EcDoser {
+    state: DoseState,
+    dosed_ml: f32, // Since boot, for the log
+}
+
+impl Default for EcDoser {
+    fn default() -> Self {
+        Self { state: DoseState::Idle, dosed_ml: 0.0 }
+    }
+}
+
+impl EcDoser {
+    /// Ends a running pulse, e.g. when the loop was switched off. What went in still mixes.
+    pub fn stop(&mut self, config: &ControlConfig) {
+        if let DoseState::Dosing { .. } = self.state {
+            self.state = DoseState::Mixing { until: Instant::now() + Duration::from_secs(config.ec_mix_secs as u64) };
+        }
+    }
+
+    /// Returns whether the pump runs this step. `ec` is None while the sensor is not reading.
+    pub fn step(&mut self, ec: Option<Number>, target: Number, config: &ControlConfig) -> bool {
+        let now = Instant::now();
+        match self.state {
+            DoseState::Dosing { until } if now < until => return true,
+            DoseState::Dosing { .. } => {
+                self.state = DoseState::Mixing { until: now + Duration::from_secs(config.ec_mix_secs as u64) };
+                return false;
+            }
+            DoseState::Mixing { until } if now < until => return false,
+            DoseState::Mixing { .. } | DoseState::Idle => self.state = DoseState::Idle,
+        }
+
+        let Some(ec) = ec else { return false };
+        // Positive when this pump moves EC towards the target
+        let error = match config.ec_dose_direction {
+            DoseDirection::Dilute => ec - target,
+            DoseDirection::Concentrate => target - ec,
+        };
+        if error <= config.ec_deadband || config.ec_pump_ml_per_sec <= Number::ZERO {
+            return false;
+        }
+
+        let ml = (error * config.ec_dose_ml_per_ppm).clamp(config.ec_dose_min_ml, config.ec_dose_max_ml);
+        let pulse_ms = (ml.to_num::<f32>() / config.ec_pump_ml_per_sec.to_num::<f32>() * 1000.0) as u64;
+        self.dosed_ml += ml.to_num::<f32>();
+        defmt::info!(
+            "EC dose: {} ml ({} ms) at EC {} for target {}, {} ml since boot",
+            ml.to_num::<f32>(),
+            pulse_ms,
+            ec.to_num::<f32>(),
+            target.to_num::<f32>(),
+            self.dosed_ml
+        );
+        self.state = DoseState::Dosing { until: now + Duration::from_millis(pulse_ms) };
+        true
+    }
+}
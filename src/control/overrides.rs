@@ -0,0 +1,230 @@
+//! Manual actuator overrides for commissioning, e.g. run the water pump for 10 s or hold the
+//! LED at 30 %. They are applied on top of the controller outputs and revert to automatic
+//! control when their TTL runs out.
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+use crate::hardware_manager::ActuatorOutputs;
+
+/// TTL when a request does not give one
+pub const OVERRIDE_TTL_DEFAULT_SECS: u32 = 60;
+/// Longest TTL accepted, so a forgotten override does not run unattended
+pub const OVERRIDE_TTL_MAX_SECS: u32 = 60 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, defmt::Format)]
+#[serde(rename_all = "snake_case")]
+pub enum Actuator {
+    /// -255..255, positive heats
+    PeltierTemp,
+    PeltierHum,
+    FanInner,
+    FanTempOuter,
+    FanHumHot,
+    /// Non-zero = on
+    FanVent,
+    Led,
+    /// Non-zero = on
+    PumpNutrient,
+    /// Non-zero = on
+    PumpWater,
+}
+
+impl Actuator {
+    pub const ALL: [Actuator; 9] = [
+        Actuator::PeltierTemp,
+        Actuator::PeltierHum,
+        Actuator::FanInner,
+        Actuator::FanTempOuter,
+        Actuator::FanHumHot,
+        Actuator::FanVent,
+        Actuator::Led,
+        Actuator::PumpNutrient,
+        Actuator::PumpWater,
+    ];
+
+    /// Short name for the LCD
+    pub fn label(self) -> &'static str {
+        match self {
+            Actuator::PeltierTemp => "Peltier",
+            Actuator::PeltierHum => "Dehum",
+            Actuator::FanInner => "Fan in",
+            Actuator::FanTempOuter => "Fan out",
+            Actuator::FanHumHot => "Fan hum",
+            Actuator::FanVent => "Vent",
+            Actuator::Led => "LED",
+            Actuator::PumpNutrient => "Pump 21",
+            Actuator::PumpWater => "Pump 22",
+        }
+    }
+
+    fn value_range(self) -> (i16, i16) {
+        match self {
+            Actuator::PeltierTemp => (-255, 255),
+            Actuator::FanVent | Actuator::PumpNutrient | Actuator::PumpWater => (0, 1),
+            _ => (0, 255),
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Body of `POST /api/overrides` and of the `plant/override` MQTT topic.
+///
+/// `{"actuator":"pump_water","value":1,"ttl_secs":10}` sets an override,
+/// `{"actuator":"pump_water"}` clears it and `{}` clears all of them.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct OverrideRequest {
+    pub actuator: Option<Actuator>,
+    pub value: Option<i16>,
+    #[serde(default = "default_ttl")]
+    pub ttl_secs: u32,
+}
+
+fn default_ttl() -> u32 {
+    OVERRIDE_TTL_DEFAULT_SECS
+}
+
+/// An active override, as reported over HTTP / MQTT
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct OverrideStatus {
+    pub actuator: Actuator,
+    pub value: i16,
+    pub remaining_secs: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Override {
+    value: i16,
+    expires: Instant,
+}
+
+#[derive(Default)]
+pub struct ActuatorOverrides {
+    slots: [Option<Override>; Actuator::ALL.len()],
+}
+
+pub type SharedOverrides = Rc<Mutex<CriticalSectionRawMutex, ActuatorOverrides>>;
+
+impl ActuatorOverrides {
+    pub fn set(&mut self, actuator: Actuator, value: i16, ttl_secs: u32) -> Result<(), &'static str> {
+        let (min, max) = actuator.value_range();
+        if value < min || value > max {
+            return Err("value out of range for this actuator");
+        }
+        if ttl_secs == 0 || ttl_secs > OVERRIDE_TTL_MAX_SECS {
+            return Err("ttl_secs must be 1..3600");
+        }
+
+        self.slots[actuator.index()] = Some(Override {
+            value,
+            expires: Instant::now() + Duration::from_secs(ttl_secs as u64),
+        });
+        defmt::info!("Override {} = {} for {} s", actuator, value, ttl_secs);
+        Ok(())
+    }
+
+    pub fn clear(&mut self, actuator: Actuator) {
+        if self.slots[actuator.index()].take().is_some() {
+            defmt::info!("Override {} cleared", actuator);
+        }
+    }
+
+    pub fn clear_all(&mut self) {
+        for actuator in Actuator::ALL {
+            self.clear(actuator);
+        }
+    }
+
+    pub fn handle(&mut self, req: &OverrideRequest) -> Result<(), &'static str> {
+        match (req.actuator, req.value) {
+            (Some(actuator), Some(value)) => self.set(actuator, value, req.ttl_secs),
+            (Some(actuator), None) => {
+                self.clear(actuator);
+                Ok(())
+            }
+            (None, None) => {
+                self.clear_all();
+                Ok(())
+            }
+            (None, Some(_)) => Err("value given without actuator"),
+        }
+    }
+
+    /// Drops overrides whose TTL ran out, control goes back to the loops.
+    fn expire(&mut self) {
+        let now = Instant::now();
+        for actuator in Actuator::ALL {
+            if let Some(o) = self.slots[actuator.index()]
+                && o.expires <= now
+            {
+                self.slots[actuator.index()] = None;
+                defmt::info!("Override {} expired", actuator);
+            }
+        }
+    }
+
+    pub fn active(&mut self) -> Vec<OverrideStatus> {
+        self.expire();
+        let now = Instant::now();
+        Actuator::ALL
+            .iter()
+            .filter_map(|&actuator| {
+                self.slots[actuator.index()].map(|o| OverrideStatus {
+                    actuator,
+                    value: o.value,
+                    remaining_secs: o.expires.saturating_duration_since(now).as_secs() as u32,
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the active overrides into `outputs`. Returns false if there were none.
+    /// The caller applies the safety limits afterwards.
+    pub fn apply(&mut self, outputs: &mut ActuatorOutputs) -> bool {
+        self.expire();
+        let mut applied = false;
+        for actuator in Actuator::ALL {
+            let Some(o) = self.slots[actuator.index()] else { continue };
+            applied = true;
+            let pwm = o.value.clamp(0, 255) as u8;
+            match actuator {
+                Actuator::PeltierTemp => {
+                    outputs.peltier_temp_dir = o.value >= 0;
+                    outputs.peltier_temp_pwm = o.value.unsigned_abs().min(255) as u8;
+                }
+                Actuator::PeltierHum => outputs.peltier_hum_pwm = pwm,
+                Actuator::FanInner => outputs.fan_inner_speed = pwm,
+                Actuator::FanTempOuter => outputs.fan_temp_outer_speed = pwm,
+                Actuator::FanHumHot => outputs.fan_hum_hot_speed = pwm,
+                Actuator::FanVent => outputs.fan_vent_on = o.value != 0,
+                Actuator::Led => outputs.led_intensity = pwm,
+                Actuator::PumpNutrient => outputs.pump_nutrient = o.value != 0,
+                Actuator::PumpWater => outputs.pump_water = o.value != 0,
+            }
+        }
+
+        // A forced Peltier gets its fans, same as in post_process, unless those are forced too
+        if outputs.peltier_temp_pwm > 0 && self.slots[Actuator::PeltierTemp.index()].is_some() {
+            if self.slots[Actuator::FanInner.index()].is_none() {
+                outputs.fan_inner_speed = 255;
+            }
+            if self.slots[Actuator::FanTempOuter.index()].is_none() {
+                outputs.fan_temp_outer_speed = 255;
+            }
+        }
+        if outputs.peltier_hum_pwm > 0
+            && self.slots[Actuator::PeltierHum.index()].is_some()
+            && self.slots[Actuator::FanHumHot.index()].is_none()
+        {
+            outputs.fan_hum_hot_speed = 255;
+        }
+        applied
+    }
+}
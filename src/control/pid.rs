@@ -0,0 +1,72 @@
+use super::{Number, PidGains};
+
+/// PID with output limits and anti-windup, for loops that saturate in normal operation.
+///
+/// The integral only accumulates while the output is unsaturated, or while the error
+/// pulls it back out of saturation, so it never winds up past the limits.
+/// The derivative acts on the measurement, so setpoint steps do not kick the output.
+/// Gains are per control step, like the other loops.
+pub struct LimitedPid {
+    gains: PidGains,
+    integral: Number,
+    prev_measurement: Option<Number>,
+    out_min: Number,
+    out_max: Number,
+}
+
+impl LimitedPid {
+    pub fn new(gains: PidGains, out_min: Number, out_max: Number) -> Self {
+        Self {
+            gains,
+            integral: Number::ZERO,
+            prev_measurement: None,
+            out_min,
+            out_max,
+        }
+    }
+
+    pub fn set_gains(&mut self, gains: PidGains) {
+        self.gains = gains;
+    }
+
+    pub fn gains(&self) -> PidGains {
+        self.gains
+    }
+
+    pub fn set_limits(&mut self, out_min: Number, out_max: Number) {
+        self.out_min = out_min;
+        self.out_max = out_max;
+        self.integral = self.integral.clamp(out_min, out_max);
+    }
+
+    /// Clears the integral and derivative history, e.g. when the loop is switched off.
+    pub fn reset(&mut self) {
+        self.integral = Number::ZERO;
+        self.prev_measurement = None;
+    }
+
+    pub fn update(&mut self, setpoint: Number, measurement: Number) -> Number {
+        let error = setpoint - measurement;
+
+        let derivative = match self.prev_measurement {
+            Some(prev) => prev - measurement,
+            None => Number::ZERO,
+        };
+        self.prev_measurement = Some(measurement);
+
+        let p = self.gains.kp.saturating_mul(error);
+        let d = self.gains.kd.saturating_mul(derivative);
+        let integral = self.integral.saturating_add(self.gains.ki.saturating_mul(error));
+
+        let unclamped = p.saturating_add(integral).saturating_add(d);
+        let output = unclamped.clamp(self.out_min, self.out_max);
+
+        let winding_up = (unclamped > self.out_max && error > Number::ZERO)
+            || (unclamped < self.out_min && error < Number::ZERO);
+        if !winding_up {
+            self.integral = integral.clamp(self.out_min, self.out_max);
+        }
+
+        output
+    }
+}
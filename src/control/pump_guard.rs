@@ -0,0 +1,257 @@
+//! Run-time limits of the tray pump (GPIO21): longest continuous run, shortest rest between
+//! runs and a daily budget. Without them a leaking tray or a shifted sensor, which never reads
+//! wet, keeps the pump running. The limits also hold for manual overrides.
+
+use embassy_time::{Duration, Instant};
+
+use super::ControlConfig;
+use crate::config_types::PumpCounters;
+use crate::hardware_manager::ActuatorOutputs;
+
+/// What happened to the daily budget on a step, for the `PumpBudget` fault.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetEvent {
+    None,
+    /// Used up on this step
+    Exceeded,
+    /// A new day started, with a fresh budget
+    Renewed,
+}
+
+pub struct PumpGuard {
+    counters: PumpCounters,
+    on_since: Option<Instant>,
+    off_since: Option<Instant>, // Last stop, for the rest between runs
+    last_update: Instant,
+}
+
+impl PumpGuard {
+    /// `counters` as saved, so a reboot does not hand out a fresh budget.
+    pub fn new(counters: PumpCounters) -> Self {
+        Self {
+            counters,
+            on_since: None,
+            off_since: None,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn counters(&self) -> PumpCounters {
+        self.counters
+    }
+
+    /// Limits `outputs.pump_nutrient`. `today` is the local date (days since 0001-01-01),
+    /// None while the clock is not set. The budget fault follows the returned event.
+    pub fn apply(&mut self, outputs: &mut ActuatorOutputs, config: &ControlConfig, today: Option<i32>) -> BudgetEvent {
+        self.apply_at(Instant::now(), outputs, config, today)
+    }
+
+    /// Lifts the lockout with a fresh budget for the rest of the day. This is what
+    /// acknowledging the budget fault does, and the only way out while the clock is not set.
+    pub fn reset_budget(&mut self) {
+        #[cfg(feature = "firmware")]
+        if self.counters.locked_out {
+            defmt::info!("Tray pump: lockout lifted, fresh budget");
+        }
+        self.counters = PumpCounters { day: self.counters.day, ..PumpCounters::default() };
+    }
+
+    fn apply_at(&mut self, now: Instant, outputs: &mut ActuatorOutputs, config: &ControlConfig, today: Option<i32>) -> BudgetEvent {
+        let elapsed_ms = now.saturating_duration_since(self.last_update).as_millis() as u32;
+        self.last_update = now;
+
+        // Local midnight, or the first step after a reboot past it: fresh budget
+        let mut event = BudgetEvent::None;
+        if let Some(day) = today
+            && day != self.counters.day
+        {
+            #[cfg(feature = "firmware")]
+            if self.counters.locked_out {
+                defmt::info!("Tray pump: new day, lockout lifted");
+            }
+            self.counters = PumpCounters { day, ..PumpCounters::default() };
+            event = BudgetEvent::Renewed;
+        }
+
+        // The pump ran since the last step if it was on then
+        if self.on_since.is_some() {
+            self.counters.on_ms_today = self.counters.on_ms_today.saturating_add(elapsed_ms);
+        }
+
+        if !self.counters.locked_out && self.budget_used(config) {
+            self.counters.locked_out = true;
+            event = BudgetEvent::Exceeded;
+            #[cfg(feature = "firmware")]
+            defmt::warn!("Tray pump: daily budget used up ({} s), locked out until midnight or acknowledged", self.counters.on_ms_today / 1000);
+        }
+
+        let mut on = outputs.pump_nutrient && !self.counters.locked_out;
+        match (on, self.on_since, self.off_since) {
+            (true, Some(since), _) if now.saturating_duration_since(since) >= Duration::from_secs(config.pump_max_on_secs as u64) => {
+                #[cfg(feature = "firmware")]
+                defmt::warn!("Tray pump: ran {} s without reaching the wet level, resting", config.pump_max_on_secs);
+                on = false;
+            }
+            (true, None, Some(stopped)) if now.saturating_duration_since(stopped) < Duration::from_secs(config.pump_min_off_secs as u64) => {
+                on = false;
+            }
+            _ => {}
+        }
+
+        match (on, self.on_since) {
+            (true, None) => self.on_since = Some(now),
+            (false, Some(_)) => {
+                self.on_since = None;
+                self.off_since = Some(now);
+            }
+            _ => {}
+        }
+        outputs.pump_nutrient = on;
+        event
+    }
+
+    /// Pumped volume today in ml, from the calibrated flow rate.
+    pub fn volume_today_ml(counters: &PumpCounters, config: &ControlConfig) -> u32 {
+        (counters.on_ms_today as f32 / 60_000.0 * config.pump_flow_ml_per_min.to_num::<f32>()) as u32
+    }
+
+    /// Time budget, and the volume budget when one is set
+    fn budget_used(&self, config: &ControlConfig) -> bool {
+        self.counters.on_ms_today / 1000 >= config.pump_daily_budget_secs
+            || (config.pump_daily_budget_ml > 0 && Self::volume_today_ml(&self.counters, config) >= config.pump_daily_budget_ml)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{FaultCode, FaultSupervisor};
+    use crate::sensor_manager::SensorData;
+
+    const DAY: i32 = 739_000;
+
+    struct Run {
+        guard: PumpGuard,
+        config: ControlConfig,
+        now: Instant,
+    }
+
+    impl Run {
+        fn new(counters: PumpCounters) -> Self {
+            let mut guard = PumpGuard::new(counters);
+            let now = Instant::now();
+            guard.last_update = now;
+            let config = ControlConfig {
+                pump_max_on_secs: 60,
+                pump_min_off_secs: 120,
+                pump_daily_budget_secs: 150,
+                ..Default::default()
+            };
+            Self { guard, config, now }
+        }
+
+        /// Advances `secs` and asks for the pump, returns whether it runs and the budget trip
+        fn step(&mut self, secs: u64, want: bool, today: Option<i32>) -> (bool, bool) {
+            let (on, event) = self.step_event(secs, want, today);
+            (on, event == BudgetEvent::Exceeded)
+        }
+
+        fn step_event(&mut self, secs: u64, want: bool, today: Option<i32>) -> (bool, BudgetEvent) {
+            self.now += Duration::from_secs(secs);
+            let mut outputs = ActuatorOutputs { pump_nutrient: want, ..Default::default() };
+            let event = self.guard.apply_at(self.now, &mut outputs, &self.config, today);
+            (outputs.pump_nutrient, event)
+        }
+
+        /// The control loop's order: guard, budget fault, then the supervisor's safe states
+        fn step_with_faults(&mut self, faults: &mut FaultSupervisor, secs: u64, today: Option<i32>) -> bool {
+            self.now += Duration::from_secs(secs);
+            let mut outputs = ActuatorOutputs { pump_nutrient: true, ..Default::default() };
+            match self.guard.apply_at(self.now, &mut outputs, &self.config, today) {
+                BudgetEvent::Exceeded => faults.raise(FaultCode::PumpBudget, None),
+                BudgetEvent::Renewed => faults.clear(FaultCode::PumpBudget),
+                BudgetEvent::None => {}
+            }
+            faults.supervise(&SensorData::default(), &mut outputs, &ControlConfig::default(), None);
+            outputs.pump_nutrient
+        }
+    }
+
+    #[test]
+    fn long_runs_are_cut_and_rested() {
+        let mut run = Run::new(PumpCounters { day: DAY, ..Default::default() });
+        assert_eq!(run.step(0, true, Some(DAY)), (true, false));
+        assert_eq!(run.step(59, true, Some(DAY)), (true, false));
+        assert_eq!(run.step(1, true, Some(DAY)), (false, false));
+        // Resting, even when asked
+        assert_eq!(run.step(119, true, Some(DAY)), (false, false));
+        assert_eq!(run.step(1, true, Some(DAY)), (true, false));
+        assert_eq!(run.guard.counters().on_ms_today, 60_000);
+    }
+
+    #[test]
+    fn budget_locks_out_until_the_next_day() {
+        let mut run = Run::new(PumpCounters { day: DAY, ..Default::default() });
+        for _ in 0..2 {
+            assert!(run.step(0, true, Some(DAY)).0);
+            run.step(60, true, Some(DAY));
+            run.step(120, false, Some(DAY));
+        }
+        assert_eq!(run.step(0, true, Some(DAY)), (true, false));
+        assert_eq!(run.step(30, true, Some(DAY)), (false, true));
+        assert!(run.guard.counters().locked_out);
+        assert_eq!(run.step(600, true, Some(DAY)), (false, false));
+
+        assert_eq!(run.step_event(0, true, Some(DAY + 1)), (true, BudgetEvent::Renewed));
+        assert_eq!(run.guard.counters(), PumpCounters { day: DAY + 1, on_ms_today: 0, locked_out: false });
+    }
+
+    #[test]
+    fn budget_fault_clears_at_midnight() {
+        let mut run = Run::new(PumpCounters { day: DAY, on_ms_today: 140_000, ..Default::default() });
+        let mut faults = FaultSupervisor::default();
+        assert!(run.step_with_faults(&mut faults, 0, Some(DAY)));
+        assert!(!run.step_with_faults(&mut faults, 10, Some(DAY)));
+        assert!(faults.is_latched(FaultCode::PumpBudget));
+        assert!(!run.step_with_faults(&mut faults, 600, Some(DAY)));
+
+        assert!(run.step_with_faults(&mut faults, 600, Some(DAY + 1)));
+        assert!(!faults.is_latched(FaultCode::PumpBudget));
+    }
+
+    #[test]
+    fn budget_fault_from_before_a_reboot_clears_on_a_new_day() {
+        // Saved locked out, the fault raised again at start-up
+        let counters = PumpCounters { day: DAY, on_ms_today: 150_000, locked_out: true };
+        let mut run = Run::new(counters);
+        let mut faults = FaultSupervisor::default();
+        faults.raise(FaultCode::PumpBudget, None);
+
+        // Still locked out while the clock is not set
+        assert!(!run.step_with_faults(&mut faults, 0, None));
+        assert!(run.step_with_faults(&mut faults, 5, Some(DAY + 1)));
+        assert!(!faults.is_latched(FaultCode::PumpBudget));
+    }
+
+    #[test]
+    fn reset_lifts_the_lockout_without_a_clock() {
+        let counters = PumpCounters { day: DAY, on_ms_today: 150_000, locked_out: true };
+        let mut run = Run::new(counters);
+        assert_eq!(run.step(600, true, None), (false, false));
+        assert_eq!(run.guard.counters(), counters);
+
+        run.guard.reset_budget();
+        assert_eq!(run.guard.counters(), PumpCounters { day: DAY, ..Default::default() });
+        assert_eq!(run.step(0, true, None), (true, false));
+    }
+
+    #[test]
+    fn volume_budget() {
+        let mut run = Run::new(PumpCounters { day: DAY, ..Default::default() });
+        run.config.pump_daily_budget_ml = 50; // 30 s at 100 ml/min
+        run.step(0, true, Some(DAY));
+        assert_eq!(run.step(29, true, Some(DAY)), (true, false));
+        assert_eq!(run.step(1, true, Some(DAY)), (false, true));
+        assert_eq!(PumpGuard::volume_today_ml(&run.guard.counters(), &run.config), 50);
+    }
+}
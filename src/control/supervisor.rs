@@ -0,0 +1,438 @@
+//! Fault supervisor around `PlantController::step`.
+//!
+//! Checks the plant for failures the loops cannot see themselves: a Peltier that does not move
+//! the air temperature, a Peltier whose hot side runs away (dead fan), a pump running too long
+//! and sensors that are lost or frozen. A fault latches until it is acknowledged over HTTP or
+//! MQTT, and while latched it holds the actuators it concerns in a safe state.
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+use super::{ControlConfig, Number, NTC_PELTIER_HUM_COLD, NTC_PELTIER_HUM_HOT, NTC_PELTIER_INNER, NTC_PELTIER_OUTER};
+use crate::hardware_manager::ActuatorOutputs;
+use crate::sensor_manager::SensorData;
+
+/// Temperature Peltier PWM counted as full effort
+const FULL_EFFORT_PWM: u8 = 250;
+/// Change in a Peltier's PWM after which the readings it acts on have to move
+const FROZEN_DRIVE_CHANGE: i16 = 32;
+/// Smaller than any sensor's resolution, so only filter rounding (~0.001 C)
+const FROZEN_TEMP_BAND: Number = Number::from_bits(64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "firmware", derive(defmt::Format))]
+#[serde(rename_all = "snake_case")]
+pub enum FaultCode {
+    /// Temperature Peltier at full effort without the air temperature following
+    TempNoResponse,
+    /// Temperature Peltier hot side running away from the cold side
+    TempPeltierRunaway,
+    /// Humidity Peltier hot side running away from the cold side
+    HumPeltierRunaway,
+    /// A pump ran longer than `fault_pump_max_secs`
+    PumpRunTime,
+    /// No reading from the chamber air sensor
+    AirSensorLost,
+    /// Chamber air reading not moving while the temperature Peltier's drive changes
+    AirSensorFrozen,
+    /// A Peltier NTC reading not moving while its Peltier's drive changes
+    NtcFrozen,
+    /// Tray pump used up its daily budget, locked out until midnight or until acknowledged
+    PumpBudget,
+}
+
+impl FaultCode {
+    pub const ALL: [FaultCode; 8] = [
+        FaultCode::TempNoResponse,
+        FaultCode::TempPeltierRunaway,
+        FaultCode::HumPeltierRunaway,
+        FaultCode::PumpRunTime,
+        FaultCode::AirSensorLost,
+        FaultCode::AirSensorFrozen,
+        FaultCode::NtcFrozen,
+        FaultCode::PumpBudget,
+    ];
+
+    /// Error code for the LCD
+    pub fn id(self) -> &'static str {
+        match self {
+            FaultCode::TempNoResponse => "F01",
+            FaultCode::TempPeltierRunaway => "F02",
+            FaultCode::HumPeltierRunaway => "F03",
+            FaultCode::PumpRunTime => "F04",
+            FaultCode::AirSensorLost => "F05",
+            FaultCode::AirSensorFrozen => "F06",
+            FaultCode::NtcFrozen => "F07",
+            FaultCode::PumpBudget => "F08",
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            FaultCode::TempNoResponse => "temperature not responding to the Peltier",
+            FaultCode::TempPeltierRunaway => "temperature Peltier hot side overheating",
+            FaultCode::HumPeltierRunaway => "humidity Peltier hot side overheating",
+            FaultCode::PumpRunTime => "pump ran too long",
+            FaultCode::AirSensorLost => "chamber air sensor not reading",
+            FaultCode::AirSensorFrozen => "chamber air sensor reading frozen",
+            FaultCode::NtcFrozen => "Peltier NTC reading frozen",
+            FaultCode::PumpBudget => "tray pump daily budget used up",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Puts the actuators this fault concerns in their safe state.
+    fn safe_state(self, outputs: &mut ActuatorOutputs) {
+        match self {
+            FaultCode::TempNoResponse => outputs.peltier_temp_pwm = 0,
+            FaultCode::TempPeltierRunaway => {
+                // Keep the heatsink fan on to get the heat out
+                outputs.peltier_temp_pwm = 0;
+                outputs.fan_temp_outer_speed = 255;
+            }
+            FaultCode::HumPeltierRunaway => {
+                outputs.peltier_hum_pwm = 0;
+                outputs.fan_hum_hot_speed = 255;
+            }
+            FaultCode::PumpBudget => outputs.pump_nutrient = false,
+            FaultCode::PumpRunTime => {
+                outputs.pump_nutrient = false;
+                outputs.pump_water = false;
+            }
+            FaultCode::AirSensorLost | FaultCode::AirSensorFrozen | FaultCode::NtcFrozen => {
+                outputs.peltier_temp_pwm = 0;
+                outputs.peltier_hum_pwm = 0;
+            }
+        }
+    }
+}
+
+/// Body of `POST /api/faults/ack` and of the `plant/faults/ack` MQTT topic.
+/// `{"code":"pump_run_time"}` acknowledges one fault, `{}` all of them.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct FaultAck {
+    pub code: Option<FaultCode>,
+}
+
+/// A latched fault, as reported over HTTP / MQTT
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct FaultStatus {
+    pub code: FaultCode,
+    pub id: &'static str,
+    pub message: &'static str,
+    pub ts: u64, // Unix time it tripped, 0 if the clock is not set
+}
+
+/// Watches one reading for being stuck.
+///
+/// A reading that holds still while nothing acts on it is normal: an idle Peltier's NTC, or
+/// any channel of the 8-bit NTC ADC at steady state. So a reading only counts as frozen when
+/// it did not move for the whole window while the actuator acting on it was running and
+/// changed its drive by at least `FROZEN_DRIVE_CHANGE`.
+#[derive(Clone, Copy, Default)]
+struct FrozenCheck<T> {
+    // When the reading last moved, its value then and the lowest and highest drive since
+    window: Option<(Instant, T, i16, i16)>,
+}
+
+impl<T: Copy> FrozenCheck<T> {
+    /// `drive` is `None` while the actuator is off. Returns true once the reading is frozen.
+    fn update(&mut self, now: Instant, reading: Option<T>, drive: Option<i16>, moved: impl Fn(T, T) -> bool, window: Duration) -> bool {
+        let (Some(reading), Some(drive)) = (reading, drive) else {
+            self.window = None;
+            return false;
+        };
+        match &mut self.window {
+            Some((since, last, low, high)) if !moved(*last, reading) => {
+                *low = (*low).min(drive);
+                *high = (*high).max(drive);
+                *high - *low >= FROZEN_DRIVE_CHANGE && now.saturating_duration_since(*since) >= window
+            }
+            _ => {
+                self.window = Some((now, reading, drive, drive));
+                false
+            }
+        }
+    }
+}
+
+fn temp_moved(a: Number, b: Number) -> bool {
+    (a - b).abs() > FROZEN_TEMP_BAND
+}
+
+/// Latched faults and the state of the checks.
+#[derive(Default)]
+pub struct FaultSupervisor {
+    latched: [Option<u64>; FaultCode::ALL.len()],
+
+    // Full effort since, the air temperature then and whether heating
+    full_effort: Option<(Instant, Number, bool)>,
+    pump_on_since: [Option<Instant>; 2], // Nutrient, water
+    air_lost_since: Option<Instant>,
+    air_frozen: FrozenCheck<(Number, u8)>,
+    ntc_frozen: [FrozenCheck<Number>; 4],
+}
+
+pub type SharedFaults = Rc<Mutex<CriticalSectionRawMutex, FaultSupervisor>>;
+
+impl FaultSupervisor {
+    /// Runs the checks on this step's outputs, latches new faults and puts the actuators of
+    /// all latched faults in their safe state. Called last, after the manual overrides.
+    pub fn supervise(&mut self, sensors: &SensorData, outputs: &mut ActuatorOutputs, config: &ControlConfig, now_ts: Option<u64>) {
+        let now = Instant::now();
+        let secs = |s: u32| Duration::from_secs(s as u64);
+
+        // Peltier at full effort, the air temperature has to follow
+        let air_temp = sensors.internal.map(|r| r.temp);
+        match (air_temp, outputs.peltier_temp_pwm >= FULL_EFFORT_PWM) {
+            (Some(temp), true) => {
+                let heating = outputs.peltier_temp_dir;
+                match self.full_effort {
+                    Some((since, start, dir)) if dir == heating => {
+                        if now.saturating_duration_since(since) >= secs(config.fault_no_response_secs) {
+                            let moved = if heating { temp - start } else { start - temp };
+                            if moved < config.fault_min_temp_change {
+                                self.latch(FaultCode::TempNoResponse, now_ts);
+                            }
+                            self.full_effort = Some((now, temp, heating));
+                        }
+                    }
+                    _ => self.full_effort = Some((now, temp, heating)),
+                }
+            }
+            _ => self.full_effort = None,
+        }
+
+        // Hot side running away from the cold side of a running Peltier
+        let ntc = &sensors.ntc_temps;
+        let runaway = |cold: Option<Number>, hot: Option<Number>| {
+            matches!((cold, hot), (Some(c), Some(h)) if (h - c).abs() > config.fault_peltier_delta_max)
+        };
+        if outputs.peltier_temp_pwm > 0 && runaway(ntc[NTC_PELTIER_INNER], ntc[NTC_PELTIER_OUTER]) {
+            self.latch(FaultCode::TempPeltierRunaway, now_ts);
+        }
+        if outputs.peltier_hum_pwm > 0 && runaway(ntc[NTC_PELTIER_HUM_COLD], ntc[NTC_PELTIER_HUM_HOT]) {
+            self.latch(FaultCode::HumPeltierRunaway, now_ts);
+        }
+
+        // Pump run time
+        let mut pump_overrun = false;
+        for (since, on) in self.pump_on_since.iter_mut().zip([outputs.pump_nutrient, outputs.pump_water]) {
+            if !on {
+                *since = None;
+            } else if now.saturating_duration_since(*since.get_or_insert(now)) >= secs(config.fault_pump_max_secs) {
+                *since = None;
+                pump_overrun = true;
+            }
+        }
+        if pump_overrun {
+            self.latch(FaultCode::PumpRunTime, now_ts);
+        }
+
+        // Air sensor lost
+        if sensors.internal.is_some() {
+            self.air_lost_since = None;
+        } else if now.saturating_duration_since(*self.air_lost_since.get_or_insert(now)) >= secs(config.fault_sensor_lost_secs) {
+            self.latch(FaultCode::AirSensorLost, now_ts);
+        }
+
+        // Frozen readings, only checked where a running Peltier should move them. Signed for
+        // the temperature Peltier, so a swing from heating to cooling counts in full.
+        let frozen_window = secs(config.fault_sensor_frozen_secs);
+        let temp_drive = (outputs.peltier_temp_pwm > 0).then(|| {
+            let pwm = outputs.peltier_temp_pwm as i16;
+            if outputs.peltier_temp_dir { pwm } else { -pwm }
+        });
+        let hum_drive = (outputs.peltier_hum_pwm > 0).then_some(outputs.peltier_hum_pwm as i16);
+
+        let air = sensors.internal.map(|r| (r.temp, r.hum));
+        let air_moved = |a: (Number, u8), b: (Number, u8)| temp_moved(a.0, b.0) || a.1 != b.1;
+        if self.air_frozen.update(now, air, temp_drive, air_moved, frozen_window) {
+            self.latch(FaultCode::AirSensorFrozen, now_ts);
+        }
+
+        // A missing NTC already shuts its Peltier down in the safety limits
+        let mut ntc_frozen = false;
+        for (i, check) in self.ntc_frozen.iter_mut().enumerate() {
+            let drive = match i {
+                NTC_PELTIER_INNER | NTC_PELTIER_OUTER => temp_drive,
+                _ => hum_drive,
+            };
+            ntc_frozen |= check.update(now, ntc[i], drive, temp_moved, frozen_window);
+        }
+        if ntc_frozen {
+            self.latch(FaultCode::NtcFrozen, now_ts);
+        }
+
+        for code in FaultCode::ALL {
+            if self.latched[code.index()].is_some() {
+                code.safe_state(outputs);
+            }
+        }
+    }
+
+    /// Latches a fault found outside the supervisor's own checks.
+    pub fn raise(&mut self, code: FaultCode, now_ts: Option<u64>) {
+        self.latch(code, now_ts);
+    }
+
+    /// Latches `code` unless it already is.
+    fn latch(&mut self, code: FaultCode, now_ts: Option<u64>) {
+        let slot = &mut self.latched[code.index()];
+        if slot.is_none() {
+            #[cfg(feature = "firmware")]
+            defmt::error!("Fault {} ({}): {}", code.id(), code, code.message());
+            *slot = Some(now_ts.unwrap_or(0));
+        }
+    }
+
+    /// Clears a latched fault, or all of them. A condition still present trips again once
+    /// its check has run for its full time.
+    pub fn acknowledge(&mut self, ack: &FaultAck) {
+        for code in FaultCode::ALL {
+            if ack.code.is_none_or(|c| c == code) && self.latched[code.index()].take().is_some() {
+                #[cfg(feature = "firmware")]
+                defmt::info!("Fault {} acknowledged", code.id());
+            }
+        }
+        // Start the checks over, so a fault is not re-raised from before the acknowledgement
+        *self = Self { latched: self.latched, ..Self::default() };
+    }
+
+    /// Clears one fault once its cause is gone, without restarting the other checks.
+    pub fn clear(&mut self, code: FaultCode) {
+        if self.latched[code.index()].take().is_some() {
+            #[cfg(feature = "firmware")]
+            defmt::info!("Fault {} cleared", code.id());
+        }
+    }
+
+    pub fn is_latched(&self, code: FaultCode) -> bool {
+        self.latched[code.index()].is_some()
+    }
+
+    pub fn active(&self) -> Vec<FaultStatus> {
+        FaultCode::ALL
+            .iter()
+            .filter_map(|&code| {
+                self.latched[code.index()].map(|ts| FaultStatus {
+                    code,
+                    id: code.id(),
+                    message: code.message(),
+                    ts,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensor_manager::TempHumReading;
+
+    /// Checks trip on the first step that qualifies
+    fn config() -> ControlConfig {
+        ControlConfig { fault_sensor_frozen_secs: 0, fault_sensor_lost_secs: 0, ..Default::default() }
+    }
+
+    fn sensors(air_temp: f32, ntc: f32) -> SensorData {
+        SensorData {
+            internal: Some(TempHumReading { temp: Number::from_num(air_temp), hum: 60 }),
+            ntc_temps: [Some(Number::from_num(ntc)); 4],
+            ..Default::default()
+        }
+    }
+
+    fn hum_peltier(pwm: u8) -> ActuatorOutputs {
+        ActuatorOutputs { peltier_hum_pwm: pwm, ..Default::default() }
+    }
+
+    fn temp_peltier(pwm: u8, heat: bool) -> ActuatorOutputs {
+        ActuatorOutputs { peltier_temp_pwm: pwm, peltier_temp_dir: heat, ..Default::default() }
+    }
+
+    fn latched(faults: &FaultSupervisor) -> Vec<FaultCode> {
+        faults.active().iter().map(|f| f.code).collect()
+    }
+
+    #[test]
+    fn steady_readings_are_not_frozen_while_the_drive_holds() {
+        let mut faults = FaultSupervisor::default();
+        let data = sensors(22.0, 22.0);
+        for pwm in [0, 0, 0, 120, 120, 120, 0, 0] {
+            faults.supervise(&data, &mut hum_peltier(pwm), &config(), None);
+            faults.supervise(&data, &mut temp_peltier(pwm, true), &config(), None);
+        }
+        assert_eq!(latched(&faults), []);
+    }
+
+    #[test]
+    fn ntc_not_following_its_peltier_is_frozen() {
+        let mut faults = FaultSupervisor::default();
+        let mut data = sensors(22.0, 22.0);
+        for pwm in [60, 80, 100] {
+            // The air keeps moving, only the NTCs are stuck
+            data.internal.as_mut().unwrap().temp += Number::from_num(0.1);
+            let mut outputs = hum_peltier(pwm);
+            outputs.peltier_temp_pwm = 100;
+            faults.supervise(&data, &mut outputs, &config(), Some(1_700_000_000));
+            if pwm == 100 {
+                assert_eq!(outputs.peltier_temp_pwm, 0);
+                assert_eq!(outputs.peltier_hum_pwm, 0);
+            }
+        }
+        assert_eq!(latched(&faults), [FaultCode::NtcFrozen]);
+        assert_eq!(faults.active()[0].ts, 1_700_000_000);
+    }
+
+    #[test]
+    fn readings_that_move_are_not_frozen() {
+        let mut faults = FaultSupervisor::default();
+        // One 8-bit ADC step is about 0.3 C, a filtered value moves by less
+        for (step, pwm) in [100u8, 160, 220, 160, 100, 40].into_iter().enumerate() {
+            let data = sensors(22.0 + 0.1 * step as f32, 22.0 + 0.05 * step as f32);
+            faults.supervise(&data, &mut temp_peltier(pwm, step % 2 == 0), &config(), None);
+            faults.supervise(&data, &mut hum_peltier(pwm), &config(), None);
+        }
+        assert_eq!(latched(&faults), []);
+    }
+
+    #[test]
+    fn air_sensor_frozen_and_lost() {
+        let mut faults = FaultSupervisor::default();
+        let mut data = sensors(22.0, 22.0);
+        for (step, pwm) in [100u8, 140].into_iter().enumerate() {
+            data.ntc_temps = [Some(Number::from_num(22 + step)); 4];
+            faults.supervise(&data, &mut temp_peltier(pwm, false), &config(), None);
+        }
+        assert_eq!(latched(&faults), [FaultCode::AirSensorFrozen]);
+
+        data.internal = None;
+        faults.supervise(&data, &mut ActuatorOutputs::default(), &config(), None);
+        assert_eq!(latched(&faults), [FaultCode::AirSensorLost, FaultCode::AirSensorFrozen]);
+    }
+
+    #[test]
+    fn acknowledge_clears_and_restarts_the_checks() {
+        let mut faults = FaultSupervisor::default();
+        faults.raise(FaultCode::PumpBudget, None);
+        faults.raise(FaultCode::PumpRunTime, None);
+
+        let mut outputs = ActuatorOutputs { pump_nutrient: true, pump_water: true, ..Default::default() };
+        faults.supervise(&sensors(22.0, 22.0), &mut outputs, &config(), None);
+        assert!(!outputs.pump_nutrient && !outputs.pump_water);
+
+        faults.acknowledge(&FaultAck { code: Some(FaultCode::PumpRunTime) });
+        assert_eq!(latched(&faults), [FaultCode::PumpBudget]);
+        faults.acknowledge(&FaultAck { code: None });
+        assert_eq!(latched(&faults), []);
+    }
+}
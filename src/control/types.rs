@@ -0,0 +1,305 @@
+//! Plain data types shared with the user script engine.
+//! Kept free of hardware and runtime dependencies so they also build on the host.
+
+use fixed::types::I16F16;
+use piddiy::PidController;
+use serde::{Serialize, Deserialize};
+
+// Type alias for our fixed-point number
+pub type Number = I16F16;
+
+// NTC Sensor Indices
+pub const NTC_PELTIER_INNER: usize = 0;
+pub const NTC_PELTIER_OUTER: usize = 1;
+pub const NTC_PELTIER_HUM_COLD: usize = 2;
+pub const NTC_PELTIER_HUM_HOT: usize = 3;
+
+/// PID Gains structure for cleaner configuration
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PidGains {
+    pub kp: Number,
+    pub ki: Number,
+    pub kd: Number,
+}
+
+impl PidGains {
+    pub fn new(kp: f32, ki: f32, kd: f32) -> Self {
+        Self {
+            kp: Number::from_num(kp),
+            ki: Number::from_num(ki),
+            kd: Number::from_num(kd),
+        }
+    }
+
+    /// Apply these gains to a PidController
+    pub fn apply_to(&self, pid: &mut PidController<Number, Number>) {
+        pid.kp(self.kp).ki(self.ki).kd(self.kd);
+    }
+}
+
+/// How a control loop drives its actuator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlMode {
+    /// Actuator off, loop state cleared
+    Off,
+    /// On/off around the setpoint with a deadband
+    Hysteresis,
+    /// Loops that only switch a pump treat this as Hysteresis
+    Pid,
+    /// Fixed output: PWM 0-255 (negative cools for temperature), non-zero = on for pumps.
+    /// Safety cutoffs still apply.
+    Manual(i16),
+}
+
+/// What the EC dosing pump (GPIO22) is plumbed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoseDirection {
+    /// Plain water, doses while EC is above the target
+    Dilute,
+    /// Nutrient concentrate, doses while EC is below the target
+    Concentrate,
+}
+
+/// Mode of each control loop, switchable at runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoopModes {
+    pub temperature: ControlMode,
+    pub humidity: ControlMode,
+    pub tray_water: ControlMode,
+    pub ec: ControlMode,
+    pub fans: ControlMode,
+    pub light: ControlMode,
+}
+
+impl Default for LoopModes {
+    fn default() -> Self {
+        Self {
+            temperature: ControlMode::Pid,
+            humidity: ControlMode::Pid,
+            tray_water: ControlMode::Hysteresis,
+            ec: ControlMode::Off, // Dosing pump not plumbed in yet
+            fans: ControlMode::Pid,
+            light: ControlMode::Hysteresis, // Open loop, Pid holds a PPFD on the light sensor
+        }
+    }
+}
+
+/// Partial update of `LoopModes` (HTTP / MQTT), missing loops are left as they are.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct LoopModesUpdate {
+    pub temperature: Option<ControlMode>,
+    pub humidity: Option<ControlMode>,
+    pub tray_water: Option<ControlMode>,
+    pub ec: Option<ControlMode>,
+    pub fans: Option<ControlMode>,
+    pub light: Option<ControlMode>,
+}
+
+impl LoopModesUpdate {
+    pub fn apply_to(&self, modes: &mut LoopModes) {
+        if let Some(m) = self.temperature { modes.temperature = m; }
+        if let Some(m) = self.humidity { modes.humidity = m; }
+        if let Some(m) = self.tray_water { modes.tray_water = m; }
+        if let Some(m) = self.ec { modes.ec = m; }
+        if let Some(m) = self.fans { modes.fans = m; }
+        if let Some(m) = self.light { modes.light = m; }
+    }
+}
+
+/// Configuration for the controller, including PID gains and limits.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ControlConfig {
+    pub modes: LoopModes,
+
+    // Temperature Control (Cascade)
+    pub air_temp: PidGains,      // Primary Loop (Air Temp -> Target Peltier Temp)
+    pub peltier_temp_heat: PidGains, // Secondary Loop (Peltier Temp -> PWM)
+    pub peltier_temp_cool: PidGains,
+    // Range the primary loop may ask of the Peltier inner surface
+    pub peltier_temp_min: Number,
+    pub peltier_temp_max: Number,
+
+    // Humidity Loop
+    pub hum_cold_side: PidGains,
+    pub hum_cold_target: Number,     // Cold-side setpoint while the dew point is unknown
+    pub hum_cold_dew_margin: Number, // Cold side this far below the dew point
+    pub hum_cold_min: Number,        // Never below this, keeps the plate above freezing
+
+    // Frost on the humidity cold side: near `frost_temp` for `frost_detect_secs` with the
+    // hot-minus-cold delta down by `frost_delta_drop` -> Peltier off for `defrost_secs`
+    pub frost_temp: Number,
+    pub frost_delta_drop: Number,
+    pub frost_detect_secs: u32,
+    pub defrost_secs: u32,
+
+    // Feedforward Gains
+    pub k_ff_hum: Number,
+    pub k_ff_vent: Number,
+
+    // Fan Control
+    pub fan_temp_outer: PidGains,
+    pub fan_hum_hot: PidGains,
+    pub peltier_temp_diff_target: Number,
+
+    pub k_fan_effort: Number,
+    pub fan_base_day: Number,
+    pub fan_base_night: Number,
+    pub max_fan_speed: u8,
+
+    // Soil Moisture Control
+    pub soil_low_threshold: Number,
+    pub soil_high_threshold: Number,
+    
+    // Water Tray Control (Calibration Reference Values)
+    // Recorded ADC values for states
+    pub water_cal_no_tray: Number,  // ~3000
+    pub water_cal_dry_tray: Number, // ~2000-2500
+    pub water_cal_wet_tray: Number, // ~1000-1500
+
+    // Tray Pump Limits
+    pub pump_max_on_secs: u32,       // Longest continuous run
+    pub pump_min_off_secs: u32,      // Shortest rest between runs
+    pub pump_daily_budget_secs: u32, // Run time per local day, then locked out until midnight or acknowledged
+    pub pump_daily_budget_ml: u32,   // Same in volume, 0 = time budget only
+    pub pump_flow_ml_per_min: Number,
+    
+    // VPD Control (when the targets carry a VPD)
+    pub leaf_temp_offset: Number, // Leaf temperature below air temperature
+    pub vpd_hysteresis: Number,   // kPa
+
+    // CO2 Ventilation: outside air in while CO2 is outside [co2_vent_low, co2_vent_high] ppm,
+    // until it is `co2_vent_hysteresis` back inside. 0 turns a limit off.
+    pub co2_vent_low: Number,  // Plants used it up with the lights on
+    pub co2_vent_high: Number,
+    pub co2_vent_hysteresis: Number,
+
+    // Closed-loop LED (light mode Pid): the target light intensity scales `light_ppfd_target`
+    // (255 = all of it), the loop holds that on the light sensor
+    pub light_ppfd_target: Number, // µmol/m²/s
+    pub light_ppfd: PidGains,      // PPFD error -> LED intensity 0-255
+
+    // EC Dosing (target is `PlantConfiguration.nominal_ec`, in the EC sensor's ppm)
+    pub ec_dose_direction: DoseDirection,
+    pub ec_deadband: Number,        // No dosing within this of the target
+    pub ec_pump_ml_per_sec: Number, // Calibrated pump rate
+    pub ec_dose_ml_per_ppm: Number, // Pulse volume per ppm off target
+    pub ec_dose_min_ml: Number,
+    pub ec_dose_max_ml: Number,
+    pub ec_mix_secs: u32,           // Wait after a pulse before EC is read again
+
+    // Adaptive Tuner (air temperature and humidity cold-side loops, PID mode only)
+    pub tuner_enabled: bool,
+    pub tuner_kp_min: Number,
+    pub tuner_kp_max: Number,
+    pub tuner_ki_max: Number,
+    pub tuner_kd_max: Number,
+
+    // Fault Supervisor
+    pub fault_no_response_secs: u32,     // Full Peltier effort for this long...
+    pub fault_min_temp_change: Number,   // ...has to move the air temperature by this much
+    pub fault_peltier_delta_max: Number, // Hot minus cold side of a running Peltier
+    pub fault_pump_max_secs: u32,
+    pub fault_sensor_lost_secs: u32,
+    pub fault_sensor_frozen_secs: u32,   // Reading not moving while its Peltier's drive changes
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            modes: LoopModes::default(),
+            air_temp: PidGains::new(5.0, 0.05, 0.0), // Aggressive Kp
+            peltier_temp_heat: PidGains::new(2.0, 0.1, 0.0),
+            peltier_temp_cool: PidGains::new(5.0, 0.1, 0.0),
+            peltier_temp_min: Number::from_num(0.0), // Keep the inner surface from icing
+            peltier_temp_max: Number::from_num(60.0), // Same as the overheat cutoff
+            hum_cold_side: PidGains::new(2.0, 0.1, 0.0),
+            hum_cold_target: Number::from_num(5.0), // Target 5C for dehumidification
+            hum_cold_dew_margin: Number::from_num(3.0),
+            hum_cold_min: Number::from_num(2.0),
+            frost_temp: Number::from_num(0.5),
+            frost_delta_drop: Number::from_num(1.0),
+            frost_detect_secs: 10 * 60,
+            defrost_secs: 15 * 60,
+            k_ff_hum: Number::from_num(0.2), // Scaled down for 0-255 input
+            k_ff_vent: Number::from_num(0.2),
+            fan_temp_outer: PidGains::new(5.0, 0.1, 0.0),
+            fan_hum_hot: PidGains::new(5.0, 0.1, 0.0),
+            peltier_temp_diff_target: Number::from_num(10.0), // Target 10C difference (keep it low)
+            k_fan_effort: Number::from_num(0.5),
+            fan_base_day: Number::from_num(51), // ~20% of 255
+            fan_base_night: Number::from_num(0),
+            max_fan_speed: 204, // ~80% of 255
+            soil_low_threshold: Number::from_num(100), // Wet (Stop)
+            soil_high_threshold: Number::from_num(220), // Dry (Start)
+            water_cal_no_tray: Number::from_num(3000), 
+            water_cal_dry_tray: Number::from_num(2200),
+            water_cal_wet_tray: Number::from_num(1200),
+            pump_max_on_secs: 2 * 60,
+            pump_min_off_secs: 10 * 60,
+            pump_daily_budget_secs: 15 * 60,
+            pump_daily_budget_ml: 0,
+            pump_flow_ml_per_min: Number::from_num(100.0),
+            leaf_temp_offset: Number::from_num(2.0), // Transpiring leaf under LEDs
+            vpd_hysteresis: Number::from_num(0.1),
+            co2_vent_low: Number::from_num(350),
+            co2_vent_high: Number::from_num(1500),
+            co2_vent_hysteresis: Number::from_num(100),
+            light_ppfd_target: Number::from_num(400.0),
+            light_ppfd: PidGains::new(0.0, 0.05, 0.0), // Integral only, sensor noise does not reach the LED
+            ec_dose_direction: DoseDirection::Dilute,
+            ec_deadband: Number::from_num(50.0), // ppm
+            ec_pump_ml_per_sec: Number::from_num(1.5),
+            ec_dose_ml_per_ppm: Number::from_num(0.1),
+            ec_dose_min_ml: Number::from_num(2.0),
+            ec_dose_max_ml: Number::from_num(30.0),
+            ec_mix_secs: 5 * 60,
+            tuner_enabled: false,
+            tuner_kp_min: Number::from_num(0.1),
+            tuner_kp_max: Number::from_num(20.0),
+            tuner_ki_max: Number::from_num(10.0),
+            tuner_kd_max: Number::from_num(10.0),
+            fault_no_response_secs: 15 * 60,
+            fault_min_temp_change: Number::from_num(0.5),
+            fault_peltier_delta_max: Number::from_num(45.0),
+            fault_pump_max_secs: 5 * 60,
+            fault_sensor_lost_secs: 60,
+            fault_sensor_frozen_secs: 30 * 60,
+        }
+    }
+}
+
+impl ControlConfig {
+    /// Humidity Peltier cold-side setpoint: `hum_cold_dew_margin` below the dew point,
+    /// no lower than `hum_cold_min`.
+    pub fn hum_cold_setpoint(&self, dew_point: Option<Number>) -> Number {
+        match dew_point {
+            Some(dp) => (dp - self.hum_cold_dew_margin).max(self.hum_cold_min),
+            None => self.hum_cold_target,
+        }
+    }
+}
+
+/// Target state determined by the script engine
+#[derive(Clone, Debug, Default)]
+pub struct TargetState {
+    pub temp: Number,
+    pub humidity: u8,
+    /// Leaf VPD target in kPa, used instead of `humidity` when set
+    pub vpd: Option<Number>,
+    pub vent_on: bool,
+    pub light_intensity: u8,
+}
+
+#[cfg(feature = "firmware")]
+impl defmt::Format for TargetState {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "TargetState {{ temp: {}, humidity: {}, vpd: {}, vent_on: {}, light_intensity: {} }}", 
+            self.temp.to_num::<f32>(), 
+            self.humidity, 
+            self.vpd.map(|v| v.to_num::<f32>()), 
+            self.vent_on, 
+            self.light_intensity
+        );
+    }
+}
@@ -4,19 +4,8 @@ use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex;
 use alloc::rc::Rc;
 
-#[derive(Clone, Copy, Debug, Default, defmt::Format)]
-pub struct ActuatorOutputs {
-    pub peltier_temp_dir: bool, // true = Heat, false = Cool
-    pub peltier_temp_pwm: u8,
-    pub peltier_hum_pwm: u8,
-    pub fan_inner_speed: u8,
-    pub fan_temp_outer_speed: u8,
-    pub fan_hum_hot_speed: u8,
-    pub fan_vent_on: bool,
-    pub led_intensity: u8,
-    pub pump_nutrient: bool,
-    pub pump_water: bool,
-}
+mod actuator_outputs;
+pub use actuator_outputs::*;
 
 pub type SharedActuatorState = Rc<Mutex<CriticalSectionRawMutex, ActuatorOutputs>>;
 
@@ -25,10 +14,55 @@ pub trait HardwareInterface {
     async fn actuate(&mut self, outputs: &ActuatorOutputs);
 }
 
+/// Actuator outputs, already bound to their pins. Built by `actuator_pins!`.
+pub struct ActuatorPins<'a> {
+    pub led_pwm: Pwm<'a>,
+    pub fan_pwm: Pwm<'a>, // Controls both Inner (A) and Outer (B)
+    pub peltier_pwm: Pwm<'a>,
+    pub peltier_dir_pin: Output<'a>,
+    pub hum_pwm: Pwm<'a>, // Humidity Peltier (A) and its hot-side fan (B)
+    pub pump_nutrient: Output<'a>,
+    pub pump_water: Output<'a>,
+    pub fan_vent: Output<'a>,
+}
+
+/// Pin assignments for the actuators. This is the only place they are named;
+/// rewire the board here.
+///
+/// A PWM pin must match its slice: GPIO n is slice (n / 2) % 8, channel A if n is even.
+/// Pins already taken elsewhere: 0-1 (I2C), 6-8 and 14-15 (LCD, encoder),
+/// 23-25 and 29 (WiFi), 26-27 (ADC).
+#[macro_export]
+macro_rules! actuator_pins {
+    ($p:ident) => {{
+        use embassy_rp::gpio::{Level, Output};
+        use embassy_rp::pwm::{Config as PwmConfig, Pwm};
+        $crate::hardware_manager::ActuatorPins {
+            // GPIO16: Temp Peltier PWM (Slice 0 A), GPIO17: Temp Peltier Dir
+            peltier_pwm: Pwm::new_output_a($p.PWM_SLICE0, $p.PIN_16, PwmConfig::default()),
+            peltier_dir_pin: Output::new($p.PIN_17, Level::Low),
+            // GPIO18 (A) = Inner fan, GPIO19 (B) = Temp Peltier outer fan (Slice 1)
+            fan_pwm: Pwm::new_output_ab($p.PWM_SLICE1, $p.PIN_18, $p.PIN_19, PwmConfig::default()),
+            // GPIO20: LED (Slice 2 A)
+            led_pwm: Pwm::new_output_a($p.PWM_SLICE2, $p.PIN_20, PwmConfig::default()),
+            // GPIO10 (A) = Hum Peltier MOSFET, GPIO11 (B) = Hum Peltier hot-side fan (Slice 5)
+            hum_pwm: Pwm::new_output_ab($p.PWM_SLICE5, $p.PIN_10, $p.PIN_11, PwmConfig::default()),
+            pump_nutrient: Output::new($p.PIN_21, Level::Low),
+            pump_water: Output::new($p.PIN_22, Level::Low),
+            fan_vent: Output::new($p.PIN_28, Level::Low),
+        }
+    }};
+}
+
+/// Slowest the humidity hot-side fan may turn while its Peltier is powered.
+/// Without airflow the hot side overheats within seconds.
+const HUM_HOT_FAN_MIN: u8 = 128;
+
 pub struct HardwareManager<'a> {
     pub led_pwm: Pwm<'a>,
     pub fan_pwm: Pwm<'a>, // Controls both Inner (A) and Outer (B)
     pub peltier_pwm: Pwm<'a>, 
+    pub hum_pwm: Pwm<'a>, // Humidity Peltier (A) and its hot-side fan (B)
     pub pump_nutrient: Output<'a>,
     pub pump_water: Output<'a>, 
     pub fan_vent: Output<'a>,   
@@ -36,23 +70,16 @@ pub struct HardwareManager<'a> {
 }
 
 impl<'a> HardwareManager<'a> {
-    pub fn new(
-        led_pwm: Pwm<'a>,
-        fan_pwm: Pwm<'a>,
-        peltier_pwm: Pwm<'a>,
-        peltier_dir_pin: Output<'a>,
-        pump_nutrient: Output<'a>,
-        pump_water: Output<'a>,
-        fan_vent: Output<'a>,
-    ) -> Self {
+    pub fn new(pins: ActuatorPins<'a>) -> Self {
         Self {
-            led_pwm,
-            fan_pwm,
-            peltier_pwm,
-            peltier_dir_pin,
-            pump_nutrient,
-            pump_water,
-            fan_vent,
+            led_pwm: pins.led_pwm,
+            fan_pwm: pins.fan_pwm,
+            peltier_pwm: pins.peltier_pwm,
+            hum_pwm: pins.hum_pwm,
+            peltier_dir_pin: pins.peltier_dir_pin,
+            pump_nutrient: pins.pump_nutrient,
+            pump_water: pins.pump_water,
+            fan_vent: pins.fan_vent,
         }
     }
 }
@@ -98,6 +125,27 @@ impl<'a> HardwareInterface for HardwareManager<'a> {
         peltier_conf.compare_a = final_pwm as u16; 
         self.peltier_pwm.set_config(&peltier_conf);
 
+        // Humidity Peltier (MOSFET, no direction) and its hot-side fan - Share Slice 5
+        // 125 MHz / 19.5 / 256 = ~25 kHz: above hearing for the fan and slow enough for the
+        // MOSFET gate driver
+        let hum_fan = if outputs.peltier_hum_pwm > 0 {
+            outputs.fan_hum_hot_speed.max(HUM_HOT_FAN_MIN)
+        } else {
+            outputs.fan_hum_hot_speed
+        };
+        let mut hum_conf = PwmConfig::default();
+        hum_conf.top = 255;
+        hum_conf.divider = fixed::FixedU16::<fixed::types::extra::U4>::from_num(19.5);
+        hum_conf.compare_a = outputs.peltier_hum_pwm as u16;
+        hum_conf.compare_b = hum_fan as u16;
+        self.hum_pwm.set_config(&hum_conf);
+
+        if outputs.fan_vent_on {
+            self.fan_vent.set_high();
+        } else {
+            self.fan_vent.set_low();
+        }
+
         if outputs.pump_nutrient {
             self.pump_nutrient.set_high();
         } else {
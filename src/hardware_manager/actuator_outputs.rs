@@ -0,0 +1,17 @@
+//! What the control loop asks of the actuators, kept free of the HAL so it also builds on
+//! the host.
+
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "firmware", derive(defmt::Format))]
+pub struct ActuatorOutputs {
+    pub peltier_temp_dir: bool, // true = Heat, false = Cool
+    pub peltier_temp_pwm: u8,
+    pub peltier_hum_pwm: u8,
+    pub fan_inner_speed: u8,
+    pub fan_temp_outer_speed: u8,
+    pub fan_hum_hot_speed: u8,
+    pub fan_vent_on: bool,
+    pub led_intensity: u8,
+    pub pump_nutrient: bool,
+    pub pump_water: bool,
+}
@@ -0,0 +1,59 @@
+//! Host build of the script engine, for testing grow recipes on a PC.
+//!
+//! Only the user script engine, the plain data types it needs and the hardware-free control
+//! helpers (for their unit tests) are built here, with the same module paths as in the
+//! firmware. Without the `host` feature this crate is empty; the firmware itself is the
+//! `rp2040_plant_automation` binary.
+//!
+//! ```text
+//! cargo run --no-default-features --features host --target x86_64-unknown-linux-gnu \
+//!     --bin script_runner -- recipe.lisp sensors.csv --days 42
+//! ```
+
+#![cfg_attr(not(feature = "host"), no_std)]
+
+#[cfg(feature = "host")]
+extern crate alloc;
+
+#[cfg(feature = "host")]
+pub mod control {
+    mod types;
+    pub use types::*;
+    mod autotune;
+    pub use autotune::*;
+    mod supervisor;
+    pub use supervisor::*;
+    mod pump_guard;
+    pub use pump_guard::{BudgetEvent, PumpGuard};
+}
+
+#[cfg(feature = "host")]
+pub mod hardware_manager {
+    mod actuator_outputs;
+    pub use actuator_outputs::*;
+}
+
+#[cfg(feature = "host")]
+pub mod sensor_manager {
+    mod sensor_data;
+    pub use sensor_data::*;
+    pub mod sensor_filter;
+}
+
+#[cfg(feature = "host")]
+pub mod config_types;
+
+#[cfg(feature = "host")]
+pub mod userscript;
+
+// Same allocator as the firmware, so the sandbox sees the same heap accounting
+#[cfg(feature = "host")]
+static mut ARENA: core::mem::MaybeUninit<[u8; 64 << 20]> = core::mem::MaybeUninit::uninit();
+
+#[cfg(feature = "host")]
+#[allow(static_mut_refs)]
+#[global_allocator]
+static ALLOCATOR: userscript::sandbox::SandboxedAlloc<talc::Talck<spin::Mutex<()>, talc::ClaimOnOom>> =
+    userscript::sandbox::SandboxedAlloc::new(talc::Talc::new(unsafe {
+        talc::ClaimOnOom::new(talc::Span::from_array(ARENA.as_ptr().cast_mut()))
+    }).lock());
@@ -20,7 +20,7 @@ use alloc::rc::Rc;
 use core::mem::MaybeUninit;
 use embassy_executor::Spawner;
 use embassy_rp::bind_interrupts;
-use chrono::Timelike;
+use chrono::{Datelike, Timelike};
 use embassy_rp::clocks::{ClockConfig, CoreVoltage};
 use embassy_rp::config::Config;
 use embassy_rp::peripherals::PIO0;
@@ -45,20 +45,22 @@ mod ui;
 pub mod network;
 pub mod sensor_history;
 
-use embassy_rp::gpio::{Output, Level};
-use embassy_rp::pwm::{Pwm, Config as PwmConfig};
 use embassy_rp::adc::{Adc, Config as AdcConfig};
 use crate::hardware_manager::{HardwareManager, SharedActuatorState, ActuatorOutputs, HardwareInterface};
-use crate::control::PlantController;
+use crate::control::{ActuatorOverrides, Autotune, BudgetEvent, FaultCode, FaultSupervisor, PlantController, PumpGuard, SharedAutotune, SharedFaults, SharedOverrides, SharedTunerLog};
+use crate::userscript::{UserScript, ScriptError, ScriptStatus, SharedScriptStatus};
+use crate::userscript::sandbox::{SandboxedAlloc, take_quarantine};
+use embassy_rp::watchdog::Watchdog;
 
 static mut ARENA: MaybeUninit<[u8; 1024 * 160]> = MaybeUninit::uninit();
 
 // 힙 allocator
+// Wrapped so script evaluation can be held to its heap/step/time budget
 #[allow(static_mut_refs)]
 #[global_allocator]
-static ALLOCATOR: Talck<spin::Mutex<()>, ClaimOnOom> = Talc::new(unsafe {
+static ALLOCATOR: SandboxedAlloc<Talck<spin::Mutex<()>, ClaimOnOom>> = SandboxedAlloc::new(Talc::new(unsafe {
 	ClaimOnOom::new(talc::Span::from_array(ARENA.as_ptr().cast_mut()))
-}).lock();
+}).lock());
 
 bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => InterruptHandler<PIO0>;
@@ -72,6 +74,13 @@ async fn main(spawner: Spawner) {
     cc.core_voltage = CoreVoltage::V1_30;
     let p = embassy_rp::init(Config::new(cc));
 
+    // A script that tripped the sandbox before the reset is recorded in the watchdog scratch registers
+    let mut watchdog = Watchdog::new(p.WATCHDOG);
+    let script_quarantine = take_quarantine(&mut watchdog);
+    if let Some(q) = &script_quarantine {
+        defmt::warn!("Script was aborted before reset ({}), it stays disabled until changed", q.reason);
+    }
+
     //spawn input handling task
     let Pio {
         mut common, sm0, sm1, irq0, ..
@@ -108,47 +117,17 @@ async fn main(spawner: Spawner) {
     let shared_history: crate::sensor_history::SharedHistory = Rc::new(Mutex::new(Deque::new()));
     spawner.spawn(crate::sensor_history::history_task(shared_history.clone(), shared_sensor_data.clone()).unwrap());
 
-    spawner.spawn(crate::sensor_manager::sensor_task(i2c, adc, pin_soil, pin_ec, shared_sensor_data.clone()).unwrap());
+    spawner.spawn(crate::sensor_manager::sensor_task(i2c, adc, pin_soil, pin_ec, shared_sensor_data.clone(), shared_config.clone(), time_manager.clone()).unwrap());
 
-    // Hardware Peripherals
-    // PWMs
-    // GPIO16: Peltier PWM (Slice 0 A)
-    let peltier_pwm = Pwm::new_output_a(p.PWM_SLICE0, p.PIN_16, PwmConfig::default());
-    // GPIO17: Peltier Dir (Output)
-    let peltier_dir = Output::new(p.PIN_17, Level::Low);
-    
-    // GPIO18/19: Fans (Slice 1)
-    // GPIO18 (A) = Inner, GPIO19 (B) = Outer
-    let fan_pwm = Pwm::new_output_ab(p.PWM_SLICE1, p.PIN_18, p.PIN_19, PwmConfig::default());
-
-    // GPIO20: LED (Slice 2 A)
-    // 1000Hz PWM for LED
-    // Clock = 125MHz typically.
-    // 1kHz = 125M / (div * (top+1))
-    // Let div = 2.0 -> (top+1) = 62,500 -> top = 62499
-    let mut led_conf = PwmConfig::default();
-    led_conf.top = 62499; 
-    led_conf.divider = fixed::FixedU16::<fixed::types::extra::U4>::from_num(2.0);
-    let led_pwm = Pwm::new_output_a(p.PWM_SLICE2, p.PIN_20, led_conf);
-
-    // GPIO21: Pump Nutrient (Output)
-    let pump_nutrient = Output::new(p.PIN_21, Level::Low);
-    
-    // Mocked/Unused
-    let pump_water = Output::new(p.PIN_22, Level::Low); // Arbitrary unused
-    let fan_vent = Output::new(p.PIN_28, Level::Low);   // Arbitrary unused
-
-    let mut hardware = HardwareManager::new(
-        led_pwm,
-        fan_pwm,
-        peltier_pwm,
-        peltier_dir,
-        pump_nutrient,
-        pump_water,
-        fan_vent
-    );
+    // Actuators, pins are assigned in hardware_manager.rs
+    let mut hardware = HardwareManager::new(actuator_pins!(p));
 
     let shared_actuator_state: SharedActuatorState = Rc::new(Mutex::new(ActuatorOutputs::default()));
+    let shared_script_status: SharedScriptStatus = Rc::new(Mutex::new(ScriptStatus::default()));
+    let shared_overrides: SharedOverrides = Rc::new(Mutex::new(ActuatorOverrides::default()));
+    let shared_tuner_log: SharedTunerLog = Rc::new(Mutex::new(Deque::new()));
+    let shared_autotune: SharedAutotune = Rc::new(Mutex::new(Autotune::default()));
+    let shared_faults: SharedFaults = Rc::new(Mutex::new(FaultSupervisor::default()));
 
     let (wifi_control, net_steck) = network::init_network(
         &spawner,
@@ -156,6 +135,11 @@ async fn main(spawner: Spawner) {
         time_manager.clone(),
         shared_sensor_data.clone(),
         shared_history.clone(),
+        shared_script_status.clone(),
+        shared_overrides.clone(),
+        shared_tuner_log.clone(),
+        shared_autotune.clone(),
+        shared_faults.clone(),
         &mut common,
         sm1,
         irq0,
@@ -174,6 +158,10 @@ async fn main(spawner: Spawner) {
         time_manager.clone(),
         shared_sensor_data.clone(),
         shared_actuator_state.clone(),
+        shared_script_status.clone(),
+        shared_overrides.clone(),
+        shared_autotune.clone(),
+        shared_faults.clone(),
         // # hardwares
         &mut common,
         sm0,
@@ -192,6 +180,26 @@ async fn main(spawner: Spawner) {
     };
     
     let mut controller = PlantController::new(initial_calibration.pid_config);
+    let mut user_script = UserScript::new();
+    let mut loaded_script_revision: Option<u32> = None;
+    let mut last_good_targets = script_quarantine.as_ref().and_then(|q| q.last_good.clone());
+    user_script.set_quarantine(script_quarantine);
+    {
+        let cfg = shared_config.lock().await;
+        user_script.set_state(cfg.script_state().clone());
+    }
+    // Script state is written to flash at most this often to spare the flash
+    let script_state_save_interval = embassy_time::Duration::from_secs(10 * 60);
+    let mut script_state_dirty = false;
+    let mut last_script_state_save = embassy_time::Instant::now();
+
+    // Tray pump limits, the counters survive reboots so the daily budget does too
+    let mut pump_guard = PumpGuard::new(shared_config.lock().await.pump_counters());
+    if pump_guard.counters().locked_out {
+        shared_faults.lock().await.raise(FaultCode::PumpBudget, None);
+    }
+    let mut saved_pump_counters = pump_guard.counters();
+    let mut last_pump_counters_save = embassy_time::Instant::now();
 
     loop {
         // Run control logic every 10 * 100ms = 1s?
@@ -212,41 +220,137 @@ async fn main(spawner: Spawner) {
                     let data = shared_sensor_data.lock().await;
                     data.clone()
                 };
-                
+
                 // Get Targets and Calibration
-                let (target_temp, light_intensity, start_hour, end_hour, pid_config) = {
+                let now_utc = time_manager.get_time();
+                let now_ts = now_utc.map(|dt| dt.timestamp() as u64);
+                let (now_local, schedule_targets, days_since_start, pid_config, nominal_ec) = {
                     let cfg = shared_config.lock().await;
                     let pc = cfg.plant_config();
-                    (pc.target_temp, pc.light_intensity, pc.light_start_hour, pc.light_end_hour,
-                     cfg.calibration().pid_config) // Copy
+
+                    // Local time for the schedule and the script
+                    let now_local = now_utc.and_then(|utc| cfg.settings().local_time(utc));
+                    let current_hour = now_local.map(|dt| dt.hour() as u8).unwrap_or(12);
+
+                    // Reload the script if it was changed (HTTP / MQTT)
+                    if loaded_script_revision != Some(cfg.script_revision()) {
+                        loaded_script_revision = Some(cfg.script_revision());
+                        match user_script.update_script(pc.script_str()) {
+                            Ok(()) => defmt::info!("Script reloaded (revision {}): {}", cfg.script_revision(), user_script.stats()),
+                            Err(e) => defmt::warn!("Script compile failed: {}", e),
+                        }
+                    }
+
+                    (now_local, pc.scheduled_targets(current_hour), pc.days_since_start(now_ts),
+                     cfg.calibration().pid_config, pc.nominal_ec) // Copy
                 };
                 
                 // Sync Controller Config (incl. Water Tray Calibration)
                 controller.update_config(pid_config);
+                controller.set_ec_target(nominal_ec);
 
-                // Schedule Logic
-                let current_hour = if let Some(dt) = time_manager.get_time() {
-                    dt.hour() as u8
+                // Script targets, falling back to the static schedule on error,
+                // or to the last good script targets if the sandbox stopped it
+                let (targets, script_active, script_error) = if user_script.is_empty() {
+                    (schedule_targets, false, None)
                 } else {
-                    12 
-                };
-                let is_light_on = if start_hour < end_hour {
-                    current_hour >= start_hour && current_hour < end_hour
-                } else {
-                     current_hour >= start_hour || current_hour < end_hour
-                };
-                let target_light = if is_light_on { light_intensity } else { 0 };
-
-                use crate::control::TargetState;
-                use fixed::types::I16F16;
-                let targets = TargetState {
-                    temp: I16F16::from_num(target_temp),
-                    humidity: 60, 
-                    vent_on: true, 
-                    light_intensity: target_light,
+                    match user_script.calculate_targets(&sensors, &schedule_targets, now_local, days_since_start) {
+                        Ok(t) => {
+                            last_good_targets = Some(t.clone());
+                            (t, true, None)
+                        }
+                        Err(e @ ScriptError::LimitExceeded(_)) => {
+                            let fallback = last_good_targets.clone().unwrap_or(schedule_targets);
+                            (fallback, false, Some(alloc::format!("{}", e)))
+                        }
+                        Err(e) => (schedule_targets, false, Some(alloc::format!("{}", e))),
+                    }
                 };
+
+                {
+                    let mut st = shared_script_status.lock().await;
+                    if script_error.is_some() && st.last_error != script_error {
+                        defmt::warn!("Script failed, using schedule: {}", script_error.as_deref().unwrap_or(""));
+                    }
+                    st.active_targets = targets.clone();
+                    st.script_active = script_active;
+                    st.last_error = script_error;
+                    st.stats = user_script.stats();
+                }
+
+                script_state_dirty |= user_script.take_state_changed();
+                if script_state_dirty && last_script_state_save.elapsed() >= script_state_save_interval {
+                    let state = user_script.state().clone();
+                    let mut cfg = shared_config.lock().await;
+                    cfg.update_script_state(|s| *s = state).await;
+                    script_state_dirty = false;
+                    last_script_state_save = embassy_time::Instant::now();
+                }
                 
-                let outputs = controller.step(&sensors, targets).await;
+                // Autotune commands from the web UI
+                let autotune_command = shared_autotune.lock().await.command.take();
+                if let Some(command) = autotune_command {
+                    controller.handle_autotune_command(command);
+                }
+
+                let mut outputs = controller.step(&sensors, targets).await;
+                shared_autotune.lock().await.status = controller.autotune_status();
+
+                // Adaptive tuner: keep a log of its gain changes, save the gains once they settle
+                let gain_changes = controller.take_gain_changes();
+                if !gain_changes.is_empty() {
+                    let mut log = shared_tuner_log.lock().await;
+                    for mut change in gain_changes {
+                        change.ts = now_ts.unwrap_or(0);
+                        if log.is_full() {
+                            log.pop_front();
+                        }
+                        let _ = log.push_back(change);
+                    }
+                }
+                if controller.take_tuned_gains_settled() {
+                    let tuned = controller.config();
+                    defmt::info!("Tuned gains settled, saving");
+                    let mut cfg = shared_config.lock().await;
+                    cfg.update_calibration(|cal| {
+                        cal.pid_config.air_temp = tuned.air_temp;
+                        cal.pid_config.hum_cold_side = tuned.hum_cold_side;
+                    }).await;
+                }
+
+                // Manual overrides (HTTP / MQTT), the safety limits still have the last word
+                if shared_overrides.lock().await.apply(&mut outputs) {
+                    PlantController::apply_safety_limits(&mut outputs, &sensors);
+                }
+
+                // Tray pump run-time limits and daily budget, after the overrides so they hold
+                // for manual runs too. Acknowledging the budget fault lifts the lockout, a new day
+                // clears the fault.
+                let today = now_local.map(|dt| dt.num_days_from_ce());
+                if pump_guard.counters().locked_out && !shared_faults.lock().await.is_latched(FaultCode::PumpBudget) {
+                    pump_guard.reset_budget();
+                }
+                match pump_guard.apply(&mut outputs, &pid_config, today) {
+                    BudgetEvent::Exceeded => shared_faults.lock().await.raise(FaultCode::PumpBudget, now_ts),
+                    BudgetEvent::Renewed => shared_faults.lock().await.clear(FaultCode::PumpBudget),
+                    BudgetEvent::None => {}
+                }
+                {
+                    // Day changes and lockouts are saved right away, the run time every 10 minutes
+                    let counters = pump_guard.counters();
+                    let save = counters != saved_pump_counters
+                        && (counters.day != saved_pump_counters.day
+                            || counters.locked_out != saved_pump_counters.locked_out
+                            || last_pump_counters_save.elapsed() >= script_state_save_interval);
+                    shared_config.lock().await.update_pump_counters(counters, save).await;
+                    if save {
+                        saved_pump_counters = counters;
+                        last_pump_counters_save = embassy_time::Instant::now();
+                    }
+                }
+
+                // Fault supervisor: latched faults hold their actuators safe until acknowledged
+                shared_faults.lock().await.supervise(&sensors, &mut outputs, &pid_config, now_ts);
                 
                 // Actuate
                 hardware.actuate(&outputs).await;
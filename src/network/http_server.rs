@@ -11,11 +11,21 @@ use picoserve::{Router, Config, Timeouts, Server};
 use embassy_time::Duration;
 
 use crate::config_manager::SharedConfig;
+use crate::config_types::{AirSensorRoles, Co2SensorConfig, FilterConfig, LightSensorConfig, NtcConfig, PlantConfiguration};
 use crate::network::ShareNetworkStack;
 use crate::sensor_manager::SharedSensorData;
 use crate::sensor_history::SharedHistory;
+use crate::control::{
+    AutotuneCommand, AutotuneDirection, AutotuneSettings, AutotuneStatus, FaultAck, GainChange, LoopModesUpdate,
+    OverrideRequest, PumpGuard, SharedAutotune, SharedFaults, SharedOverrides, SharedTunerLog, TargetState, TuningRule,
+};
+use crate::time_manager::SharedTimeManager;
+use crate::userscript::{self, ScriptError, SharedScriptStatus};
 use serde::{Deserialize, Serialize};
 
+/// A nominal EC in the old mS/cm range, e.g. 1.2 typed into the ppm field
+const NOMINAL_EC_UNIT_ERROR: &str = "nominal_ec is in ppm as the EC sensor reads it (e.g. 700), not mS/cm";
+
 #[derive(Deserialize)]
 struct ConfigUpdate {
     plant_name: Option<String>,
@@ -43,6 +53,155 @@ struct FullConfigResponse {
     water_cal_wet_tray: i32,
 }
 
+#[derive(Serialize)]
+struct ScriptStatusResponse {
+    script_active: bool,
+    error: Option<String>,
+    target_temp: f32,
+    target_humidity: u8,
+    target_vpd: Option<f32>,
+    vent_on: bool,
+    light_intensity: u8,
+    compile_time_us: u32,
+    compile_heap_bytes: u32,
+    eval_time_us: u32,
+    eval_heap_bytes: u32,
+    heap_in_use: u32,
+    eval_steps: u32,
+}
+
+/// Sensor values for a dry run; anything left out uses the current reading.
+#[derive(Deserialize)]
+struct ValidateSensors {
+    temp: Option<f32>,
+    humidity: Option<u8>,
+    soil: Option<f32>,
+    ec: Option<f32>,
+    co2: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct ScriptValidateRequest {
+    script_source: String,
+    sensors: Option<ValidateSensors>,
+    days: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct TargetStateResponse {
+    temp: f32,
+    humidity: u8,
+    vpd: Option<f32>,
+    vent_on: bool,
+    light_intensity: u8,
+}
+
+#[derive(Serialize)]
+struct ScriptErrorResponse {
+    kind: &'static str,
+    message: String,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TunerUpdate {
+    enabled: Option<bool>,
+    kp_min: Option<f32>,
+    kp_max: Option<f32>,
+    ki_max: Option<f32>,
+    kd_max: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct TunerResponse {
+    enabled: bool,
+    kp_min: f32,
+    kp_max: f32,
+    ki_max: f32,
+    kd_max: f32,
+    changes: alloc::vec::Vec<GainChange>,
+}
+
+#[derive(Serialize)]
+struct AutotuneResponse {
+    status: AutotuneStatus,
+    // Candidate gains [kp, ki, kd] once a result is waiting for confirmation
+    ziegler_nichols: Option<[f32; 3]>,
+    tyreus_luyben: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+struct AutotuneConfirm {
+    rule: TuningRule,
+}
+
+#[derive(Serialize)]
+struct ClimateResponse {
+    temp: Option<f32>,
+    humidity: Option<u8>,
+    vpd: Option<f32>,
+    leaf_vpd: Option<f32>,
+    dew_point: Option<f32>,
+    co2: Option<f32>, // ppm
+    ppfd: Option<f32>, // µmol/m²/s
+    dli: f32,          // mol/m² so far today
+    /// Humidity Peltier cold-side setpoint derived from the dew point
+    cold_side_target: f32,
+}
+
+#[derive(Serialize)]
+struct PumpStatusResponse {
+    on_secs_today: u32,
+    ml_today: u32,
+    budget_secs: u32,
+    budget_ml: u32, // 0 = time budget only
+    locked_out: bool,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: &'static str,
+}
+
+#[derive(Serialize)]
+struct ScriptValidateResponse {
+    ok: bool,
+    targets: Option<TargetStateResponse>,
+    error: Option<ScriptErrorResponse>,
+}
+
+impl ScriptValidateResponse {
+    fn from_result(result: Result<TargetState, ScriptError>) -> Self {
+        match result {
+            Ok(t) => Self {
+                ok: true,
+                targets: Some(TargetStateResponse {
+                    temp: t.temp.to_num(),
+                    humidity: t.humidity,
+                    vpd: t.vpd.map(|v| v.to_num()),
+                    vent_on: t.vent_on,
+                    light_intensity: t.light_intensity,
+                }),
+                error: None,
+            },
+            Err(e) => {
+                let position = e.position();
+                Self {
+                    ok: false,
+                    targets: None,
+                    error: Some(ScriptErrorResponse {
+                        kind: e.kind(),
+                        message: e.message(),
+                        line: position.map(|(line, _)| line),
+                        column: position.map(|(_, column)| column),
+                    }),
+                }
+            }
+        }
+    }
+}
+
 const HTML_HEAD: &str = r#"
 <!DOCTYPE html>
 <html>
@@ -57,7 +216,7 @@ const HTML_HEAD: &str = r#"
         button { margin-top: 20px; padding: 10px 20px; }
         .row { display: flex; align-items: center; gap: 10px; }
     </style>
-    <script src="/script.js?v=3"></script>
+    <script src="/script.js?v=6"></script>
 </head>
 <body>
     <h1>Configuration</h1>
@@ -87,6 +246,156 @@ async function readTrayTo(id) {
         alert('Failed to read sensor');
     }
 }
+
+function fmtGains(g) {
+    return g ? 'kp ' + g[0].toFixed(3) + ', ki ' + g[1].toFixed(4) + ', kd ' + g[2].toFixed(3) : '';
+}
+
+async function autotunePoll() {
+    try {
+        const r = await (await fetch('/api/autotune')).json();
+        const s = r.status;
+        let text = s.state;
+        if (s.state === 'running') {
+            text = 'Running (' + s.direction + '), setpoint ' + s.setpoint.toFixed(1) + ' C, cycle ' + s.cycle + '/' + s.cycles + ', step ' + s.steps;
+        } else if (s.state === 'done') {
+            text = 'Done (' + s.result.direction + '): Ku ' + s.result.ku.toFixed(2) + ', Pu ' + s.result.pu_secs.toFixed(0) + ' s'
+                + ' | Ziegler-Nichols: ' + fmtGains(r.ziegler_nichols) + ' | Tyreus-Luyben: ' + fmtGains(r.tyreus_luyben);
+        } else if (s.state === 'aborted') {
+            text = 'Aborted: ' + s.reason;
+        }
+        document.getElementById('at_status').textContent = text;
+    } catch (e) {}
+}
+
+async function autotunePost(path, body) {
+    const r = await fetch('/api/autotune/' + path, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(body || {}),
+    });
+    if (!r.ok) {
+        alert((await r.json()).error);
+    }
+    autotunePoll();
+}
+
+function autotuneStart() {
+    autotunePost('start', {
+        direction: document.getElementById('at_direction').value,
+        signal: document.getElementById('at_signal').value,
+    });
+}
+
+function autotuneConfirm() {
+    if (confirm('Save the tuned Peltier gains?')) {
+        autotunePost('confirm', { rule: document.getElementById('at_rule').value });
+    }
+}
+
+async function faultsPoll() {
+    try {
+        const faults = await (await fetch('/api/faults')).json();
+        document.getElementById('fault_list').textContent = faults.length
+            ? faults.map(f => f.id + ': ' + f.message).join(' | ')
+            : 'None';
+    } catch (e) {}
+}
+
+async function faultsAck() {
+    await fetch('/api/faults/ack', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: '{}',
+    });
+    faultsPoll();
+}
+
+function ntcShowModel() {
+    const beta = document.getElementById('ntc_model').value === 'beta';
+    document.getElementById('ntc_beta').style.display = beta ? 'flex' : 'none';
+    document.getElementById('ntc_sh').style.display = beta ? 'none' : 'flex';
+}
+
+async function ntcLoad() {
+    try {
+        const n = await (await fetch('/api/sensors/ntc')).json();
+        const val = (id, v) => document.getElementById(id).value = v;
+        val('ntc_series', n.series_resistor);
+        document.getElementById('ntc_to_ground').checked = n.ntc_to_ground;
+        if (n.model.beta) {
+            val('ntc_model', 'beta');
+            val('ntc_r0', n.model.beta.r0);
+            val('ntc_t0', n.model.beta.t0);
+            val('ntc_b', n.model.beta.beta);
+        } else {
+            val('ntc_model', 'steinhart_hart');
+            val('ntc_a', n.model.steinhart_hart.a);
+            val('ntc_sh_b', n.model.steinhart_hart.b);
+            val('ntc_c', n.model.steinhart_hart.c);
+        }
+        ntcShowModel();
+    } catch (e) {}
+}
+
+async function ntcSave() {
+    const num = id => parseFloat(document.getElementById(id).value);
+    const model = document.getElementById('ntc_model').value === 'beta'
+        ? { beta: { r0: num('ntc_r0'), t0: num('ntc_t0'), beta: num('ntc_b') } }
+        : { steinhart_hart: { a: num('ntc_a'), b: num('ntc_sh_b'), c: num('ntc_c') } };
+    const r = await fetch('/api/sensors/ntc', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            model: model,
+            series_resistor: num('ntc_series'),
+            ntc_to_ground: document.getElementById('ntc_to_ground').checked,
+        }),
+    });
+    if (!r.ok) {
+        alert((await r.json()).error);
+    }
+    ntcLoad();
+}
+
+async function rolesLoad() {
+    try {
+        const r = await (await fetch('/api/sensors/roles')).json();
+        document.getElementById('role_sht20').value = r.sht20;
+        document.getElementById('role_aht20').value = r.aht20;
+        document.getElementById('role_fuse').checked = r.fuse;
+    } catch (e) {}
+}
+
+async function rolesSave() {
+    await fetch('/api/sensors/roles', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            sht20: document.getElementById('role_sht20').value,
+            aht20: document.getElementById('role_aht20').value,
+            fuse: document.getElementById('role_fuse').checked,
+        }),
+    });
+    rolesLoad();
+}
+
+window.addEventListener('load', () => {
+    if (document.getElementById('ntc_model')) {
+        ntcLoad();
+    }
+    if (document.getElementById('role_sht20')) {
+        rolesLoad();
+    }
+    if (document.getElementById('fault_list')) {
+        faultsPoll();
+        setInterval(faultsPoll, 5000);
+    }
+    if (document.getElementById('at_status')) {
+        autotunePoll();
+        setInterval(autotunePoll, 3000);
+    }
+});
 "#;
 
 const HTML_FOOT: &str = r#"
@@ -112,6 +421,51 @@ struct AppState {
     config: SharedConfig,
     sensor_data: SharedSensorData,
     history: SharedHistory,
+    script_status: SharedScriptStatus,
+    overrides: SharedOverrides,
+    tuner_log: SharedTunerLog,
+    autotune: SharedAutotune,
+    faults: SharedFaults,
+    time_manager: SharedTimeManager,
+}
+
+/// Compiles and runs `source` once against the current sensors, schedule and script state,
+/// without touching the script the control loop is running.
+async fn dry_run_script(
+    state: &AppState,
+    source: &str,
+    overrides: Option<&ValidateSensors>,
+    days: Option<u32>,
+) -> Result<TargetState, ScriptError> {
+    use crate::control::Number;
+    use crate::sensor_manager::TempHumReading;
+    use chrono::Timelike;
+
+    let mut sensors = state.sensor_data.lock().await.clone();
+    if let Some(o) = overrides {
+        if o.temp.is_some() || o.humidity.is_some() {
+            let current = sensors.internal.unwrap_or(TempHumReading { temp: Number::from_num(25), hum: 50 });
+            sensors.internal = Some(TempHumReading {
+                temp: o.temp.map(Number::from_num).unwrap_or(current.temp),
+                hum: o.humidity.unwrap_or(current.hum),
+            });
+        }
+        if let Some(v) = o.soil { sensors.soil_moisture = Some(Number::from_num(v)); }
+        if let Some(v) = o.ec { sensors.ec_level = Some(Number::from_num(v)); }
+        if let Some(v) = o.co2 { sensors.co2_level = Some(Number::from_num(v)); }
+    }
+
+    let now_utc = state.time_manager.get_time();
+    let (now_local, defaults, days_since_start, script_state) = {
+        let cfg = state.config.lock().await;
+        let now_local = now_utc.and_then(|utc| cfg.settings().local_time(utc));
+        let hour = now_local.map(|dt| dt.hour() as u8).unwrap_or(12);
+        let pc = cfg.plant_config();
+        let days_since_start = days.unwrap_or_else(|| pc.days_since_start(now_utc.map(|dt| dt.timestamp() as u64)));
+        (now_local, pc.scheduled_targets(hour), days_since_start, cfg.script_state().clone())
+    };
+
+    userscript::dry_run(source, &sensors, &defaults, now_local, days_since_start, script_state)
 }
 
 async fn index(State(state): State<AppState>) -> impl IntoResponse {
@@ -127,6 +481,12 @@ async fn index(State(state): State<AppState>) -> impl IntoResponse {
         script_source_str.push_str(s);
     }
 
+    let script_error = state.script_status.lock().await.last_error.clone();
+    let script_error_html = match script_error {
+        Some(e) => format!("<p><b>Script error (using schedule):</b> {}</p>", html_escape(&e)),
+        None => String::new(),
+    };
+
     let mut response_buffer = String::new();
     response_buffer.push_str(HTML_HEAD);
     let _ = write!(response_buffer, r#"
@@ -134,9 +494,9 @@ async fn index(State(state): State<AppState>) -> impl IntoResponse {
         <label for="plant_name">Plant Name:</label>
         <input type="text" id="plant_name" name="plant_name" value="{}">
         
-        <label for="nominal_ec">Nominal EC:</label>
+        <label for="nominal_ec">Nominal EC (ppm):</label>
         <div class="row">
-            <input type="number" step="0.01" id="nominal_ec" name="nominal_ec" value="{}">
+            <input type="number" step="1" min="0" id="nominal_ec" name="nominal_ec" value="{}">
             <button type="button" onclick="fetchEC()">Read Sensor</button>
         </div>
 
@@ -177,10 +537,62 @@ async fn index(State(state): State<AppState>) -> impl IntoResponse {
         <hr>
 
         <label for="script_source">Script Source:</label>
+        {}
         <textarea id="script_source" name="script_source" rows="10">{}</textarea>
         
         <button type="submit">Save</button>
     </form>
+
+    <hr>
+    <h3>Peltier NTC Calibration</h3>
+    <div class="row">
+        <select id="ntc_model" onchange="ntcShowModel()"><option value="beta">Beta</option><option value="steinhart_hart">Steinhart-Hart</option></select>
+        <label><input type="checkbox" id="ntc_to_ground" style="width:auto"> NTC to ground</label>
+    </div>
+    <label for="ntc_series">Series Resistor (Ohm):</label>
+    <input type="number" step="1" id="ntc_series">
+    <div class="row" id="ntc_beta">
+        <label for="ntc_r0">R0 (Ohm):</label><input type="number" step="1" id="ntc_r0">
+        <label for="ntc_t0">T0 (C):</label><input type="number" step="0.1" id="ntc_t0">
+        <label for="ntc_b">Beta:</label><input type="number" step="1" id="ntc_b">
+    </div>
+    <div class="row" id="ntc_sh">
+        <label for="ntc_a">A:</label><input type="text" id="ntc_a">
+        <label for="ntc_sh_b">B:</label><input type="text" id="ntc_sh_b">
+        <label for="ntc_c">C:</label><input type="text" id="ntc_c">
+    </div>
+    <button type="button" onclick="ntcSave()">Save NTC</button>
+
+    <hr>
+    <h3>Air Sensors</h3>
+    <div class="row">
+        <label for="role_sht20">SHT20:</label><select id="role_sht20"><option value="internal">Internal</option><option value="external">External</option><option value="spare">Spare</option></select>
+        <label for="role_aht20">AHT20:</label><select id="role_aht20"><option value="internal">Internal</option><option value="external">External</option><option value="spare">Spare</option></select>
+        <label><input type="checkbox" id="role_fuse" style="width:auto"> Fuse</label>
+    </div>
+    <p>Failover needs both sensors set to Internal. With one Internal sensor, losing it raises F05 and stops the Peltiers; the External sensor is never used for the chamber.</p>
+    <button type="button" onclick="rolesSave()">Save roles</button>
+
+    <hr>
+    <h3>Faults</h3>
+    <p id="fault_list">-</p>
+    <button type="button" onclick="faultsAck()">Acknowledge all</button>
+    <p>Acknowledging F08 lifts the tray pump lockout with a fresh daily budget.</p>
+
+    <hr>
+    <h3>Peltier Autotune</h3>
+    <div class="row">
+        <select id="at_direction"><option value="heat">Heat gains</option><option value="cool">Cool gains</option></select>
+        <select id="at_signal"><option value="peltier_ntc">Peltier NTC</option><option value="air_temp">Air temperature</option></select>
+        <button type="button" onclick="autotuneStart()">Start</button>
+        <button type="button" onclick="autotunePost('abort')">Abort</button>
+    </div>
+    <p id="at_status">-</p>
+    <div class="row">
+        <select id="at_rule"><option value="ziegler_nichols">Ziegler-Nichols</option><option value="tyreus_luyben">Tyreus-Luyben</option></select>
+        <button type="button" onclick="autotuneConfirm()">Save gains</button>
+        <button type="button" onclick="autotunePost('discard')">Discard</button>
+    </div>
     "#, 
     html_escape(&plant_conf.plant_name), 
     plant_conf.nominal_ec, 
@@ -191,6 +603,7 @@ async fn index(State(state): State<AppState>) -> impl IntoResponse {
     tray_no,
     tray_dry,
     tray_wet,
+    script_error_html,
     html_escape(&script_source_str));
     response_buffer.push_str(HTML_FOOT);
 
@@ -267,6 +680,17 @@ async fn update_config(State(state): State<AppState>, body: String) -> impl Into
         }
     }
 
+    if !PlantConfiguration::nominal_ec_is_ppm(nominal_ec) {
+        return Response::new(StatusCode::BAD_REQUEST, String::from(NOMINAL_EC_UNIT_ERROR))
+            .with_headers([("Content-Type", "text/plain")]);
+    }
+
+    if let Err(e) = dry_run_script(&state, &script_source, None, None).await {
+        defmt::warn!("Rejected script: {}", e.message().as_str());
+        return Response::new(StatusCode::BAD_REQUEST, format!("Script rejected: {}", e))
+            .with_headers([("Content-Type", "text/plain")]);
+    }
+
     {
         let mut cfg = state.config.lock().await;
         
@@ -293,7 +717,7 @@ async fn update_config(State(state): State<AppState>, body: String) -> impl Into
         }
     }
 
-    Response::new(StatusCode::SEE_OTHER, "")
+    Response::new(StatusCode::SEE_OTHER, String::new())
         .with_headers([("Location", "/")])
 }
 
@@ -323,6 +747,20 @@ async fn update_config_json(
     State(state): State<AppState>, 
     picoserve::extract::Json(update): picoserve::extract::Json<ConfigUpdate>
 ) -> impl IntoResponse {
+    if update.nominal_ec.is_some_and(|v| !PlantConfiguration::nominal_ec_is_ppm(v)) {
+        let json = serde_json::to_string(&ErrorResponse { error: NOMINAL_EC_UNIT_ERROR }).unwrap_or_default();
+        return Response::new(StatusCode::BAD_REQUEST, json)
+            .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")]);
+    }
+
+    if let Some(source) = &update.script_source {
+        if let Err(e) = dry_run_script(&state, source, None, None).await {
+            let json = serde_json::to_string(&ScriptValidateResponse::from_result(Err(e))).unwrap_or_default();
+            return Response::new(StatusCode::BAD_REQUEST, json)
+                .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")]);
+        }
+    }
+
     let mut cfg = state.config.lock().await;
     
     cfg.update_plant_config(|c| {
@@ -347,7 +785,152 @@ async fn update_config_json(
         }).await;
     }
     
-    Response::new(StatusCode::OK, "{}")
+    Response::new(StatusCode::OK, String::from("{}"))
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Live chamber climate. Leaf VPD uses the configured leaf temperature offset.
+async fn get_climate(State(state): State<AppState>) -> impl IntoResponse {
+    let pid_config = state.config.lock().await.calibration().pid_config;
+    let leaf_offset: f32 = pid_config.leaf_temp_offset.to_num();
+    let resp = {
+        let data = state.sensor_data.lock().await;
+        ClimateResponse {
+            temp: data.internal.map(|r| r.temp.to_num()),
+            humidity: data.internal.map(|r| r.hum),
+            vpd: data.vpd.map(|v| v.to_num()),
+            leaf_vpd: data.internal.map(|r| {
+                let t: f32 = r.temp.to_num();
+                crate::sensor_manager::vapor_pressure_deficit(t, r.hum as f32, t - leaf_offset)
+            }),
+            dew_point: data.dew_point.map(|v| v.to_num()),
+            co2: data.co2_level.map(|v| v.to_num()),
+            ppfd: data.ppfd.map(|v| v.to_num()),
+            dli: data.dli.to_num(),
+            cold_side_target: pid_config.hum_cold_setpoint(data.dew_point).to_num(),
+        }
+    };
+    let json = serde_json::to_string(&resp).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_filter(State(state): State<AppState>) -> impl IntoResponse {
+    let filter = state.config.lock().await.calibration().filter;
+    let json = serde_json::to_string(&filter).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Replaces the sensor filter settings, see `FilterConfig`. The sensor task picks them up.
+async fn update_filter(
+    State(state): State<AppState>,
+    picoserve::extract::Json(filter): picoserve::extract::Json<FilterConfig>,
+) -> impl IntoResponse {
+    let valid = filter.channels.iter().all(|c| c.q >= 0.0 && c.r > 0.0) && filter.outlier_sigma >= 0.0;
+    let (status, json) = if valid {
+        state.config.lock().await.update_calibration(|cal| cal.filter = filter).await;
+        (StatusCode::OK, serde_json::to_string(&filter).unwrap_or_default())
+    } else {
+        let err = ErrorResponse { error: "q must be >= 0, r > 0 and outlier_sigma >= 0" };
+        (StatusCode::BAD_REQUEST, serde_json::to_string(&err).unwrap_or_default())
+    };
+    Response::new(status, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_sensor_roles(State(state): State<AppState>) -> impl IntoResponse {
+    let roles = state.config.lock().await.calibration().air_sensors;
+    let json = serde_json::to_string(&roles).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Reassigns the air sensors, see `AirSensorRoles`. The sensor task picks them up.
+async fn update_sensor_roles(
+    State(state): State<AppState>,
+    picoserve::extract::Json(roles): picoserve::extract::Json<AirSensorRoles>,
+) -> impl IntoResponse {
+    state.config.lock().await.update_calibration(|cal| cal.air_sensors = roles).await;
+    let json = serde_json::to_string(&roles).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_co2_sensor(State(state): State<AppState>) -> impl IntoResponse {
+    let co2 = state.config.lock().await.calibration().co2;
+    let json = serde_json::to_string(&co2).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Changes the CO2 sensor settings, see `Co2SensorConfig`. The sensor task sends them to the sensor.
+async fn update_co2_sensor(
+    State(state): State<AppState>,
+    picoserve::extract::Json(co2): picoserve::extract::Json<Co2SensorConfig>,
+) -> impl IntoResponse {
+    let (status, json) = if (700..=1200).contains(&co2.ambient_pressure_hpa) {
+        state.config.lock().await.update_calibration(|cal| cal.co2 = co2).await;
+        (StatusCode::OK, serde_json::to_string(&co2).unwrap_or_default())
+    } else {
+        let err = ErrorResponse { error: "ambient_pressure_hpa must be 700-1200" };
+        (StatusCode::BAD_REQUEST, serde_json::to_string(&err).unwrap_or_default())
+    };
+    Response::new(status, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_light_sensor(State(state): State<AppState>) -> impl IntoResponse {
+    let light = state.config.lock().await.calibration().light;
+    let json = serde_json::to_string(&light).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Changes the lux to PPFD factor, see `LightSensorConfig`.
+async fn update_light_sensor(
+    State(state): State<AppState>,
+    picoserve::extract::Json(light): picoserve::extract::Json<LightSensorConfig>,
+) -> impl IntoResponse {
+    let (status, json) = if light.ppfd_per_klux > 0.0 {
+        state.config.lock().await.update_calibration(|cal| cal.light = light).await;
+        (StatusCode::OK, serde_json::to_string(&light).unwrap_or_default())
+    } else {
+        let err = ErrorResponse { error: "ppfd_per_klux must be > 0" };
+        (StatusCode::BAD_REQUEST, serde_json::to_string(&err).unwrap_or_default())
+    };
+    Response::new(status, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_ntc(State(state): State<AppState>) -> impl IntoResponse {
+    let ntc = state.config.lock().await.calibration().ntc;
+    let json = serde_json::to_string(&ntc).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Changes the thermistor model and divider of the Peltier NTCs, see `NtcConfig`.
+async fn update_ntc(
+    State(state): State<AppState>,
+    picoserve::extract::Json(ntc): picoserve::extract::Json<NtcConfig>,
+) -> impl IntoResponse {
+    let (status, json) = if ntc.is_valid() {
+        state.config.lock().await.update_calibration(|cal| cal.ntc = ntc).await;
+        (StatusCode::OK, serde_json::to_string(&ntc).unwrap_or_default())
+    } else {
+        let err = ErrorResponse { error: "NTC parameters and series resistor must be positive" };
+        (StatusCode::BAD_REQUEST, serde_json::to_string(&err).unwrap_or_default())
+    };
+    Response::new(status, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Read statistics of every sensor channel, see `SensorHealth`.
+async fn get_sensor_health(State(state): State<AppState>) -> impl IntoResponse {
+    let health = state.sensor_data.lock().await.health;
+    let json = serde_json::to_string(&health).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
         .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
 }
 
@@ -358,6 +941,242 @@ async fn get_history(State(state): State<AppState>) -> impl IntoResponse {
         .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
 }
 
+async fn get_script_status(State(state): State<AppState>) -> impl IntoResponse {
+    let resp = {
+        let st = state.script_status.lock().await;
+        ScriptStatusResponse {
+            script_active: st.script_active,
+            error: st.last_error.clone(),
+            target_temp: st.active_targets.temp.to_num(),
+            target_humidity: st.active_targets.humidity,
+            target_vpd: st.active_targets.vpd.map(|v| v.to_num()),
+            vent_on: st.active_targets.vent_on,
+            light_intensity: st.active_targets.light_intensity,
+            compile_time_us: st.stats.compile_time_us,
+            compile_heap_bytes: st.stats.compile_heap_bytes,
+            eval_time_us: st.stats.eval_time_us,
+            eval_heap_bytes: st.stats.eval_heap_bytes,
+            heap_in_use: st.stats.heap_in_use,
+            eval_steps: st.stats.eval_steps,
+        }
+    };
+
+    let json = serde_json::to_string(&resp).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn validate_script(
+    State(state): State<AppState>,
+    picoserve::extract::Json(req): picoserve::extract::Json<ScriptValidateRequest>,
+) -> impl IntoResponse {
+    let result = dry_run_script(&state, &req.script_source, req.sensors.as_ref(), req.days).await;
+    let status = if result.is_ok() { StatusCode::OK } else { StatusCode::BAD_REQUEST };
+    let json = serde_json::to_string(&ScriptValidateResponse::from_result(result)).unwrap_or_default();
+    Response::new(status, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_modes(State(state): State<AppState>) -> impl IntoResponse {
+    let modes = state.config.lock().await.calibration().pid_config.modes;
+    let json = serde_json::to_string(&modes).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Switches control loops between Off / Hysteresis / Pid / Manual, e.g.
+/// `{"temperature":"Off","light":{"Manual":128}}`. Returns the resulting modes.
+async fn update_modes(
+    State(state): State<AppState>,
+    picoserve::extract::Json(update): picoserve::extract::Json<LoopModesUpdate>,
+) -> impl IntoResponse {
+    let mut cfg = state.config.lock().await;
+    cfg.update_calibration(|cal| update.apply_to(&mut cal.pid_config.modes)).await;
+    let modes = cfg.calibration().pid_config.modes;
+    defmt::info!("Control modes changed: {:?}", defmt::Debug2Format(&modes));
+
+    let json = serde_json::to_string(&modes).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_overrides(State(state): State<AppState>) -> impl IntoResponse {
+    let active = state.overrides.lock().await.active();
+    let json = serde_json::to_string(&active).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Forces an actuator for a while, see `OverrideRequest`. Returns the active overrides.
+async fn update_overrides(
+    State(state): State<AppState>,
+    picoserve::extract::Json(req): picoserve::extract::Json<OverrideRequest>,
+) -> impl IntoResponse {
+    let mut overrides = state.overrides.lock().await;
+    let (status, json) = match overrides.handle(&req) {
+        Ok(()) => (StatusCode::OK, serde_json::to_string(&overrides.active()).unwrap_or_default()),
+        Err(msg) => (StatusCode::BAD_REQUEST, serde_json::to_string(&ErrorResponse { error: msg }).unwrap_or_default()),
+    };
+    Response::new(status, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn tuner_response(state: &AppState) -> String {
+    let c = state.config.lock().await.calibration().pid_config;
+    let changes = state.tuner_log.lock().await.iter().copied().collect();
+    let resp = TunerResponse {
+        enabled: c.tuner_enabled,
+        kp_min: c.tuner_kp_min.to_num(),
+        kp_max: c.tuner_kp_max.to_num(),
+        ki_max: c.tuner_ki_max.to_num(),
+        kd_max: c.tuner_kd_max.to_num(),
+        changes,
+    };
+    serde_json::to_string(&resp).unwrap_or_default()
+}
+
+/// Adaptive tuner settings and the gain changes it made since boot
+async fn get_tuner(State(state): State<AppState>) -> impl IntoResponse {
+    Response::new(StatusCode::OK, tuner_response(&state).await)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn update_tuner(
+    State(state): State<AppState>,
+    picoserve::extract::Json(update): picoserve::extract::Json<TunerUpdate>,
+) -> impl IntoResponse {
+    {
+        use fixed::types::I16F16;
+        let mut cfg = state.config.lock().await;
+        cfg.update_calibration(|cal| {
+            let c = &mut cal.pid_config;
+            if let Some(v) = update.enabled { c.tuner_enabled = v; }
+            if let Some(v) = update.kp_min { c.tuner_kp_min = I16F16::saturating_from_num(v.max(0.0)); }
+            if let Some(v) = update.kp_max { c.tuner_kp_max = I16F16::saturating_from_num(v.max(0.0)); }
+            if let Some(v) = update.ki_max { c.tuner_ki_max = I16F16::saturating_from_num(v.max(0.0)); }
+            if let Some(v) = update.kd_max { c.tuner_kd_max = I16F16::saturating_from_num(v.max(0.0)); }
+            // clamp() panics on an inverted range
+            c.tuner_kp_max = c.tuner_kp_max.max(c.tuner_kp_min);
+        }).await;
+    }
+    Response::new(StatusCode::OK, tuner_response(&state).await)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Tray pump usage against its daily budget.
+async fn get_pump(State(state): State<AppState>) -> impl IntoResponse {
+    let resp = {
+        let cfg = state.config.lock().await;
+        let counters = cfg.pump_counters();
+        let pid_config = &cfg.calibration().pid_config;
+        PumpStatusResponse {
+            on_secs_today: counters.on_ms_today / 1000,
+            ml_today: PumpGuard::volume_today_ml(&counters, pid_config),
+            budget_secs: pid_config.pump_daily_budget_secs,
+            budget_ml: pid_config.pump_daily_budget_ml,
+            locked_out: counters.locked_out,
+        }
+    };
+    let json = serde_json::to_string(&resp).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_faults(State(state): State<AppState>) -> impl IntoResponse {
+    let active = state.faults.lock().await.active();
+    let json = serde_json::to_string(&active).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Acknowledges latched faults, see `FaultAck`. Returns the faults still latched.
+async fn ack_faults(
+    State(state): State<AppState>,
+    picoserve::extract::Json(ack): picoserve::extract::Json<FaultAck>,
+) -> impl IntoResponse {
+    let mut faults = state.faults.lock().await;
+    faults.acknowledge(&ack);
+    let json = serde_json::to_string(&faults.active()).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+async fn get_autotune(State(state): State<AppState>) -> impl IntoResponse {
+    let status = state.autotune.lock().await.status;
+    let candidate = |rule| match status {
+        AutotuneStatus::Done { result } => {
+            let g = result.gains(rule);
+            Some([g.kp.to_num(), g.ki.to_num(), g.kd.to_num()])
+        }
+        _ => None,
+    };
+    let resp = AutotuneResponse {
+        status,
+        ziegler_nichols: candidate(TuningRule::ZieglerNichols),
+        tyreus_luyben: candidate(TuningRule::TyreusLuyben),
+    };
+    let json = serde_json::to_string(&resp).unwrap_or_default();
+    Response::new(StatusCode::OK, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+fn autotune_reply(result: Result<(), &'static str>) -> impl IntoResponse {
+    let (status, json) = match result {
+        Ok(()) => (StatusCode::OK, String::from("{}")),
+        Err(error) => (StatusCode::BAD_REQUEST, serde_json::to_string(&ErrorResponse { error }).unwrap_or_default()),
+    };
+    Response::new(status, json)
+        .with_headers([("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")])
+}
+
+/// Starts a relay experiment on the temperature Peltier, see `AutotuneSettings`.
+async fn start_autotune(
+    State(state): State<AppState>,
+    picoserve::extract::Json(settings): picoserve::extract::Json<AutotuneSettings>,
+) -> impl IntoResponse {
+    let mut at = state.autotune.lock().await;
+    let result = match at.status {
+        AutotuneStatus::Running { .. } => Err("autotune already running"),
+        AutotuneStatus::Done { .. } => Err("confirm or discard the last result first"),
+        AutotuneStatus::Idle | AutotuneStatus::Aborted { .. } => {
+            at.command = Some(AutotuneCommand::Start(settings));
+            Ok(())
+        }
+    };
+    autotune_reply(result)
+}
+
+async fn abort_autotune(State(state): State<AppState>) -> impl IntoResponse {
+    state.autotune.lock().await.command = Some(AutotuneCommand::Abort);
+    autotune_reply(Ok(()))
+}
+
+/// Writes the tuned gains into the calibration, the only way autotune changes it.
+async fn confirm_autotune(
+    State(state): State<AppState>,
+    picoserve::extract::Json(req): picoserve::extract::Json<AutotuneConfirm>,
+) -> impl IntoResponse {
+    let status = state.autotune.lock().await.status;
+    let AutotuneStatus::Done { result } = status else {
+        return autotune_reply(Err("no autotune result to confirm"));
+    };
+
+    let gains = result.gains(req.rule);
+    state.config.lock().await.update_calibration(|cal| match result.direction {
+        AutotuneDirection::Heat => cal.pid_config.peltier_temp_heat = gains,
+        AutotuneDirection::Cool => cal.pid_config.peltier_temp_cool = gains,
+    }).await;
+    defmt::info!("Autotune gains saved: kp {} ki {} kd {}", gains.kp.to_num::<f32>(), gains.ki.to_num::<f32>(), gains.kd.to_num::<f32>());
+
+    state.autotune.lock().await.command = Some(AutotuneCommand::Clear);
+    autotune_reply(Ok(()))
+}
+
+async fn discard_autotune(State(state): State<AppState>) -> impl IntoResponse {
+    state.autotune.lock().await.command = Some(AutotuneCommand::Clear);
+    autotune_reply(Ok(()))
+}
+
 async fn handle_options() -> impl IntoResponse {
     Response::new(StatusCode::OK, "")
         .with_headers([
@@ -373,6 +1192,12 @@ pub async fn http_server_task(
     shared_config: SharedConfig,
     shared_sensor_data: SharedSensorData,
     shared_history: SharedHistory,
+    shared_script_status: SharedScriptStatus,
+    shared_overrides: SharedOverrides,
+    shared_tuner_log: SharedTunerLog,
+    shared_autotune: SharedAutotune,
+    shared_faults: SharedFaults,
+    time_manager: SharedTimeManager,
 ) {
     let app = Router::new()
         .route("/", get(index))
@@ -381,11 +1206,37 @@ pub async fn http_server_task(
         .route("/api/tray", get(get_tray))
         .route("/api/config", get(get_config_json).post(update_config_json).options(handle_options))
         .route("/api/history", get(get_history))
+        .route("/api/climate", get(get_climate))
+        .route("/api/sensors/health", get(get_sensor_health))
+        .route("/api/sensors/roles", get(get_sensor_roles).post(update_sensor_roles).options(handle_options))
+        .route("/api/sensors/co2", get(get_co2_sensor).post(update_co2_sensor).options(handle_options))
+        .route("/api/sensors/light", get(get_light_sensor).post(update_light_sensor).options(handle_options))
+        .route("/api/sensors/ntc", get(get_ntc).post(update_ntc).options(handle_options))
+        .route("/api/filter", get(get_filter).post(update_filter).options(handle_options))
+        .route("/api/script/status", get(get_script_status))
+        .route("/api/modes", get(get_modes).post(update_modes).options(handle_options))
+        .route("/api/overrides", get(get_overrides).post(update_overrides).options(handle_options))
+        .route("/api/tuner", get(get_tuner).post(update_tuner).options(handle_options))
+        .route("/api/pump", get(get_pump))
+        .route("/api/faults", get(get_faults))
+        .route("/api/faults/ack", post(ack_faults).options(handle_options))
+        .route("/api/autotune", get(get_autotune))
+        .route("/api/autotune/start", post(start_autotune).options(handle_options))
+        .route("/api/autotune/abort", post(abort_autotune).options(handle_options))
+        .route("/api/autotune/confirm", post(confirm_autotune).options(handle_options))
+        .route("/api/autotune/discard", post(discard_autotune).options(handle_options))
+        .route("/api/script/validate", post(validate_script).options(handle_options))
         .route("/config", post(update_config))
         .with_state(AppState {
             config: shared_config,
             sensor_data: shared_sensor_data,
             history: shared_history,
+            script_status: shared_script_status,
+            overrides: shared_overrides,
+            tuner_log: shared_tuner_log,
+            autotune: shared_autotune,
+            faults: shared_faults,
+            time_manager,
         });
 
     let timeouts = Timeouts {
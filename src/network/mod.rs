@@ -29,6 +29,11 @@ pub async fn init_network(
 	time_manager: SharedTimeManager,
     shared_sensor_data: crate::sensor_manager::SharedSensorData,
     shared_history: crate::sensor_history::SharedHistory,
+    shared_script_status: crate::userscript::SharedScriptStatus,
+    shared_overrides: crate::control::SharedOverrides,
+    shared_tuner_log: crate::control::SharedTunerLog,
+    shared_autotune: crate::control::SharedAutotune,
+    shared_faults: crate::control::SharedFaults,
 
 	wifi_pio_common: &mut Common<'static, PIO0>,
 	wifi_sm: StateMachine<'static, PIO0, 1>,
@@ -71,10 +76,10 @@ pub async fn init_network(
 
 	let shared_stack = Rc::new(Mutex::new(stack));
 	
-	spawner.spawn(time_sync_task::time_sync_task(time_manager, shared_stack.clone(), config.clone()).unwrap());
+	spawner.spawn(time_sync_task::time_sync_task(time_manager.clone(), shared_stack.clone(), config.clone()).unwrap());
 	spawner.spawn(connection_monitor::connection_monitor_task(control.clone(), shared_stack.clone(), config.clone()).unwrap());
-    spawner.spawn(http_server::http_server_task(shared_stack.clone(), config.clone(), shared_sensor_data.clone(), shared_history.clone()).unwrap());
-    spawner.spawn(mqtt_task::mqtt_task(shared_stack.clone(), config.clone(), shared_sensor_data.clone()).unwrap());
+    spawner.spawn(http_server::http_server_task(shared_stack.clone(), config.clone(), shared_sensor_data.clone(), shared_history.clone(), shared_script_status.clone(), shared_overrides.clone(), shared_tuner_log, shared_autotune, shared_faults.clone(), time_manager.clone()).unwrap());
+    spawner.spawn(mqtt_task::mqtt_task(shared_stack.clone(), config.clone(), shared_sensor_data.clone(), shared_overrides, shared_faults, time_manager).unwrap());
 
 	(control, shared_stack)
 }
@@ -1,9 +1,12 @@
 use embassy_net::{IpEndpoint};
 use embassy_time::{Duration, Timer, Instant};
 use crate::config_manager::SharedConfig;
+use crate::control::{FaultAck, LoopModesUpdate, OverrideRequest, SharedFaults, SharedOverrides};
 use crate::sensor_manager::SharedSensorData;
+use crate::time_manager::SharedTimeManager;
 use crate::network::ShareNetworkStack;
 use serde::{Deserialize, Serialize};
+use chrono::Timelike;
 use embassy_net::tcp::TcpSocket;
 use myrtio_mqtt::MqttClient;
 use myrtio_mqtt::transport::TcpTransport;
@@ -24,6 +27,11 @@ struct MqttLog {
     hum_out: u8,
     soil: f32,
     ec: f32,
+    vpd: f32,
+    dew_point: f32,
+    co2: f32,
+    ppfd: f32,
+    dli: f32,
 }
 
 #[derive(Deserialize)]
@@ -33,6 +41,28 @@ struct ConfigUpdate {
     light_intensity: Option<u8>,
     light_start_hour: Option<u8>,
     light_end_hour: Option<u8>,
+    script_source: Option<alloc::string::String>,
+}
+
+/// Dry-runs a script received over MQTT before it is saved, at the current local time like
+/// `POST /api/script/validate`.
+async fn validate_script(
+    config: &SharedConfig,
+    sensor_data: &SharedSensorData,
+    time_manager: &SharedTimeManager,
+    source: &str,
+) -> Result<(), crate::userscript::ScriptError> {
+    let sensors = sensor_data.lock().await.clone();
+    let now_utc = time_manager.get_time();
+    let (now_local, defaults, days, state) = {
+        let cfg = config.lock().await;
+        let now_local = now_utc.and_then(|utc| cfg.settings().local_time(utc));
+        let hour = now_local.map(|dt| dt.hour() as u8).unwrap_or(12);
+        let pc = cfg.plant_config();
+        let days = pc.days_since_start(now_utc.map(|dt| dt.timestamp() as u64));
+        (now_local, pc.scheduled_targets(hour), days, cfg.script_state().clone())
+    };
+    crate::userscript::dry_run(source, &sensors, &defaults, now_local, days, state).map(|_| ())
 }
 
 #[embassy_executor::task]
@@ -40,6 +70,9 @@ pub async fn mqtt_task(
     stack: ShareNetworkStack,
     config: SharedConfig,
     sensor_data: SharedSensorData,
+    overrides: SharedOverrides,
+    faults: SharedFaults,
+    time_manager: SharedTimeManager,
 ) {
     let mut rx_buffer = [0u8; 1024];
     let mut tx_buffer = [0u8; 1024];
@@ -85,11 +118,13 @@ pub async fn mqtt_task(
         }
         defmt::info!("MQTT: Connected!");
 
-        if let Err(e) = client.subscribe("plant/config", QoS::AtLeastOnce).await {
-             defmt::warn!("MQTT: Subscribe Error: {:?}", defmt::Debug2Format(&e));
-             // continue? or retry?
-        } else {
-             defmt::info!("MQTT: Subscribed.");
+        for topic in ["plant/config", "plant/modes", "plant/override", "plant/faults/ack"] {
+            if let Err(e) = client.subscribe(topic, QoS::AtLeastOnce).await {
+                 defmt::warn!("MQTT: Subscribe Error: {:?}", defmt::Debug2Format(&e));
+                 // continue? or retry?
+            } else {
+                 defmt::info!("MQTT: Subscribed to {}.", topic);
+            }
         }
         
 
@@ -104,6 +139,12 @@ pub async fn mqtt_task(
                     if pkt.topic == "plant/config" {
                          if let Ok(json_str) = core::str::from_utf8(&pkt.payload) {
                              if let Ok(update) = serde_json::from_str::<ConfigUpdate>(json_str) {
+                                 if let Some(source) = &update.script_source {
+                                     if let Err(e) = validate_script(&config, &sensor_data, &time_manager, source).await {
+                                         defmt::warn!("MQTT: Rejected config update, script error: {}", e.message().as_str());
+                                         continue;
+                                     }
+                                 }
                                  defmt::info!("MQTT: Applying Config Update");
                                  let mut cfg = config.lock().await;
                                  cfg.update_plant_config(|c| {
@@ -111,6 +152,10 @@ pub async fn mqtt_task(
                                      if let Some(v) = update.light_intensity { c.light_intensity = v; }
                                      if let Some(v) = update.light_start_hour { c.light_start_hour = v; }
                                      if let Some(v) = update.light_end_hour { c.light_end_hour = v; }
+                                     if let Some(v) = update.script_source {
+                                         c.script_source.clear();
+                                         c.script_source.extend_from_slice(v.as_bytes()).ok();
+                                     }
                                      if let Some(v) = update.plant_name { 
                                          if let Ok(s) = heapless::String::try_from(v.as_str()) {
                                               c.plant_name = s;
@@ -119,6 +164,50 @@ pub async fn mqtt_task(
                                  }).await;
                              }
                          }
+                    } else if pkt.topic == "plant/modes" {
+                         // Same body as POST /api/modes
+                         match serde_json::from_slice::<LoopModesUpdate>(&pkt.payload) {
+                             Ok(update) => {
+                                 let mut cfg = config.lock().await;
+                                 cfg.update_calibration(|cal| update.apply_to(&mut cal.pid_config.modes)).await;
+                                 defmt::info!("MQTT: Control modes changed: {:?}", defmt::Debug2Format(&cfg.calibration().pid_config.modes));
+                             }
+                             Err(_) => defmt::warn!("MQTT: Invalid control modes update"),
+                         }
+                    } else if pkt.topic == "plant/override" {
+                         // Same body as POST /api/overrides, the active overrides are echoed on plant/overrides
+                         let active = match serde_json::from_slice::<OverrideRequest>(&pkt.payload) {
+                             Ok(req) => {
+                                 let mut ov = overrides.lock().await;
+                                 if let Err(msg) = ov.handle(&req) {
+                                     defmt::warn!("MQTT: Override rejected: {}", msg);
+                                 }
+                                 ov.active()
+                             }
+                             Err(_) => {
+                                 defmt::warn!("MQTT: Invalid override request");
+                                 continue;
+                             }
+                         };
+                         if let Ok(json) = serde_json::to_string(&active) {
+                             client.publish("plant/overrides", json.as_bytes(), QoS::AtMostOnce).await.ok();
+                         }
+                    } else if pkt.topic == "plant/faults/ack" {
+                         // Same body as POST /api/faults/ack, the faults still latched go out on plant/faults
+                         let active = match serde_json::from_slice::<FaultAck>(&pkt.payload) {
+                             Ok(ack) => {
+                                 let mut f = faults.lock().await;
+                                 f.acknowledge(&ack);
+                                 f.active()
+                             }
+                             Err(_) => {
+                                 defmt::warn!("MQTT: Invalid fault acknowledgement");
+                                 continue;
+                             }
+                         };
+                         if let Ok(json) = serde_json::to_string(&active) {
+                             client.publish("plant/faults", json.as_bytes(), QoS::AtMostOnce).await.ok();
+                         }
                     }
                 },
 
@@ -145,6 +234,11 @@ pub async fn mqtt_task(
                          hum_out: data.external.map(|r| r.hum).unwrap_or(0),
                          soil: data.soil_moisture.map(|v| v.to_num()).unwrap_or(0.0),
                          ec: data.ec_level.map(|v| v.to_num()).unwrap_or(0.0),
+                         vpd: data.vpd.map(|v| v.to_num()).unwrap_or(0.0),
+                         dew_point: data.dew_point.map(|v| v.to_num()).unwrap_or(0.0),
+                         co2: data.co2_level.map(|v| v.to_num()).unwrap_or(0.0),
+                         ppfd: data.ppfd.map(|v| v.to_num()).unwrap_or(0.0),
+                         dli: data.dli.to_num(),
                      }
                  };
                  
@@ -152,6 +246,17 @@ pub async fn mqtt_task(
                  if let Ok(json) = serde_json::to_string(&log_entry) {
                      client.publish("plant/logs", json.as_bytes(), QoS::AtMostOnce).await.ok();
                  }
+
+                 let health = sensor_data.lock().await.health;
+                 if let Ok(json) = serde_json::to_string(&health) {
+                     client.publish("plant/sensors/health", json.as_bytes(), QoS::AtMostOnce).await.ok();
+                 }
+
+                 // Latched faults, an empty list once they are acknowledged
+                 let active = faults.lock().await.active();
+                 if let Ok(json) = serde_json::to_string(&active) {
+                     client.publish("plant/faults", json.as_bytes(), QoS::AtMostOnce).await.ok();
+                 }
                  last_publish = Instant::now();
              }
              
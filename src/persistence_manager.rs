@@ -4,7 +4,8 @@ use embassy_rp::peripherals::FLASH;
 use sequential_storage::map::{fetch_item, store_item};
 use sequential_storage::cache::NoCache;
 
-use crate::config_types::{CalibrationData, DeviceSettings, PlantConfiguration};
+use crate::config_types::{CalibrationData, DeviceSettings, PlantConfiguration, PumpCounters, ScriptState};
+use crate::config_types::{CALIBRATION_MAX_BYTES, PLANT_CONFIG_MAX_BYTES, SCRIPT_STATE_MAX_BYTES};
 
 const FLASH_SIZE: usize = 2 * 1024 * 1024;
 
@@ -18,6 +19,12 @@ const FLASH_RANGE_END: u32 = 0x200000;
 const KEY_CALIBRATION: u8 = 1;
 const KEY_SETTINGS: u8 = 2;
 const KEY_PLANT_CONFIG: u8 = 3;
+const KEY_SCRIPT_STATE: u8 = 4;
+const KEY_PUMP_COUNTERS: u8 = 5;
+/// `store_item` and `fetch_item` need room for the key in front of the value
+const KEY_LEN: usize = core::mem::size_of::<u8>();
+const SETTINGS_MAX_BYTES: usize = 512;
+const PUMP_COUNTERS_MAX_BYTES: usize = 32;
 
 pub struct PersistenceManager<'d> {
     flash: Flash<'d, FLASH, Async, FLASH_SIZE>,
@@ -33,22 +40,21 @@ impl<'d> PersistenceManager<'d> {
     }
 
     pub async fn save_calibration(&mut self, data: &CalibrationData) -> Result<(), ()> {
-        let mut buf = [0u8; 1024]; // Buffer for serialization
-        let bytes = postcard::to_slice(data, &mut buf).map_err(|_| ())?;
-        
-        let slice: &[u8] = &*bytes;
+        let mut buf = [0u8; CALIBRATION_MAX_BYTES];
+        // Versioned, see `CALIBRATION_VERSION`
+        let slice = data.encode(&mut buf).ok_or(())?;
         store_item::<u8, &[u8], _>(
             &mut self.flash,
             self.flash_range.clone(),
             &mut NoCache::new(),
-            &mut [0u8; 128], // Scratch buffer
+            &mut [0u8; CALIBRATION_MAX_BYTES + KEY_LEN],
             &KEY_CALIBRATION,
             &slice,
         ).await.map_err(|_| ())
     }
 
     pub async fn load_calibration(&mut self) -> Option<CalibrationData> {
-        let mut buf = [0u8; 1024];
+        let mut buf = [0u8; CALIBRATION_MAX_BYTES + KEY_LEN];
         
         let item = fetch_item(
             &mut self.flash,
@@ -58,11 +64,15 @@ impl<'d> PersistenceManager<'d> {
             &KEY_CALIBRATION,
         ).await.ok()??; // Result -> Option -> Option (if None found)
 
-        postcard::from_bytes(item).ok()
+        let data = CalibrationData::decode(item);
+        if data.is_none() {
+            defmt::warn!("Stored calibration ({} bytes) has an unknown layout, using defaults", item.len());
+        }
+        data
     }
 
     pub async fn save_settings(&mut self, data: &DeviceSettings) -> Result<(), ()> {
-        let mut buf = [0u8; 512];
+        let mut buf = [0u8; SETTINGS_MAX_BYTES];
         let bytes = postcard::to_slice(data, &mut buf).map_err(|_| ())?;
         
         let slice: &[u8] = &*bytes;
@@ -70,14 +80,14 @@ impl<'d> PersistenceManager<'d> {
             &mut self.flash,
             self.flash_range.clone(),
             &mut NoCache::new(),
-            &mut [0u8; 128],
+            &mut [0u8; SETTINGS_MAX_BYTES + KEY_LEN],
             &KEY_SETTINGS,
             &slice,
         ).await.map_err(|_| ())
     }
 
     pub async fn load_settings(&mut self) -> Option<DeviceSettings> {
-        let mut buf = [0u8; 512];
+        let mut buf = [0u8; SETTINGS_MAX_BYTES + KEY_LEN];
         
         let item = fetch_item(
             &mut self.flash,
@@ -91,12 +101,8 @@ impl<'d> PersistenceManager<'d> {
     }
 
     pub async fn save_plant_config(&mut self, data: &PlantConfiguration) -> Result<(), ()> {
-        // Plant config can be large (8kB script + metadata)
-        // We need a larger buffer.
-        // WARNING: 9KB on stack might be too much. Consider using heap or splitting.
-        // Since we have 'alloc', let's use a heap-allocated buffer.
-        
-        let mut buf = [0u8; 4096]; 
+        // The script makes this the largest item, both buffers go on the heap
+        let mut buf = alloc::vec![0u8; PLANT_CONFIG_MAX_BYTES];
         let bytes = postcard::to_slice(data, &mut buf).map_err(|_| ())?;
         
         let slice: &[u8] = &*bytes;
@@ -104,14 +110,14 @@ impl<'d> PersistenceManager<'d> {
             &mut self.flash,
             self.flash_range.clone(),
             &mut NoCache::new(),
-            &mut [0u8; 128],
+            &mut alloc::vec![0u8; PLANT_CONFIG_MAX_BYTES + KEY_LEN],
             &KEY_PLANT_CONFIG,
             &slice,
         ).await.map_err(|_| ())
     }
 
     pub async fn load_plant_config(&mut self) -> Option<PlantConfiguration> {
-        let mut buf = [0u8; 4096];
+        let mut buf = alloc::vec![0u8; PLANT_CONFIG_MAX_BYTES + KEY_LEN];
         
         let item = fetch_item(
             &mut self.flash,
@@ -121,6 +127,69 @@ impl<'d> PersistenceManager<'d> {
             &KEY_PLANT_CONFIG,
         ).await.ok()??;
 
+        let mut config: PlantConfiguration = postcard::from_bytes(item).ok()?;
+        let ms_cm = config.nominal_ec;
+        if config.migrate_nominal_ec() {
+            defmt::info!("Stored nominal EC {} mS/cm converted to {} ppm", ms_cm, config.nominal_ec);
+        }
+        Some(config)
+    }
+
+    pub async fn save_script_state(&mut self, data: &ScriptState) -> Result<(), ()> {
+        let mut buf = [0u8; SCRIPT_STATE_MAX_BYTES];
+        let bytes = postcard::to_slice(data, &mut buf).map_err(|_| ())?;
+        
+        let slice: &[u8] = &*bytes;
+        store_item::<u8, &[u8], _>(
+            &mut self.flash,
+            self.flash_range.clone(),
+            &mut NoCache::new(),
+            &mut [0u8; SCRIPT_STATE_MAX_BYTES + KEY_LEN],
+            &KEY_SCRIPT_STATE,
+            &slice,
+        ).await.map_err(|_| ())
+    }
+
+    pub async fn load_script_state(&mut self) -> Option<ScriptState> {
+        let mut buf = [0u8; SCRIPT_STATE_MAX_BYTES + KEY_LEN];
+        
+        let item = fetch_item(
+            &mut self.flash,
+            self.flash_range.clone(),
+            &mut NoCache::new(),
+            &mut buf,
+            &KEY_SCRIPT_STATE,
+        ).await.ok()??;
+
+        postcard::from_bytes(item).ok()
+    }
+
+    pub async fn save_pump_counters(&mut self, data: &PumpCounters) -> Result<(), ()> {
+        let mut buf = [0u8; PUMP_COUNTERS_MAX_BYTES];
+        let bytes = postcard::to_slice(data, &mut buf).map_err(|_| ())?;
+
+        let slice: &[u8] = &*bytes;
+        store_item::<u8, &[u8], _>(
+            &mut self.flash,
+            self.flash_range.clone(),
+            &mut NoCache::new(),
+            &mut [0u8; PUMP_COUNTERS_MAX_BYTES + KEY_LEN],
+            &KEY_PUMP_COUNTERS,
+            &slice,
+        ).await.map_err(|_| ())
+    }
+
+    pub async fn load_pump_counters(&mut self) -> Option<PumpCounters> {
+        let mut buf = [0u8; PUMP_COUNTERS_MAX_BYTES + KEY_LEN];
+
+        let item = fetch_item(
+            &mut self.flash,
+            self.flash_range.clone(),
+            &mut NoCache::new(),
+            &mut buf,
+            &KEY_PUMP_COUNTERS,
+        ).await.ok()??;
+
         postcard::from_bytes(item).ok()
     }
 }
\ No newline at end of file
@@ -14,6 +14,9 @@ pub struct HistoryEntry {
     pub hum: u8,
     pub soil: f32,
     pub ec: f32,
+    pub vpd: f32, // kPa
+    pub dew_point: f32,
+    pub co2: f32, // ppm
 }
 
 pub type SharedHistory = Rc<Mutex<CriticalSectionRawMutex, Deque<HistoryEntry, 10>>>;
@@ -28,13 +31,16 @@ pub async fn history_task(
         Timer::after(Duration::from_secs(60)).await;
         
         // Capture Sensor Data
-        let (temp, hum, soil, ec) = {
+        let (temp, hum, soil, ec, vpd, dew_point, co2) = {
             let s = shared_sensor.lock().await;
             let t = s.internal.map(|r| r.temp.to_num::<f32>()).unwrap_or(0.0);
             let h = s.internal.map(|r| r.hum).unwrap_or(0);
             let soil_v = s.soil_moisture.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
             let ec_v = s.ec_level.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
-            (t, h, soil_v, ec_v)
+            let vpd_v = s.vpd.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
+            let dew_v = s.dew_point.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
+            let co2_v = s.co2_level.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
+            (t, h, soil_v, ec_v, vpd_v, dew_v, co2_v)
         };
         
         let entry = HistoryEntry {
@@ -42,7 +48,10 @@ pub async fn history_task(
             temp,
             hum,
             soil,
-            ec
+            ec,
+            vpd,
+            dew_point,
+            co2,
         };
         
         // Push to History
@@ -3,50 +3,79 @@ use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
 use embedded_hal_async::i2c::I2c;
 use shared_bus::asynch::i2c::I2cDevice;
 
+use crate::config_manager::SharedConfig;
+use crate::config_types::{AirSensorRoles, CalibrationData, Co2SensorConfig, LightSensorConfig, NtcConfig, NtcModel, SensorRole};
+use crate::time_manager::SharedTimeManager;
 use crate::control::Number;
 use self::sensor_filter::MultiChannelKalmanFilter;
+use self::bh1750::Bh1750;
+use chrono::Datelike;
 use temp_hum_sensor_async::sht20::Sht20;
 use temp_hum_sensor_async::aht20::Aht20;
 use temp_hum_sensor_async::TempHumSensor;
+use pcf8591_async::Pcf8591;
+use scd4x_async::Scd4x;
+use num_traits::Float;
 
 
 pub mod sensor_filter;
+mod sensor_data;
+mod bh1750;
+pub use sensor_data::*;
 
 
 
 const PCF8591_VREF: f32 = 3.3;
 
-// NTC Defaults (Common 10k Module)
+/// ADC codes this close to either rail mean an open or shorted thermistor
+const NTC_RAIL_MARGIN: u8 = 3;
+/// Anything outside this range is a wiring fault, not a temperature
+const NTC_MIN_C: f32 = -40.0;
+const NTC_MAX_C: f32 = 150.0;
 
+const KELVIN: f32 = 273.15;
 
-/// Combined Temperature and Humidity Reading
-#[derive(Clone, Copy, Debug, Default)]
-pub struct TempHumReading {
-    pub temp: Number,
-    pub hum: u8,
-}
+/// The SCD4x measures every 5 s. Nothing new for three intervals and it is restarted.
+const CO2_MAX_AGE_MS: u64 = 15_000;
+
+impl NtcConfig {
+    /// °C from an 8-bit PCF8591 code, or `None` for an open or shorted thermistor.
+    pub fn temperature(&self, adc: u8) -> Option<f32> {
+        if adc <= NTC_RAIL_MARGIN || adc >= u8::MAX - NTC_RAIL_MARGIN {
+            return None;
+        }
+        let ratio = adc as f32 / 255.0;
+        let r = if self.ntc_to_ground {
+            self.series_resistor * ratio / (1.0 - ratio)
+        } else {
+            self.series_resistor * (1.0 - ratio) / ratio
+        };
 
-/// Collected sensor data from all hardware
-#[derive(Clone, Debug, Default)]
-pub struct SensorData {
-    pub internal: Option<TempHumReading>,
-    pub external: Option<TempHumReading>,
-    pub ntc_temps: Option<[Number; 4]>,
-    pub soil_moisture: Option<Number>,
-    pub ec_level: Option<Number>,
-    pub co2_level: Option<Number>,
+        let inv_t = match self.model {
+            NtcModel::Beta { r0, t0, beta } => 1.0 / (t0 + KELVIN) + (r / r0).ln() / beta,
+            NtcModel::SteinhartHart { a, b, c } => {
+                let ln_r = r.ln();
+                a + b * ln_r + c * ln_r * ln_r * ln_r
+            }
+        };
+        let t = 1.0 / inv_t - KELVIN;
+        (NTC_MIN_C..=NTC_MAX_C).contains(&t).then_some(t)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct CalibrationConfig {
     // EC (TDS)
     pub ec_k_value: f32,
+    // Peltier NTCs, from `CalibrationData`
+    pub ntc: NtcConfig,
 }
 
 impl Default for CalibrationConfig {
     fn default() -> Self {
         Self {
             ec_k_value: 1.0,
+            ntc: NtcConfig::default(),
         }
     }
 }
@@ -64,10 +93,30 @@ pub struct SensorManager<'a, I2C> {
     // Calibration
     calibration: CalibrationConfig,
 
+    health: SensorHealthReport,
+    air_roles: AirSensorRoles,
+
     // SHT20
     sht20: Sht20<I2cDevice<'a, NoopRawMutex, I2C>>,
     // AHT20
     aht20: Aht20<I2cDevice<'a, NoopRawMutex, I2C>>,
+    // PCF8591 (Peltier NTCs)
+    pcf8591: Pcf8591<I2cDevice<'a, NoopRawMutex, I2C>>,
+    // SCD4x (CO2)
+    scd4x: Scd4x<I2cDevice<'a, NoopRawMutex, I2C>>,
+    co2_config: Co2SensorConfig,
+    co2_applied: Option<Co2SensorConfig>, // What the sensor runs with, None = needs a (re)start
+    co2_last: Option<(f32, u64)>,          // Last measurement, ppm, and its uptime
+    co2_since_ms: u64,                     // Last measurement or start, for the restart
+    co2_stop_ms: Option<u64>,              // Stop sent at this uptime, settings go in after it
+    // BH1750 (light)
+    bh1750: Bh1750<I2cDevice<'a, NoopRawMutex, I2C>>,
+    light_config: LightSensorConfig,
+    light_started: bool,
+    // Daily light integral, mol/m²
+    dli: f32,
+    dli_day: Option<i32>,
+    dli_last_ms: u64,
     
     // ADC
     adc: Adc<'a, Async>,
@@ -84,83 +133,116 @@ where
         adc: Adc<'a, Async>,
         pin_soil: Channel<'a>,
         pin_ec: Channel<'a>,
+        calibration: &CalibrationData,
     ) -> Self {
-        // Initialize filter with default noise parameters
-        let q = 0.01;
-        let r_temp = 40.0;
-        let r_hum = 5.0;
-        let r_adc = 0.2; // Faster response (was 5.0)
-
-        let initial_values = [
-            25.0, 50.0, 25.0, 50.0,
-            25.0, 25.0, 25.0, 25.0,
-            4095.0, 0.0, // Soil starts at Max (Safety - No Tray), EC at 0
-        ];
-
-        let measurement_noises = [
-            r_temp, r_hum, r_temp, r_hum,
-            r_temp, r_temp, r_temp, r_temp,
-            r_adc, r_adc,
-        ];
-
         Self {
-            filter: MultiChannelKalmanFilter::new(initial_values, q, measurement_noises),
-            calibration: CalibrationConfig::default(),
+            filter: MultiChannelKalmanFilter::new(calibration.filter),
+            calibration: CalibrationConfig { ntc: calibration.ntc, ..CalibrationConfig::default() },
+            health: SensorHealthReport::default(),
+            air_roles: calibration.air_sensors,
             sht20: Sht20::new(I2cDevice::new(bus)),
             aht20: Aht20::new(I2cDevice::new(bus)),
+            pcf8591: Pcf8591::new(I2cDevice::new(bus)),
+            scd4x: Scd4x::new(I2cDevice::new(bus)),
+            co2_config: calibration.co2,
+            co2_applied: None,
+            co2_last: None,
+            co2_since_ms: 0,
+            co2_stop_ms: None,
+            bh1750: Bh1750::new(I2cDevice::new(bus)),
+            light_config: calibration.light,
+            light_started: false,
+            dli: 0.0,
+            dli_day: None,
+            dli_last_ms: embassy_time::Instant::now().as_millis(),
             adc,
             pin_soil,
             pin_ec,
         }
     }
 
-    pub async fn step(&mut self) -> SensorData {
+    /// Filter noise settings, air sensor roles, CO2 / light sensor settings and the NTC model
+    /// from `CalibrationData`, changeable at runtime.
+    pub fn set_calibration(&mut self, calibration: &CalibrationData) {
+        self.filter.set_config(&calibration.filter);
+        self.calibration.ntc = calibration.ntc;
+        self.air_roles = calibration.air_sensors;
+        self.co2_config = calibration.co2;
+        self.light_config = calibration.light;
+    }
+
+    /// `today` is the local date (days since 0001-01-01) for the daily light integral,
+    /// None while the clock is not set.
+    pub async fn step(&mut self, today: Option<i32>) -> SensorData {
         let mut data = SensorData::default();
         
         let sht = self.read_sht20_raw().await;
         let aht = self.read_aht20_raw().await;
-        // Mocked PCF8591
-        let ntcs = Some([30.0, 30.0, 5.0, 70.0]); 
+        let ntcs = self.read_ntcs().await;
         let soil = self.read_adc_soil().await;
         
         let ec_temp = sht.map(|r| r.temp).unwrap_or(25.0);
         let ec = self.read_adc_ec(ec_temp).await; 
 
-        let ntc_temps_or_default = ntcs.unwrap_or([0.0; 4]);
-
         let measurements = [
-            sht.map(|r| r.temp).unwrap_or(0.0),
-            sht.map(|r| r.hum).unwrap_or(0.0),
-            aht.map(|r| r.temp).unwrap_or(0.0),
-            aht.map(|r| r.hum).unwrap_or(0.0),
-            ntc_temps_or_default[0],
-            ntc_temps_or_default[1],
-            ntc_temps_or_default[2],
-            ntc_temps_or_default[3],
-            soil.unwrap_or(0.0),
-            ec.unwrap_or(0.0),
+            sht.map(|r| r.temp),
+            sht.map(|r| r.hum),
+            aht.map(|r| r.temp),
+            aht.map(|r| r.hum),
+            ntcs[0],
+            ntcs[1],
+            ntcs[2],
+            ntcs[3],
+            soil,
+            ec,
         ];
 
         let filtered = self.filter.update(measurements);
 
-        data.internal = sht.map(|_| TempHumReading {
-            temp: Number::from_num(filtered[0]),
-            hum: filtered[1] as u8,
-        });
-
-        data.external = aht.map(|_| TempHumReading {
-            temp: Number::from_num(filtered[2]),
-            hum: filtered[3] as u8,
-        });
-        
-        data.ntc_temps = if ntcs.is_some() {
-            Some([4, 5, 6, 7].map(|i| Number::from_num(filtered[i])))
-        } else {
-            None
+        let now_ms = embassy_time::Instant::now().as_millis();
+        let co2_ok = self.poll_co2(now_ms).await;
+        let lux = self.read_lux().await;
+        let health = &mut self.health;
+        health.sht20.record(sht.is_some(), now_ms);
+        health.aht20.record(aht.is_some(), now_ms);
+        for (h, ntc) in health.ntc.iter_mut().zip(ntcs) {
+            h.record(ntc.is_some(), now_ms);
+        }
+        health.soil.record(soil.is_some(), now_ms);
+        health.ec.record(ec.is_some(), now_ms);
+        health.co2.record(co2_ok, now_ms);
+        health.light.record(lux.is_some(), now_ms);
+
+        // A single failed read keeps the last estimate, only a stale channel reads None
+        let fresh = |h: &SensorHealth, ch: usize| filtered[ch].filter(|_| !h.stale);
+
+        // Air sensors by role, each as (temp, hum) estimates with their variances
+        let var = self.filter.variances();
+        let air_estimate = |h: &SensorHealth, ch: usize| {
+            fresh(h, ch).zip(fresh(h, ch + 1)).map(|(temp, hum)| [(temp, var[ch]), (hum, var[ch + 1])])
         };
-
-        data.soil_moisture = soil.map(|_| Number::from_num(filtered[8]));
-        data.ec_level = ec.map(|_| Number::from_num(filtered[9]));
+        let air = [
+            (self.air_roles.sht20, air_estimate(&health.sht20, 0)),
+            (self.air_roles.aht20, air_estimate(&health.aht20, 2)),
+        ];
+        let fuse = self.air_roles.fuse;
+        data.internal = combine_air_sensors(&air, SensorRole::Internal, fuse);
+        data.external = combine_air_sensors(&air, SensorRole::External, fuse);
+        
+        data.ntc_temps = [0, 1, 2, 3].map(|ch| fresh(&health.ntc[ch], 4 + ch).map(Number::from_num));
+
+        data.soil_moisture = fresh(&health.soil, 8).map(Number::from_num);
+        data.ec_level = fresh(&health.ec, 9).map(Number::from_num);
+        // Its own averaging is enough, not through the filter
+        data.co2_level = self.co2_last
+            .filter(|&(_, t)| !health.co2.stale && now_ms.saturating_sub(t) <= CO2_MAX_AGE_MS)
+            .map(|(ppm, _)| Number::from_num(ppm));
+
+        let ppfd = lux.map(|lux| lux / 1000.0 * self.light_config.ppfd_per_klux);
+        data.ppfd = ppfd.map(Number::saturating_from_num);
+        data.dli = Number::saturating_from_num(self.integrate_light(ppfd, today, now_ms));
+        data.health = *health;
+        data.update_derived();
         
         data
     }
@@ -179,6 +261,116 @@ where
         }
     }
 
+    /// Polls the SCD4x, (re)starting it first if needed. A new measurement goes to `co2_last`.
+    /// Returns whether the sensor answered.
+    async fn poll_co2(&mut self, now_ms: u64) -> bool {
+        let delay = &mut embassy_time::Delay;
+        // Ok(true) while measuring, Ok(false) while a restart waits for the stop
+        let result = match self.co2_applied {
+            Some(applied) if applied == self.co2_config => Ok(true),
+            // Pressure can change while measuring, self-calibration only while stopped
+            Some(applied) if applied.asc == self.co2_config.asc => {
+                self.scd4x.set_ambient_pressure(self.co2_config.ambient_pressure_hpa, delay).await.map(|()| true)
+            }
+            _ => self.start_co2(now_ms).await,
+        };
+        let result = match result {
+            Ok(true) => {
+                self.co2_applied = Some(self.co2_config);
+                match self.scd4x.data_ready(delay).await {
+                    Ok(true) => self.scd4x.read_co2(delay).await.map(|ppm| {
+                        self.co2_last = Some((ppm as f32, now_ms));
+                        self.co2_since_ms = now_ms;
+                        true
+                    }),
+                    Ok(false) => Ok(true),
+                    Err(e) => Err(e),
+                }
+            }
+            other => other,
+        };
+
+        match result {
+            Ok(true) if now_ms.saturating_sub(self.co2_since_ms) > CO2_MAX_AGE_MS => {
+                defmt::warn!("CO2 sensor: no measurement for {} ms, restarting", now_ms - self.co2_since_ms);
+                self.co2_applied = None;
+                false
+            }
+            Ok(_) => true,
+            Err(e) => {
+                // It may have lost power, start it over. Only logged once, it may not be fitted.
+                self.co2_stop_ms = None;
+                if self.co2_applied.take().is_some() {
+                    defmt::warn!("CO2 sensor: {}, restarting", e);
+                }
+                false
+            }
+        }
+    }
+
+    /// Stops the periodic measurement, then on a later poll, once the sensor has stopped,
+    /// applies `co2_config` and starts it again. The sensor task does not wait for the stop.
+    /// Returns whether it is measuring again.
+    async fn start_co2(&mut self, now_ms: u64) -> Result<bool, scd4x_async::Error> {
+        let Some(stop_ms) = self.co2_stop_ms else {
+            self.co2_applied = None;
+            self.scd4x.stop_periodic_measurement().await?;
+            self.co2_stop_ms = Some(now_ms);
+            return Ok(false);
+        };
+        if now_ms.saturating_sub(stop_ms) < scd4x_async::STOP_DELAY_MS as u64 {
+            return Ok(false);
+        }
+        self.co2_stop_ms = None;
+
+        let delay = &mut embassy_time::Delay;
+        let config = self.co2_config;
+        self.scd4x.set_automatic_self_calibration(config.asc, delay).await?;
+        self.scd4x.set_ambient_pressure(config.ambient_pressure_hpa, delay).await?;
+        self.scd4x.start_periodic_measurement().await?;
+        self.co2_since_ms = now_ms;
+        defmt::info!("CO2 sensor started, ASC {}, {} hPa", config.asc, config.ambient_pressure_hpa);
+        Ok(true)
+    }
+
+    /// Lux from the BH1750, starting it first if needed.
+    async fn read_lux(&mut self) -> Option<f32> {
+        if !self.light_started {
+            self.light_started = self.bh1750.start().await.is_ok();
+            if !self.light_started {
+                return None;
+            }
+        }
+        let lux = self.bh1750.read_lux().await.ok();
+        // Restart after a failed read, it may have lost power
+        self.light_started = lux.is_some();
+        lux
+    }
+
+    /// Adds this step's light to the daily light integral and returns it. Starts over at
+    /// local midnight; until the clock is set the integral runs from boot.
+    fn integrate_light(&mut self, ppfd: Option<f32>, today: Option<i32>, now_ms: u64) -> f32 {
+        if let Some(day) = today {
+            if self.dli_day.is_some_and(|d| d != day) {
+                defmt::info!("DLI yesterday: {} mol/m2", self.dli);
+                self.dli = 0.0;
+            }
+            self.dli_day = Some(day);
+        }
+        let elapsed_s = now_ms.saturating_sub(self.dli_last_ms) as f32 / 1000.0;
+        self.dli_last_ms = now_ms;
+        // µmol/m²/s over the step, a missing reading adds nothing
+        self.dli += ppfd.unwrap_or(0.0) * elapsed_s / 1_000_000.0;
+        self.dli
+    }
+
+    async fn read_ntcs(&mut self) -> [Option<f32>; 4] {
+        match self.pcf8591.read_all().await {
+            Ok(raw) => raw.map(|adc| self.calibration.ntc.temperature(adc)),
+            Err(_) => [None; 4],
+        }
+    }
+
     async fn read_adc_soil(&mut self) -> Option<f32> { 
         let raw = self.adc.read(&mut self.pin_soil).await;
         match raw {
@@ -202,6 +394,42 @@ where
 
 
 
+/// (value, variance) of temperature and humidity from one air sensor
+type AirEstimate = [(f32, f32); 2];
+
+/// Air reading for `role` from the sensors mounted there. With `fuse`, every working one
+/// counts, weighted by 1 / variance; otherwise the first working one in the list is used.
+fn combine_air_sensors(
+    sensors: &[(SensorRole, Option<AirEstimate>)],
+    role: SensorRole,
+    fuse: bool,
+) -> Option<TempHumReading> {
+    let mut working = sensors.iter().filter(|(r, _)| *r == role).filter_map(|(_, est)| *est);
+    let [temp, hum] = if fuse {
+        // Sums of value / variance and 1 / variance, per quantity
+        let mut sums = [(0.0f32, 0.0f32); 2];
+        let mut any = false;
+        for est in working {
+            any = true;
+            for (sum, (value, variance)) in sums.iter_mut().zip(est) {
+                let w = 1.0 / variance.max(f32::EPSILON);
+                sum.0 += w * value;
+                sum.1 += w;
+            }
+        }
+        if !any {
+            return None;
+        }
+        sums.map(|(weighted, weights)| weighted / weights)
+    } else {
+        working.next()?.map(|(value, _)| value)
+    };
+    Some(TempHumReading {
+        temp: Number::from_num(temp),
+        hum: hum.clamp(0.0, 100.0) as u8,
+    })
+}
+
 fn convert_ec(adc: u8, temp_c: f32, k_value: f32) -> Option<f32> {
     let v_raw = adc as f32 * (PCF8591_VREF / 255.0);
     let v_comp = v_raw / (1.0 + 0.02 * (temp_c - 25.0));
@@ -222,14 +450,25 @@ pub async fn sensor_task(
     pin_soil: Peri<'static, embassy_rp::peripherals::PIN_27>,
     pin_ec: Peri<'static, embassy_rp::peripherals::PIN_26>,
     shared_data: SharedSensorData,
+    config: SharedConfig,
+    time_manager: SharedTimeManager,
 ) {
     let bus = Mutex::new(i2c);
     let ch_soil = Channel::new_pin(pin_soil, Pull::None);
     let ch_ec = Channel::new_pin(pin_ec, Pull::None);
-    let mut manager = SensorManager::new(&bus, adc, ch_soil, ch_ec);
+    let mut manager = SensorManager::new(&bus, adc, ch_soil, ch_ec, config.lock().await.calibration());
+    let mut today = None;
     
     loop {
-        let data = manager.step().await;
+        // Pick up changed sensor settings and the local date, skipped while the config is busy
+        if let Ok(cfg) = config.try_lock() {
+            manager.set_calibration(cfg.calibration());
+            today = time_manager.get_time()
+                .and_then(|utc| cfg.settings().local_time(utc))
+                .map(|dt| dt.num_days_from_ce());
+        }
+
+        let data = manager.step(today).await;
         {
             let mut shared = shared_data.lock().await;
             *shared = data;
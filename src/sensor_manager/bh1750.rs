@@ -0,0 +1,43 @@
+//! ROHM BH1750 ambient light sensor on I2C, ADDR pin low.
+//!
+//! Runs in continuous high-resolution mode: 1 lx steps, a new value every 120 ms, up to
+//! about 54 klx.
+
+use embedded_hal_async::i2c::I2c;
+
+const ADDRESS: u8 = 0x23;
+
+const CMD_POWER_ON: u8 = 0x01;
+const CMD_CONTINUOUS_HIGH_RES: u8 = 0x10;
+
+/// Counts per lux at the default measurement time
+const COUNTS_PER_LUX: f32 = 1.2;
+
+/// No acknowledge or a bus error
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Error;
+
+pub struct Bh1750<I2C> {
+    i2c: I2C,
+}
+
+impl<I2C: I2c> Bh1750<I2C> {
+    pub fn new(i2c: I2C) -> Self {
+        Self { i2c }
+    }
+
+    /// Powers the sensor up and starts the continuous measurement. The first value is
+    /// ready 180 ms later, reads before that return 0 lx.
+    pub async fn start(&mut self) -> Result<(), Error> {
+        self.i2c.write(ADDRESS, &[CMD_POWER_ON]).await.map_err(|_| Error)?;
+        self.i2c.write(ADDRESS, &[CMD_CONTINUOUS_HIGH_RES]).await.map_err(|_| Error)?;
+        Ok(())
+    }
+
+    /// Latest measurement in lux.
+    pub async fn read_lux(&mut self) -> Result<f32, Error> {
+        let mut buf = [0u8; 2];
+        self.i2c.read(ADDRESS, &mut buf).await.map_err(|_| Error)?;
+        Ok(u16::from_be_bytes(buf) as f32 / COUNTS_PER_LUX)
+    }
+}
@@ -0,0 +1,224 @@
+use serde::Serialize;
+
+use crate::control::Number;
+
+/// A channel with no good read for this long is stale and reported as `None`
+pub const SENSOR_STALE_MS: u64 = 3000;
+/// Weight of one read in `SensorHealth::error_rate`, about the last 50 reads
+const ERROR_RATE_ALPHA: f32 = 0.02;
+
+/// Combined Temperature and Humidity Reading
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TempHumReading {
+    pub temp: Number,
+    pub hum: u8,
+}
+
+/// Collected sensor data from all hardware
+#[derive(Clone, Debug, Default)]
+pub struct SensorData {
+    pub internal: Option<TempHumReading>,
+    pub external: Option<TempHumReading>,
+    /// Peltier NTCs, indexed by `NTC_PELTIER_*`. `None` for an open or shorted thermistor.
+    pub ntc_temps: [Option<Number>; 4],
+    pub soil_moisture: Option<Number>,
+    pub ec_level: Option<Number>,
+    pub co2_level: Option<Number>,
+    /// Photosynthetic photon flux density at the light sensor, µmol/m²/s
+    pub ppfd: Option<Number>,
+    /// Daily light integral so far today (local time), mol/m²
+    pub dli: Number,
+    /// Air vapour pressure deficit in kPa, from `internal`
+    pub vpd: Option<Number>,
+    /// Dew point of the chamber air in °C, from `internal`
+    pub dew_point: Option<Number>,
+    pub health: SensorHealthReport,
+}
+
+/// Read statistics of one sensor channel.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SensorHealth {
+    /// Uptime of the last good read, ms
+    pub last_good_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    /// Share of recent reads that failed, 0..1
+    pub error_rate: f32,
+    /// No good read for `SENSOR_STALE_MS`, or never
+    pub stale: bool,
+}
+
+impl SensorHealth {
+    /// Records one read attempt at uptime `now_ms`.
+    pub fn record(&mut self, ok: bool, now_ms: u64) {
+        let failed = if ok { 0.0 } else { 1.0 };
+        self.error_rate += ERROR_RATE_ALPHA * (failed - self.error_rate);
+        if ok {
+            self.last_good_ms = Some(now_ms);
+            self.consecutive_failures = 0;
+        } else {
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        }
+        self.stale = self.last_good_ms.is_none_or(|t| now_ms.saturating_sub(t) > SENSOR_STALE_MS);
+    }
+}
+
+/// Health of every sensor. The air sensors are listed by part, their role is configurable.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SensorHealthReport {
+    pub sht20: SensorHealth,
+    pub aht20: SensorHealth,
+    /// Indexed like `SensorData::ntc_temps`
+    pub ntc: [SensorHealth; 4],
+    pub soil: SensorHealth,
+    pub ec: SensorHealth,
+    pub co2: SensorHealth,
+    pub light: SensorHealth,
+}
+
+impl SensorHealthReport {
+    /// Every channel with its name on the LCD, the optional CO2 and light sensors last
+    pub fn channels(&self) -> [(&'static str, &SensorHealth); 10] {
+        [
+            ("sht20", &self.sht20),
+            ("aht20", &self.aht20),
+            ("soil", &self.soil),
+            ("ec", &self.ec),
+            ("ntc1", &self.ntc[0]),
+            ("ntc2", &self.ntc[1]),
+            ("ntc3", &self.ntc[2]),
+            ("ntc4", &self.ntc[3]),
+            ("co2", &self.co2),
+            ("light", &self.light),
+        ]
+    }
+
+    /// Names of the stale channels, for the LCD
+    pub fn stale_channels(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.channels()
+            .into_iter()
+            // Optional sensors never seen since boot are taken as not fitted
+            .filter(|(name, h)| !matches!(*name, "co2" | "light") || h.last_good_ms.is_some())
+            .filter(|(_, h)| h.stale)
+            .map(|(name, _)| name)
+    }
+}
+
+impl SensorData {
+    /// Recomputes the values derived from `internal`.
+    pub fn update_derived(&mut self) {
+        let air = self.internal.map(|r| (r.temp.to_num::<f32>(), r.hum as f32));
+        self.vpd = air.map(|(t, rh)| Number::from_num(vapor_pressure_deficit(t, rh, t)));
+        self.dew_point = air.and_then(|(t, rh)| dew_point(t, rh)).map(Number::from_num);
+    }
+}
+
+// Magnus-Tetens constants, good to ~0.1 % over 0..50 °C
+const MAGNUS_A: f32 = 17.27;
+const MAGNUS_B: f32 = 237.3;
+
+/// Saturation vapour pressure over water, kPa.
+pub fn saturation_vapor_pressure(temp: f32) -> f32 {
+    0.61078 * num_traits::Float::exp(MAGNUS_A * temp / (temp + MAGNUS_B))
+}
+
+/// Actual vapour pressure of air at `temp` and `rh` %, kPa.
+pub fn vapor_pressure(temp: f32, rh: f32) -> f32 {
+    saturation_vapor_pressure(temp) * rh.clamp(0.0, 100.0) / 100.0
+}
+
+/// VPD between a leaf at `leaf_temp` and air at `air_temp` / `rh` %, kPa.
+/// With `leaf_temp == air_temp` this is the air VPD.
+pub fn vapor_pressure_deficit(air_temp: f32, rh: f32, leaf_temp: f32) -> f32 {
+    saturation_vapor_pressure(leaf_temp) - vapor_pressure(air_temp, rh)
+}
+
+/// Relative humidity at which the leaf VPD equals `vpd`, for showing a VPD target as RH.
+pub fn rh_for_vpd(air_temp: f32, leaf_temp: f32, vpd: f32) -> f32 {
+    let vapor = saturation_vapor_pressure(leaf_temp) - vpd;
+    (100.0 * vapor / saturation_vapor_pressure(air_temp)).clamp(0.0, 100.0)
+}
+
+/// Dew point in °C, `None` for bone dry air.
+pub fn dew_point(temp: f32, rh: f32) -> Option<f32> {
+    if rh < 1.0 {
+        return None;
+    }
+    let gamma = num_traits::Float::ln(rh.min(100.0) / 100.0) + MAGNUS_A * temp / (temp + MAGNUS_B);
+    Some(MAGNUS_B * gamma / (MAGNUS_A - gamma))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32, tol: f32) {
+        assert!((actual - expected).abs() < tol, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn saturation_pressure_matches_tables() {
+        assert_near(saturation_vapor_pressure(0.0), 0.611, 0.002);
+        assert_near(saturation_vapor_pressure(20.0), 2.339, 0.005);
+        assert_near(saturation_vapor_pressure(25.0), 3.169, 0.005);
+        assert_near(saturation_vapor_pressure(30.0), 4.246, 0.01);
+    }
+
+    #[test]
+    fn vpd_of_air_and_leaf() {
+        assert_near(vapor_pressure_deficit(25.0, 60.0, 25.0), 3.169 * 0.4, 0.005);
+        assert_near(vapor_pressure_deficit(25.0, 100.0, 25.0), 0.0, 0.0001);
+        // A leaf cooler than the air transpires less
+        assert!(vapor_pressure_deficit(25.0, 60.0, 23.0) < vapor_pressure_deficit(25.0, 60.0, 25.0));
+        // Out-of-range humidity is clamped
+        assert_eq!(vapor_pressure(25.0, 120.0), saturation_vapor_pressure(25.0));
+    }
+
+    #[test]
+    fn rh_for_vpd_inverts_the_vpd() {
+        for (air, leaf, vpd) in [(25.0, 25.0, 1.2), (22.0, 20.0, 0.8), (30.0, 28.0, 1.5)] {
+            let rh = rh_for_vpd(air, leaf, vpd);
+            assert_near(vapor_pressure_deficit(air, rh, leaf), vpd, 0.001);
+        }
+        assert_eq!(rh_for_vpd(20.0, 20.0, 10.0), 0.0);
+    }
+
+    #[test]
+    fn dew_point_matches_tables() {
+        assert_near(dew_point(20.0, 50.0).unwrap(), 9.3, 0.1);
+        assert_near(dew_point(25.0, 60.0).unwrap(), 16.7, 0.1);
+        assert_near(dew_point(15.0, 100.0).unwrap(), 15.0, 0.01);
+        assert_eq!(dew_point(20.0, 0.0), None);
+    }
+
+    #[test]
+    fn derived_values_follow_the_internal_sensor() {
+        let mut data = SensorData {
+            internal: Some(TempHumReading { temp: Number::from_num(25), hum: 60 }),
+            ..Default::default()
+        };
+        data.update_derived();
+        assert_near(data.vpd.unwrap().to_num(), 1.267, 0.005);
+        assert_near(data.dew_point.unwrap().to_num(), 16.7, 0.1);
+
+        data.internal = None;
+        data.update_derived();
+        assert_eq!(data.vpd, None);
+        assert_eq!(data.dew_point, None);
+    }
+    #[test]
+    fn stale_channels_skip_optional_sensors_never_seen() {
+        let mut health = SensorHealthReport::default();
+        let now_ms = SENSOR_STALE_MS + 1;
+        health.sht20.record(true, now_ms);
+        health.ntc.iter_mut().for_each(|h| h.record(true, now_ms));
+        health.soil.record(true, now_ms);
+        health.ec.record(false, now_ms);
+        health.aht20.record(false, now_ms);
+        health.co2.record(true, 0);
+        health.co2.record(false, now_ms);
+        health.light.record(false, now_ms);
+
+        assert_eq!(health.stale_channels().collect::<Vec<_>>(), ["aht20", "ec", "co2"]);
+        assert_eq!(health.channels()[8].1.consecutive_failures, 1);
+    }
+}
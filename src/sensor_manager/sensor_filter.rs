@@ -1,8 +1,6 @@
-use nalgebra::{U10, Matrix, Vector};
+use crate::config_types::{FilterConfig, FILTER_CHANNELS};
 
-use adskalman::{KalmanFilterNoControl, TransitionModelLinearNoControl, ObservationModelLinear, StateAndCovariance};
-
-// --- Multi-Channel Filter (10 Dimensions) ---
+// --- Multi-Channel Filter (10 independent scalar channels) ---
 // 0: SHT Temp
 // 1: SHT Hum
 // 2: AHT Temp
@@ -11,83 +9,187 @@ use adskalman::{KalmanFilterNoControl, TransitionModelLinearNoControl, Observati
 // 8: Soil
 // 9: EC
 
-type Matrix10 = Matrix<f32, U10, U10, nalgebra::ArrayStorage<f32, U10, U10>>;
-type Vector10 = Vector<f32, U10, nalgebra::ArrayStorage<f32, U10, nalgebra::U1>>;
+/// Rejected samples in a row after which the channel is taken to have really stepped
+/// (e.g. a tray put back) and the filter restarts from the measurement
+const MAX_REJECTED_RUN: u32 = 5;
+/// Weight of one innovation in the adaptive R estimate
+const ADAPTIVE_R_ALPHA: f32 = 0.02;
+/// Adaptive R never drops below this share of the configured R
+const ADAPTIVE_R_FLOOR: f32 = 0.1;
 
-struct MultiChannelModel {
-    transition_matrix: Matrix10,
-    process_noise: Matrix10,
-    observation_matrix: Matrix10,
-    measurement_noise: Matrix10,
+/// Random-walk Kalman filter of one channel.
+#[derive(Clone, Copy, Debug, Default)]
+struct ScalarKalman {
+    x: Option<f32>, // None until the first sample
+    p: f32,
+    r: f32,         // Measurement noise in use, adapted from the innovations if enabled
+    rejected_run: u32,
 }
 
-impl TransitionModelLinearNoControl<f32, U10> for MultiChannelModel {
-    fn transition_model(&self) -> &Matrix10 {
-        &self.transition_matrix
-    }
-    fn transition_model_transpose(&self) -> &Matrix10 {
-        &self.transition_matrix
-    }
-    fn transition_noise_covariance(&self) -> &Matrix10 {
-        &self.process_noise
+impl ScalarKalman {
+    fn update(&mut self, z: Option<f32>, q: f32, r_config: f32, config: &FilterConfig) -> Option<f32> {
+        if !config.adaptive_r || self.r <= 0.0 {
+            self.r = r_config;
+        }
+        let Some(x) = self.x else {
+            // Start from the first sample
+            if let Some(z) = z {
+                self.x = Some(z);
+                self.p = self.r;
+            }
+            return self.x;
+        };
+
+        // Predict
+        self.p += q;
+        let Some(z) = z else { return Some(x) };
+
+        // Innovation gate: drop samples more than k sigma off the prediction
+        let innovation = z - x;
+        let s = self.p + self.r;
+        let k = config.outlier_sigma;
+        if k > 0.0 && innovation * innovation > k * k * s {
+            self.rejected_run += 1;
+            if self.rejected_run < MAX_REJECTED_RUN {
+                return Some(x);
+            }
+            self.rejected_run = 0;
+            self.x = Some(z);
+            self.p = self.r;
+            return self.x;
+        }
+        self.rejected_run = 0;
+
+        if config.adaptive_r {
+            // E[innovation²] = P + R, so innovation² - P estimates R
+            let r_sample = innovation * innovation - self.p;
+            self.r = ((1.0 - ADAPTIVE_R_ALPHA) * self.r + ADAPTIVE_R_ALPHA * r_sample).max(r_config * ADAPTIVE_R_FLOOR);
+        }
+
+        let gain = self.p / (self.p + self.r);
+        self.p *= 1.0 - gain;
+        self.x = Some(x + gain * innovation);
+        self.x
     }
 }
 
-impl ObservationModelLinear<f32, U10, U10> for MultiChannelModel {
-    fn observation_matrix(&self) -> &Matrix10 {
-        &self.observation_matrix
+pub struct MultiChannelKalmanFilter {
+    channels: [ScalarKalman; FILTER_CHANNELS],
+    config: FilterConfig,
+}
+
+impl MultiChannelKalmanFilter {
+    pub fn new(config: FilterConfig) -> Self {
+        Self {
+            channels: [ScalarKalman::default(); FILTER_CHANNELS],
+            config,
+        }
     }
-    fn observation_matrix_transpose(&self) -> &Matrix10 {
-        &self.observation_matrix
+
+    /// Takes new noise settings, the estimates are kept.
+    pub fn set_config(&mut self, config: &FilterConfig) {
+        if self.config != *config {
+            self.config = *config;
+            for ch in &mut self.channels {
+                ch.r = 0.0; // Restart adaptation from the configured R
+            }
+        }
     }
-    fn observation_noise_covariance(&self) -> &Matrix10 {
-        &self.measurement_noise
+
+    /// `None` channels (failed reads) only get the predict step: their estimate is kept
+    /// and its uncertainty grows, instead of the filter being pulled towards a made-up value.
+    /// A channel reads `None` until its first sample.
+    pub fn update(&mut self, measurements: [Option<f32>; FILTER_CHANNELS]) -> [Option<f32>; FILTER_CHANNELS] {
+        let mut result = [None; FILTER_CHANNELS];
+        for (i, ch) in self.channels.iter_mut().enumerate() {
+            let noise = self.config.channels[i];
+            result[i] = ch.update(measurements[i], noise.q, noise.r, &self.config);
+        }
+        result
     }
-    fn evaluate(&self, state: &Vector10) -> Vector10 {
-        self.observation_matrix * state
+
+    /// Variance of each estimate, for weighting redundant sensors
+    pub fn variances(&self) -> [f32; FILTER_CHANNELS] {
+        self.channels.map(|ch| ch.p)
     }
 }
 
-pub struct MultiChannelKalmanFilter {
-    state: Vector10,
-    covariance: Matrix10,
-    model: MultiChannelModel,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl MultiChannelKalmanFilter {
-    pub fn new(initial_values: [f32; 10], process_noise: f32, measurement_noises: [f32; 10]) -> Self {
-        let mut r_diag = Vector10::zeros();
-        for i in 0..10 {
-            r_diag[i] = measurement_noises[i];
+    const SOIL: usize = 8;
+
+    fn only(channel: usize, z: Option<f32>) -> [Option<f32>; FILTER_CHANNELS] {
+        let mut m = [None; FILTER_CHANNELS];
+        m[channel] = z;
+        m
+    }
+
+    fn feed(filter: &mut MultiChannelKalmanFilter, z: f32, n: usize) -> Option<f32> {
+        let mut x = None;
+        for _ in 0..n {
+            x = filter.update(only(SOIL, Some(z)))[SOIL];
         }
+        x
+    }
 
-        Self {
-            state: Vector10::from_column_slice(&initial_values),
-            covariance: Matrix10::identity(), // Initial uncertainty
-            model: MultiChannelModel {
-                transition_matrix: Matrix10::identity(),
-                process_noise: Matrix10::identity() * process_noise,
-                observation_matrix: Matrix10::identity(),
-                measurement_noise: Matrix10::from_diagonal(&r_diag),
-            },
+    #[test]
+    fn starts_at_the_first_sample_and_converges() {
+        let mut filter = MultiChannelKalmanFilter::new(FilterConfig::default());
+        assert_eq!(filter.update([None; FILTER_CHANNELS]), [None; FILTER_CHANNELS]);
+        assert_eq!(filter.update(only(SOIL, Some(10.0)))[SOIL], Some(10.0));
+
+        let x = feed(&mut filter, 11.0, 100).unwrap();
+        assert!((x - 11.0).abs() < 0.01, "{}", x);
+        assert!(filter.variances()[SOIL] < FilterConfig::default().channels[SOIL].r);
+        // Channels without samples stay empty
+        assert_eq!(filter.update(only(SOIL, Some(11.0)))[0], None);
+    }
+
+    #[test]
+    fn missing_samples_keep_the_estimate_and_grow_the_variance() {
+        let mut filter = MultiChannelKalmanFilter::new(FilterConfig::default());
+        let x = feed(&mut filter, 10.0, 20);
+        let p = filter.variances()[SOIL];
+
+        assert_eq!(filter.update(only(SOIL, None))[SOIL], x);
+        assert_eq!(filter.update(only(SOIL, None))[SOIL], x);
+        assert!(filter.variances()[SOIL] > p);
+    }
+
+    #[test]
+    fn outliers_are_dropped_until_they_persist() {
+        let mut filter = MultiChannelKalmanFilter::new(FilterConfig::default());
+        let x = feed(&mut filter, 10.0, 50);
+        for _ in 1..MAX_REJECTED_RUN {
+            assert_eq!(filter.update(only(SOIL, Some(100.0)))[SOIL], x);
         }
+        // A real step, e.g. the tray put back: restart from the measurement
+        assert_eq!(filter.update(only(SOIL, Some(100.0)))[SOIL], Some(100.0));
+
+        // A single spike is dropped and the run count starts over
+        let x = feed(&mut filter, 100.0, 50);
+        assert_eq!(filter.update(only(SOIL, Some(0.0)))[SOIL], x);
+        assert!(filter.update(only(SOIL, Some(100.0)))[SOIL].is_some_and(|v| (v - 100.0).abs() < 0.01));
     }
 
-    pub fn update(&mut self, measurements: [f32; 10]) -> [f32; 10] {
-        let filter = KalmanFilterNoControl::new(&self.model, &self.model);
-        
-        let observation = Vector10::from_column_slice(&measurements);
-        let estimate = StateAndCovariance::new(self.state, self.covariance);
-        
-        let new_estimate = filter.step(&estimate, &observation);
-        
-        self.state = *new_estimate.state();
-        self.covariance = *new_estimate.covariance();
-        
-        let mut result = [0.0; 10];
-        for i in 0..10 {
-            result[i] = self.state[i];
+    #[test]
+    fn adaptive_r_follows_the_measured_noise() {
+        let config = FilterConfig { adaptive_r: true, outlier_sigma: 0.0, ..Default::default() };
+        let r_config = config.channels[SOIL].r;
+        let mut filter = MultiChannelKalmanFilter::new(config);
+        for i in 0..500 {
+            let z = if i % 2 == 0 { 9.0 } else { 11.0 };
+            filter.update(only(SOIL, Some(z)));
         }
-        result
+        // Noise variance is 1, five times the configured R
+        assert!(filter.channels[SOIL].r > 2.0 * r_config, "{}", filter.channels[SOIL].r);
+
+        // New settings restart the adaptation from the configured R
+        let config = FilterConfig { outlier_sigma: 3.0, ..config };
+        filter.set_config(&config);
+        filter.update(only(SOIL, Some(10.0)));
+        assert!(filter.channels[SOIL].r < 2.0 * r_config);
     }
 }
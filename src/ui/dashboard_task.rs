@@ -1,7 +1,11 @@
 use embassy_time::{Timer, Duration};
 use crate::config_manager::SharedConfig;
-use crate::sensor_manager::SharedSensorData;
+use crate::sensor_manager::{rh_for_vpd, SharedSensorData};
 use crate::hardware_manager::SharedActuatorState;
+use crate::time_manager::SharedTimeManager;
+use crate::userscript::SharedScriptStatus;
+use crate::control::{AutotuneStatus, SharedAutotune, SharedFaults, SharedOverrides};
+use crate::ui::diagnostics::SharedDiagnostics;
 use slint::ComponentHandle;
 // Import the generated slint module. The parent module `ui` has `slint::include_modules!()`.
 // We need to import the globals from that.
@@ -12,12 +16,20 @@ use crate::ui::{EmbeddedUI, PlantValue, Plant, PlantNameValue, PlantName};
 pub async fn dashboard_task(
     ui: EmbeddedUI,
     config: SharedConfig,
+    time_manager: SharedTimeManager,
     sensor_data: SharedSensorData,
     actuator_state: SharedActuatorState,
+    script_status: SharedScriptStatus,
+    overrides: SharedOverrides,
+    autotune: SharedAutotune,
+    faults: SharedFaults,
+    diagnostics: SharedDiagnostics,
 ) {
+    let mut tick: u32 = 0;
     loop {
         // ... (lines 19-46 unchanged)
         Timer::after(Duration::from_millis(500)).await;
+        tick = tick.wrapping_add(1);
 
         let sensors = {
             let data = sensor_data.lock().await;
@@ -31,16 +43,74 @@ pub async fn dashboard_task(
 
         // defmt::info!("Dash Task: Actuators: {}", actuators);
 
-        let (target_temp, target_hum, plant_name, start_ts) = {
+        let now_ts = time_manager.get_time().map(|dt| dt.timestamp() as u64);
+        let (plant_name, day, leaf_temp_offset) = {
             let cfg = config.lock().await;
             let pc = cfg.plant_config();
-            (pc.target_temp, 60, pc.plant_name.clone(), pc.start_timestamp) 
+            (
+                pc.plant_name.clone(),
+                pc.days_since_start(now_ts) as i32,
+                cfg.calibration().pid_config.leaf_temp_offset.to_num::<f32>(),
+            )
         };
 
-        let day = if let Some(_start) = start_ts {
-             0 
-        } else {
-            0
+        // Targets actually in use (script or schedule fallback)
+        let (target_temp, mut target_hum, target_vpd, script_failed) = {
+            let st = script_status.lock().await;
+            (
+                st.active_targets.temp.to_num::<f32>(),
+                st.active_targets.humidity as i32,
+                st.active_targets.vpd.map(|v| v.to_num::<f32>()),
+                st.last_error.is_some(),
+            )
+        };
+
+        // A VPD target has no slot on the LCD, show the RH it works out to at the current air temperature
+        if let (Some(vpd), Some(internal)) = (target_vpd, sensors.internal) {
+            let air_temp: f32 = internal.temp.to_num();
+            target_hum = rh_for_vpd(air_temp, air_temp - leaf_temp_offset, vpd) as i32;
+        }
+
+        // Forced actuators, e.g. "OVR Pump 22 8s" or "OVR x3 42s" (soonest to expire)
+        let override_text = {
+            let active = overrides.lock().await.active();
+            match active.iter().min_by_key(|o| o.remaining_secs) {
+                Some(o) if active.len() == 1 => Some(alloc::format!("OVR {} {}s", o.actuator.label(), o.remaining_secs)),
+                Some(o) => Some(alloc::format!("OVR x{} {}s", active.len(), o.remaining_secs)),
+                None => None,
+            }
+        };
+
+        // Latched faults, e.g. "FAULT F04" or "FAULT F04 +2", acknowledged on the web UI / MQTT
+        let fault_text = {
+            let active = faults.lock().await.active();
+            match active.first() {
+                Some(f) if active.len() == 1 => Some(alloc::format!("FAULT {}", f.id)),
+                Some(f) => Some(alloc::format!("FAULT {} +{}", f.id, active.len() - 1)),
+                None => None,
+            }
+        };
+
+        // Per-channel read statistics for the diagnostics page, the health is kept against uptime
+        if diagnostics.is_open() {
+            diagnostics.update(&sensors.health, embassy_time::Instant::now().as_millis());
+        }
+
+        // Sensors without a good read for a while, e.g. "Stale: ntc2" or "Stale: ec +1",
+        // hold the encoder button for the details
+        let stale_text = {
+            let mut stale = sensors.health.stale_channels();
+            stale.next().map(|first| match stale.count() {
+                0 => alloc::format!("Stale: {}", first),
+                more => alloc::format!("Stale: {} +{}", first, more),
+            })
+        };
+
+        // Autotune progress, the result is confirmed on the web UI
+        let autotune_text = match autotune.lock().await.status {
+            AutotuneStatus::Running { cycle, cycles, .. } => Some(alloc::format!("Tune {}/{}", cycle, cycles)),
+            AutotuneStatus::Done { .. } => Some(alloc::string::String::from("Tune done, confirm")),
+            AutotuneStatus::Idle | AutotuneStatus::Aborted { .. } => None,
         };
 
         let current_temp = sensors.internal.map(|r| r.temp.to_num::<i32>()).unwrap_or(0);
@@ -71,7 +141,19 @@ pub async fn dashboard_task(
             OutHum: out_hum,
         };
 
-        let name_str = slint::SharedString::from(plant_name.as_str());
+        // No room for the message on the LCD, the details are on /api/script/status
+        let name_str = if let Some(text) = fault_text.or(override_text).or(stale_text).or(autotune_text) {
+            slint::SharedString::from(text.as_str())
+        } else if script_failed {
+            slint::SharedString::from("Script error!")
+        } else if let (Some(vpd), Some(dp), 1) = (sensors.vpd, sensors.dew_point, (tick / 8) % 3) {
+            // 4 s each in turn with the plant name
+            slint::SharedString::from(alloc::format!("VPD {:.2} DP {:.0}", vpd.to_num::<f32>(), dp.to_num::<f32>()).as_str())
+        } else if let (Some(co2), 2) = (sensors.co2_level, (tick / 8) % 3) {
+            slint::SharedString::from(alloc::format!("CO2 {} ppm", co2.to_num::<i32>()).as_str())
+        } else {
+            slint::SharedString::from(plant_name.as_str())
+        };
         let pn = PlantName {
             name: name_str,
             day: day,
@@ -0,0 +1,168 @@
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use rgb::Gray;
+use crate::sensor_manager::{SensorHealth, SensorHealthReport};
+use crate::ui::lcd_task::GrayPixel;
+
+/// Holding the encoder button this long on the main screen opens the page
+pub const DIAGNOSTICS_HOLD_MS: u64 = 3000;
+
+const SCREEN_WIDTH: usize = 128;
+const SCREEN_HEIGHT: usize = 64;
+/// 3x5 glyphs in 4x6 cells: 32 columns, 10 lines
+const CELL_WIDTH: usize = 4;
+const CELL_HEIGHT: usize = 6;
+const TOP: usize = 2;
+const ROWS_BELOW_HEADER: usize = SCREEN_HEIGHT / CELL_HEIGHT - 1;
+
+// Same convention as the QR code: 255 is a dark dot on the LCD
+const INK: GrayPixel = GrayPixel(Gray(255));
+const PAPER: GrayPixel = GrayPixel(Gray(0));
+
+pub type SharedDiagnostics = Rc<Diagnostics>;
+
+/// Sensor diagnostics page, drawn instead of the slint windows while open. The dashboard task
+/// fills in the lines, the encoder scrolls, a click closes it.
+#[derive(Default)]
+pub struct Diagnostics {
+	open: Cell<bool>,
+	scroll: Cell<usize>,
+	dirty: Cell<bool>,
+	lines: RefCell<Vec<String>>,
+}
+
+impl Diagnostics {
+	pub fn is_open(&self) -> bool {
+		self.open.get()
+	}
+
+	pub fn open(&self) {
+		self.open.set(true);
+		self.scroll.set(0);
+		self.dirty.set(true);
+	}
+
+	pub fn close(&self) {
+		self.open.set(false);
+	}
+
+	pub fn scroll(&self, down: bool) {
+		let max = self.lines.borrow().len().saturating_sub(ROWS_BELOW_HEADER);
+		let scroll = self.scroll.get();
+		let scroll = if down { (scroll + 1).min(max) } else { scroll.saturating_sub(1) };
+		if scroll != self.scroll.get() {
+			self.scroll.set(scroll);
+			self.dirty.set(true);
+		}
+	}
+
+	/// One line per channel, `now_ms` is the uptime the health was recorded against
+	pub fn update(&self, health: &SensorHealthReport, now_ms: u64) {
+		let lines: Vec<String> = health.channels().iter().map(|(name, h)| channel_line(name, h, now_ms)).collect();
+		if *self.lines.borrow() != lines {
+			self.lines.replace(lines);
+			self.dirty.set(true);
+		}
+	}
+
+	/// Draws the page into the LCD buffer, returns false when nothing changed since the last draw
+	pub fn draw(&self, buffer: &mut [GrayPixel], force_update: bool) -> bool {
+		if !self.dirty.replace(false) && !force_update {
+			return false;
+		}
+		buffer[..SCREEN_WIDTH * SCREEN_HEIGHT].fill(PAPER);
+		draw_text(buffer, 0, "sensor fail  err   good");
+		let lines = self.lines.borrow();
+		for (row, line) in lines.iter().skip(self.scroll.get()).take(ROWS_BELOW_HEADER).enumerate() {
+			draw_text(buffer, row + 1, line);
+		}
+		true
+	}
+}
+
+/// e.g. "ntc2     14  35%    12m stale"
+fn channel_line(name: &str, health: &SensorHealth, now_ms: u64) -> String {
+	let good = match health.last_good_ms {
+		None => String::from("never"),
+		Some(t) => {
+			let secs = now_ms.saturating_sub(t) / 1000;
+			match secs {
+				0..120 => format!("{}s", secs),
+				120..7200 => format!("{}m", secs / 60),
+				_ => format!("{}h", secs / 3600),
+			}
+		}
+	};
+	format!(
+		"{:<6}{:>5}{:>4}%{:>7}{}",
+		name,
+		health.consecutive_failures.min(99_999),
+		(health.error_rate * 100.0) as u32,
+		good,
+		if health.stale { " stale" } else { "" },
+	)
+}
+
+fn draw_text(buffer: &mut [GrayPixel], line: usize, text: &str) {
+	let y0 = TOP + line * CELL_HEIGHT;
+	for (col, c) in text.chars().take(SCREEN_WIDTH / CELL_WIDTH).enumerate() {
+		let x0 = col * CELL_WIDTH;
+		for (dy, bits) in glyph(c).iter().enumerate() {
+			for dx in 0..3 {
+				if bits & (0b100 >> dx) != 0 {
+					buffer[SCREEN_WIDTH * (y0 + dy) + x0 + dx] = INK;
+				}
+			}
+		}
+	}
+}
+
+/// Rows top to bottom, the left dot in bit 2. Capitals are drawn as lower case.
+fn glyph(c: char) -> [u8; 5] {
+	match c.to_ascii_lowercase() {
+		' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+		'0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+		'1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+		'2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+		'3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+		'4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+		'5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+		'6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+		'7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+		'8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+		'9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+		'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
+		'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
+		'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
+		'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
+		'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
+		'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
+		'g' => [0b011, 0b100, 0b101, 0b101, 0b011],
+		'h' => [0b101, 0b101, 0b111, 0b101, 0b101],
+		'i' => [0b111, 0b010, 0b010, 0b010, 0b111],
+		'j' => [0b001, 0b001, 0b001, 0b101, 0b010],
+		'k' => [0b101, 0b101, 0b110, 0b101, 0b101],
+		'l' => [0b100, 0b100, 0b100, 0b100, 0b111],
+		'm' => [0b101, 0b111, 0b111, 0b101, 0b101],
+		'n' => [0b110, 0b101, 0b101, 0b101, 0b101],
+		'o' => [0b010, 0b101, 0b101, 0b101, 0b010],
+		'p' => [0b110, 0b101, 0b110, 0b100, 0b100],
+		'q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+		'r' => [0b110, 0b101, 0b110, 0b101, 0b101],
+		's' => [0b011, 0b100, 0b010, 0b001, 0b110],
+		't' => [0b111, 0b010, 0b010, 0b010, 0b010],
+		'u' => [0b101, 0b101, 0b101, 0b101, 0b111],
+		'v' => [0b101, 0b101, 0b101, 0b101, 0b010],
+		'w' => [0b101, 0b101, 0b111, 0b111, 0b101],
+		'x' => [0b101, 0b101, 0b010, 0b101, 0b101],
+		'y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+		'z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+		'%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+		'.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+		'-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+		_ => [0b111, 0b001, 0b010, 0b000, 0b010], // '?'
+	}
+}
@@ -7,12 +7,14 @@ use embassy_time::{with_timeout, Duration};
 use slint::platform::software_renderer::MinimalSoftwareWindow;
 use slint::platform::{Key, WindowEvent};
 use slint::SharedString;
+use crate::ui::diagnostics::{SharedDiagnostics, DIAGNOSTICS_HOLD_MS};
 
 #[embassy_executor::task]
 pub async fn encoder_button_input_task(
 	window: Rc<MinimalSoftwareWindow>,
 	keyboard_window: Rc<MinimalSoftwareWindow>,
-	click: Peri<'static, PIN_8>
+	click: Peri<'static, PIN_8>,
+	diagnostics: SharedDiagnostics,
 ) {
 	//let mut encoder = RotaryEncoder::<_, _, _, Infallible>::new(Input::new(en1, Pull::Up), Input::new(en2, Pull::Up), DDDD).unwrap();
 	// let mut i1 = Input::new(en1, Pull::Up);
@@ -22,18 +24,32 @@ pub async fn encoder_button_input_task(
 
 	loop {
 		click.wait_for_low().await;
+		// Any click closes the diagnostics page, slint does not see it
+		if diagnostics.is_open() {
+			diagnostics.close();
+			click.wait_for_high().await;
+			continue;
+		}
 		let w = if keyboard_window.is_minimized() {
 			&window
 		} else {
 			&keyboard_window
 		};
 		w.dispatch_event(WindowEvent::KeyPressed { text: enter.clone() });
+		let mut held_ms = 500;
 		if with_timeout(Duration::from_millis(500), click.wait_for_high()).await.is_err() {
 			w.dispatch_event(WindowEvent::KeyPressRepeated { text: enter.clone() });
 		}
 		while with_timeout(Duration::from_millis(100), click.wait_for_high()).await.is_err() {
+			held_ms += 100;
+			// A long hold on the main screen opens the sensor diagnostics page
+			if held_ms >= DIAGNOSTICS_HOLD_MS && keyboard_window.is_minimized() {
+				diagnostics.open();
+				break;
+			}
 			w.dispatch_event(WindowEvent::KeyPressRepeated { text: enter.clone() });
 		}
 		w.dispatch_event(WindowEvent::KeyReleased { text: enter.clone() });
+		click.wait_for_high().await;
 	}
 }
\ No newline at end of file
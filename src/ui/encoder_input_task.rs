@@ -4,19 +4,25 @@ use embassy_rp::pio_programs::rotary_encoder::{Direction, PioEncoder};
 use slint::platform::software_renderer::MinimalSoftwareWindow;
 use slint::platform::{Key, WindowEvent};
 use slint::SharedString;
+use crate::ui::diagnostics::SharedDiagnostics;
 
 //사용자 입력 (로터리 인코더, 클릭) 을 처리하는 태스크
 #[embassy_executor::task]
 pub async fn encoder_input_task(
 	window: Rc<MinimalSoftwareWindow>,
 	keyboard_window: Rc<MinimalSoftwareWindow>,
-	mut enc: PioEncoder<'static, PIO0, 0>
+	mut enc: PioEncoder<'static, PIO0, 0>,
+	diagnostics: SharedDiagnostics,
 ) {
 	let tab = SharedString::from(Key::Tab);
 	let backtab = SharedString::from(Key::Backtab);
 
 	loop {
 		let en = enc.read().await;
+		if diagnostics.is_open() {
+			diagnostics.scroll(matches!(en, Direction::Clockwise));
+			continue;
+		}
 		let w = if keyboard_window.is_minimized() {
 			&window
 		} else {
@@ -14,6 +14,7 @@ use slint::platform::software_renderer::{MinimalSoftwareWindow, RepaintBufferTyp
 use slint::platform::{update_timers_and_animations, WindowAdapter};
 use st7920_async::{SpiPixel8, SpiScreenBuffer, St7920SpiGdRam};
 use crate::EmbassyDelay;
+use crate::ui::diagnostics::SharedDiagnostics;
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct GrayPixel(pub Gray<u8>);
@@ -56,6 +57,7 @@ pub async fn lcd_task2(
 	dma: Peri<'static, DMA_CH0>,
 	window: Rc<MinimalSoftwareWindow>,
 	keyboard_window: Rc<MinimalSoftwareWindow>,
+	diagnostics: SharedDiagnostics,
 ) {
 	let mut config = spi::Config::default();
 	config.frequency = 600_000;
@@ -80,18 +82,31 @@ pub async fn lcd_task2(
 	let mut render_buffer = [GrayPixel::default(); 128 * 64];
 
 	let mut last_minimized = true;
+	let mut last_diagnostics_open = false;
 
 	loop {
 		ticker.next().await;
 		update_timers_and_animations();
 		yield_now().await;
 		let current_minimized = keyboard_window.is_minimized();
-		let force_update = current_minimized != last_minimized;
+		let diagnostics_open = diagnostics.is_open();
+		let force_update = current_minimized != last_minimized || diagnostics_open != last_diagnostics_open;
 		last_minimized = current_minimized;
-		let mut update = render(&*window, &mut render_buffer, force_update);
-		if !keyboard_window.is_minimized() {
-			update |= render(&*keyboard_window, &mut render_buffer[(128 * window.size().height as usize)..], force_update);
+		if last_diagnostics_open && !diagnostics_open {
+			// The slint windows have to draw everything again over the diagnostics page
+			window.request_redraw();
+			keyboard_window.request_redraw();
 		}
+		last_diagnostics_open = diagnostics_open;
+		let update = if diagnostics_open {
+			diagnostics.draw(&mut render_buffer, force_update)
+		} else {
+			let mut update = render(&*window, &mut render_buffer, force_update);
+			if !keyboard_window.is_minimized() {
+				update |= render(&*keyboard_window, &mut render_buffer[(128 * window.size().height as usize)..], force_update);
+			}
+			update
+		};
 		if update || force_update {
 			yield_now().await;
 			// top half of lcd x = 0..8, y = 0..32
@@ -5,8 +5,10 @@ mod lcd_backend;
 mod lcd_task;
 mod initial_configuration;
 mod dashboard_task;
+mod diagnostics;
 
 use alloc::boxed::Box;
+use alloc::rc::Rc;
 use embassy_executor::Spawner;
 use embassy_rp::Peri;
 use embassy_rp::peripherals::{DMA_CH0, PIN_14, PIN_15, PIN_6, PIN_7, PIN_8, PIO0, SPI0};
@@ -23,8 +25,11 @@ use crate::network::ShareNetworkStack;
 use crate::network::wifi::SharedWifiControl;
 use crate::ui::initial_configuration::initial_configuration_ui_task;
 use crate::ui::dashboard_task::dashboard_task;
+use crate::ui::diagnostics::Diagnostics;
 use crate::sensor_manager::SharedSensorData;
 use crate::hardware_manager::SharedActuatorState;
+use crate::userscript::SharedScriptStatus;
+use crate::control::{SharedAutotune, SharedFaults, SharedOverrides};
 
 use slint::SharedString;
 slint::include_modules!();
@@ -37,6 +42,10 @@ pub fn init_ui(
     time_manager: SharedTimeManager,
     sensor_data: SharedSensorData,
     actuator_state: SharedActuatorState,
+    script_status: SharedScriptStatus,
+    overrides: SharedOverrides,
+    autotune: SharedAutotune,
+    faults: SharedFaults,
 	// # hardwares
 	// ## input hardwares
 	//pio_encoder: PioEncoder<'static, PIO0, 0>,
@@ -62,6 +71,9 @@ pub fn init_ui(
 
 	slint::platform::set_platform(lcd_backend).unwrap();
 
+	// sensor diagnostics page, drawn by the lcd task instead of the slint windows
+	let diagnostics = Rc::new(Diagnostics::default());
+
 	// pio encoder
 	let prg = PioEncoderProgram::new(pio);
 	let pio_encoder: PioEncoder<'static, PIO0, 0> = PioEncoder::new(pio, pio_sm, enc_a, enc_b, &prg);
@@ -73,18 +85,21 @@ pub fn init_ui(
 		mosi,
 		dma,
 		lcd_window.clone(),
-		lcd_keyboard_window.clone()
+		lcd_keyboard_window.clone(),
+		diagnostics.clone()
 	).unwrap());
 
 	spawner.spawn(encoder_input_task(
 		lcd_window.clone(),
 		lcd_keyboard_window.clone(),
-		pio_encoder
+		pio_encoder,
+		diagnostics.clone()
 	).unwrap());
 	spawner.spawn(encoder_button_input_task(
 		lcd_window.clone(),
 		lcd_keyboard_window.clone(),
-		enc_button
+		enc_button,
+		diagnostics.clone()
 	).unwrap());
 
 	let ui2 = KeyboardWindow::new().unwrap();
@@ -203,7 +218,7 @@ pub fn init_ui(
         Image::default()
     });
 
-	spawner.spawn(initial_configuration_ui_task(ui.clone_strong(), config.clone(), wifi_control, network_stack, time_manager).unwrap());
+	spawner.spawn(initial_configuration_ui_task(ui.clone_strong(), config.clone(), wifi_control, network_stack, time_manager.clone()).unwrap());
     // Pass strong reference to keep UI alive
-    spawner.spawn(dashboard_task(ui.clone_strong(), config, sensor_data, actuator_state).unwrap());
+    spawner.spawn(dashboard_task(ui.clone_strong(), config, time_manager, sensor_data, actuator_state, script_status, overrides, autotune, faults, diagnostics).unwrap());
 }
\ No newline at end of file
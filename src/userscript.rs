@@ -1,98 +1,580 @@
 use crate::control::TargetState;
+use crate::config_types::{ScriptState, SCRIPT_SOURCE_MAX, SCRIPT_STATE_MAX_ENTRIES, SCRIPT_STATE_MAX_KEY};
 use crate::sensor_manager::SensorData;
 use fixed::types::I16F16;
 use alloc::string::{String, ToString};
+#[cfg(feature = "firmware")]
+use alloc::rc::Rc;
+#[cfg(feature = "firmware")]
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+
+mod script_value;
+use script_value::ScriptValue;
+pub mod sandbox;
+#[cfg(feature = "host")]
+pub mod recipe;
+use sandbox::{LimitKind, Quarantine, ScriptLimits};
 
 type Number = I16F16;
 
+/// Compile error with its position in the user's source (1-based).
+/// Line 0 means the error is in the built-in helpers or the generated wrapper.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "firmware", derive(defmt::Format))]
+pub struct SourceError {
+    pub msg: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceError {
+    /// `line_offset` is the number of lines placed before the user source.
+    fn from_lisp(e: blisp::LispErr, line_offset: usize) -> Self {
+        // blisp counts lines from 0
+        let line = e.pos.line + 1;
+        Self {
+            msg: e.msg,
+            line: line.saturating_sub(line_offset),
+            column: e.pos.column + 1,
+        }
+    }
+}
+
+impl core::fmt::Display for SourceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} (line {}, column {})", self.msg, self.line, self.column)
+    }
+}
+
+/// Why a script run did not produce targets.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "firmware", derive(defmt::Format))]
+pub enum ScriptError {
+    /// The script could not be parsed.
+    Init(SourceError),
+    /// The script failed type checking.
+    Typing(SourceError),
+    /// The script raised an error while running.
+    Eval(String),
+    /// The script ran, but its result is not a usable target description.
+    Type(String),
+    /// The script was stopped by the sandbox.
+    LimitExceeded(LimitKind),
+}
+
+impl core::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ScriptError::Init(e) => write!(f, "parse error: {}", e),
+            ScriptError::Typing(e) => write!(f, "type error: {}", e),
+            ScriptError::Eval(e) => write!(f, "runtime error: {}", e),
+            ScriptError::Type(e) => write!(f, "bad result: {}", e),
+            ScriptError::LimitExceeded(kind) => write!(f, "aborted: {} exceeded", kind.as_str()),
+        }
+    }
+}
+
+impl ScriptError {
+    /// Short machine-readable name, for the API
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScriptError::Init(_) => "parse",
+            ScriptError::Typing(_) => "typing",
+            ScriptError::Eval(_) => "runtime",
+            ScriptError::Type(_) => "result",
+            ScriptError::LimitExceeded(_) => "limit",
+        }
+    }
+
+    /// Position in the user's source, for compile errors
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            ScriptError::Init(e) | ScriptError::Typing(e) => Some((e.line, e.column)),
+            _ => None,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ScriptError::Init(e) | ScriptError::Typing(e) => e.msg.clone(),
+            ScriptError::Eval(e) | ScriptError::Type(e) => e.clone(),
+            ScriptError::LimitExceeded(kind) => alloc::format!("{} exceeded", kind.as_str()),
+        }
+    }
+}
+
+/// What the control loop is currently running on, for the UI and API.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptStatus {
+    /// Targets handed to the controller in the last cycle
+    pub active_targets: TargetState,
+    /// True if `active_targets` came from the script, false if from the static schedule
+    pub script_active: bool,
+    /// Error from the last script run, if it failed
+    pub last_error: Option<String>,
+    pub stats: ScriptStats,
+}
+
+#[cfg(feature = "firmware")]
+pub type SharedScriptStatus = Rc<Mutex<CriticalSectionRawMutex, ScriptStatus>>;
+
+/// Cost of the last compile and the last evaluation, as seen by the global allocator.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "firmware", derive(defmt::Format))]
+pub struct ScriptStats {
+    pub compile_time_us: u32,
+    /// Bytes allocated while parsing and type checking
+    pub compile_heap_bytes: u32,
+    pub eval_time_us: u32,
+    /// Bytes allocated during one evaluation (freed again afterwards)
+    pub eval_heap_bytes: u32,
+    /// Heap in use right after the evaluation, including the compiled script
+    pub heap_in_use: u32,
+    /// Interpreter steps used by the last evaluation
+    pub eval_steps: u32,
+}
+
+/// Name of the function the control loop calls every cycle
+const ENTRY_POINT: &str = "targets";
+
+/// Helper functions available to every script
+const STDLIB: &str = include_str!("userscript/stdlib.lisp");
+
+#[derive(Default)]
 pub struct UserScript {
     source: String,
+    // Parsed and type checked once per `update_script`
+    ctx: Option<blisp::semantics::Context>,
+    compile_error: Option<ScriptError>,
+    stats: ScriptStats,
+    limits: ScriptLimits,
+    hash: u32,
+    // Script that faulted before the last reboot; refused until it is changed
+    quarantine: Option<Quarantine>,
+    state: ScriptState,
+    state_changed: bool,
+    // Checked before saving, not driving the controller, see `dry_run`
+    dry_run: bool,
 }
 
 impl UserScript {
     pub fn new() -> Self {
+        #[cfg(feature = "firmware")]
         defmt::info!("Blisp Script Engine Init");
-        Self {
-            source: String::new(),
-        }
+        Self::default()
+    }
+
+    /// Restores the key/value store (from flash at boot).
+    pub fn set_state(&mut self, state: ScriptState) {
+        self.state = state;
+        self.state_changed = false;
+    }
+
+    pub fn state(&self) -> &ScriptState {
+        &self.state
+    }
+
+    /// True once after the script changed its key/value store.
+    pub fn take_state_changed(&mut self) -> bool {
+        core::mem::take(&mut self.state_changed)
     }
 
-    pub fn update_script(&mut self, script: &str) -> Result<(), String> {
+    /// Marks the script recorded before the last reboot as unsafe to run.
+    pub fn set_quarantine(&mut self, quarantine: Option<Quarantine>) {
+        self.quarantine = quarantine;
+    }
+
+    /// Replaces the script and compiles it together with the helpers in `stdlib.lisp`
+    /// (`min`, `max`, `clamp`, `ramp`, `interp`, `daylight`, `get`, `put`).
+    ///
+    /// A script either defines
+    /// `(export targets (temp humidity soil ec co2 days hour minute weekday state) ...)` itself,
+    /// or is a single expression over those names returning a positional list of integers,
+    /// which gets wrapped into such a function. It must be `export`, blisp only calls exported
+    /// functions from outside the script.
+    pub fn update_script(&mut self, script: &str) -> Result<(), ScriptError> {
         self.source = script.to_string();
-        Ok(())
-    }
-
-
-    pub fn calculate_targets(&mut self, sensors: &SensorData, _current_time: u64, days_since_start: u32, _time_str: &str) -> TargetState {
-        let mut full_script = String::new();
-        
-        // Wrap script in a let block to define variables
-        // (let ((temp 25.0) (humidity 50.0) ...) script)
-        
-        full_script.push_str("(let (");
-        
-        let temp_f = sensors.internal.map(|r| r.temp.to_num::<f32>()).unwrap_or(25.0);
-        full_script.push_str(&alloc::format!("(temp {}) ", temp_f));
-        
-        let hum_f = sensors.internal.map(|r| r.hum).unwrap_or(50) as f32;
-        full_script.push_str(&alloc::format!("(humidity {}) ", hum_f));
-
-        let soil_f = sensors.soil_moisture.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
-        full_script.push_str(&alloc::format!("(soil {}) ", soil_f));
-
-        let ec_f = sensors.ec_level.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
-        full_script.push_str(&alloc::format!("(ec {}) ", ec_f));
-
-        let co2_f = sensors.co2_level.map(|v| v.to_num::<f32>()).unwrap_or(0.0);
-        full_script.push_str(&alloc::format!("(co2 {}) ", co2_f));
-
-        full_script.push_str(&alloc::format!("(days {}) ", days_since_start));
-        // Strings might cause parse issues if blisp doesn't support them fully yet
-        // full_script.push_str(&alloc::format!("(time \"{}\") ", time_str));
-        
-        full_script.push_str(") \n"); // Close definitions list and add newline
-        
-        full_script.push_str(&self.source);
-        full_script.push_str(")"); // Close let
-        
-        // defmt::info!("Full Script:\n{}", full_script.as_str());
-
-        // Context creation
-        // Flow: init -> typing -> eval
-        
-        match blisp::init(&full_script) {
-            Ok(exprs) => {
-                // blisp::typing is a function
-                match blisp::typing(&exprs) {
-                    Ok(ctx) => {
-                        // defmt::info!("Context created");
-                        match blisp::eval(&full_script, &ctx) {
-                             Ok(expr) => {
-                                 defmt::info!("Script Success: {:?}", alloc::format!("{:?}", expr).as_str());
-                                 // TODO: extract values from expr
-                             }
-                             Err(e) => {
-                                 defmt::error!("Script Eval Failed: {:?}", alloc::format!("{:?}", e).as_str());
-                             }
-                        }
-                    }
-                    Err(e) => {
-                        defmt::error!("Script Typing Failed: {:?}", alloc::format!("{:?}", e).as_str());
-                    }
-                }
+        self.ctx = None;
+        self.compile_error = None;
+        self.hash = sandbox::script_hash(&self.source);
+
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(q) = &self.quarantine
+            && q.script_hash == self.hash
+        {
+            let e = ScriptError::LimitExceeded(q.reason);
+            self.compile_error = Some(e.clone());
+            return Err(e);
+        }
+
+        let entry_point = find_entry_point(&self.source);
+        if let Some((false, offset)) = entry_point {
+            let before = &self.source[..offset];
+            let e = ScriptError::Init(SourceError {
+                msg: alloc::format!("declare {} with export instead of defun", ENTRY_POINT),
+                line: before.matches('\n').count() + 1,
+                column: before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1,
+            });
+            self.compile_error = Some(e.clone());
+            return Err(e);
+        }
+
+        let prefix = if entry_point.is_some() {
+            alloc::format!("{}\n", STDLIB)
+        } else {
+            alloc::format!(
+                "{}\n(export {} (temp humidity soil ec co2 days hour minute weekday state) (Pure (-> (Int Int Int Int Int Int Int Int Int '([String Int])) '(Int)))\n",
+                STDLIB, ENTRY_POINT
+            )
+        };
+        let suffix = if entry_point.is_some() { "" } else { "\n)" };
+        let program = alloc::format!("{}{}{}", prefix, self.source, suffix);
+        let line_offset = prefix.matches('\n').count();
+
+        let start = sandbox::now_us();
+        let heap_before = heap_counters();
+
+        let result = blisp::init(&program)
+            .map_err(|e| ScriptError::Init(SourceError::from_lisp(e, line_offset)))
+            .and_then(|exprs| {
+                blisp::typing(&exprs).map_err(|e| ScriptError::Typing(SourceError::from_lisp(e, line_offset)))
+            });
+
+        self.stats.compile_time_us = (sandbox::now_us() - start) as u32;
+        self.stats.compile_heap_bytes = heap_counters().1.saturating_sub(heap_before.1) as u32;
+
+        match result {
+            Ok(ctx) => {
+                self.ctx = Some(ctx);
+                Ok(())
             }
             Err(e) => {
-                defmt::error!("Script Init Failed: {:?}", alloc::format!("{:?}", e).as_str());
+                self.compile_error = Some(e.clone());
+                Err(e)
             }
         }
+    }
+
+    /// True if there is no script to run (only whitespace)
+    pub fn is_empty(&self) -> bool {
+        self.source.trim().is_empty()
+    }
+
+    pub fn stats(&self) -> ScriptStats {
+        self.stats
+    }
+
+    /// Runs the compiled script and turns its result into a `TargetState`.
+    ///
+    /// Sensor values are passed as integers (blisp has no floats): temp in °C, humidity in %,
+    /// soil as raw ADC, ec and co2 in ppm, days since start. Missing sensors read as 0
+    /// (temp 25, humidity 50). `hour`, `minute` and `weekday` (0 = Monday) are local time,
+    /// or 12:00 Monday while the clock is unknown. `state` is the key/value store as a list
+    /// of `["key" value]` pairs.
+    ///
+    /// The script's result may be:
+    /// - a number: the target temperature,
+    /// - a positional list/tuple `[temp humidity vent light]` (trailing fields may be omitted),
+    /// - a record, i.e. a list of `["key" value]` pairs with keys `temp`, `humidity`, `vent`, `light`
+    ///   (or `temp10`, the temperature in tenths of °C), and `vpd100`, a leaf VPD target in
+    ///   hundredths of kPa that takes over from `humidity`,
+    /// - a tuple `[targets state]` of one of the above and a new key/value store.
+    ///
+    /// Any field that is missing or `None` is taken from `defaults`
+    /// (normally `PlantConfiguration::scheduled_targets`).
+    pub fn calculate_targets(
+        &mut self,
+        sensors: &SensorData,
+        defaults: &TargetState,
+        local_time: Option<DateTime<FixedOffset>>,
+        days_since_start: u32,
+    ) -> Result<TargetState, ScriptError> {
+        let ctx = match &self.ctx {
+            Some(ctx) => ctx,
+            None => {
+                return Err(self.compile_error.clone()
+                    .unwrap_or_else(|| ScriptError::Eval(String::from("no script loaded"))));
+            }
+        };
+
+        let temp = sensors.internal.map(|r| r.temp.round().to_num::<i32>()).unwrap_or(25);
+        let hum = sensors.internal.map(|r| r.hum as i32).unwrap_or(50);
+        let soil = sensors.soil_moisture.map(|v| v.round().to_num::<i32>()).unwrap_or(0);
+        let ec = sensors.ec_level.map(|v| v.round().to_num::<i32>()).unwrap_or(0);
+        let co2 = sensors.co2_level.map(|v| v.round().to_num::<i32>()).unwrap_or(0);
+
+        let (hour, minute, weekday) = local_time
+            .map(|t| (t.hour(), t.minute(), t.weekday().num_days_from_monday()))
+            .unwrap_or((12, 0, 0));
+
+        // Only this call is parsed per evaluation, the script itself is already compiled
+        let call = alloc::format!(
+            "({} {} {} {} {} {} {} {} {} {} {})",
+            ENTRY_POINT, temp, hum, soil, ec, co2, days_since_start, hour, minute, weekday,
+            format_state(&self.state)
+        );
 
-        // defmt::info!("Script eval stubbed: Context creation API missing");
-        
-        TargetState {
-            temp: Number::from_num(25.0),
-            humidity: 60,
-            vent_on: true,
-            light_intensity: 255,
+        let start = sandbox::now_us();
+        let heap_before = heap_counters();
+
+        sandbox::arm(&self.limits, self.hash, self.dry_run);
+        let results = blisp::eval_with_step(&call, ctx, &mut sandbox::step);
+        let limit_check = sandbox::disarm();
+
+        let heap_after = heap_counters();
+        self.stats.eval_time_us = (sandbox::now_us() - start) as u32;
+        self.stats.eval_heap_bytes = heap_after.1.saturating_sub(heap_before.1) as u32;
+        self.stats.heap_in_use = heap_after.0 as u32;
+
+        self.stats.eval_steps = limit_check.map_err(ScriptError::LimitExceeded)?;
+        let results = results.map_err(|e| ScriptError::Eval(alloc::format!("{:?}", e)))?;
+
+        // Only the value of the last expression matters
+        let printed = match results.into_iter().next_back() {
+            Some(Ok(printed)) => printed,
+            Some(Err(e)) => return Err(ScriptError::Eval(e)),
+            None => return Err(ScriptError::Type(String::from("script produced no value"))),
+        };
+
+        let value = ScriptValue::parse(&printed).map_err(ScriptError::Type)?;
+        let (target_value, state_value) = split_state(&value);
+        let targets = extract_targets(target_value, defaults)?;
+        if let Some(state_value) = state_value {
+            let new_state = extract_state(state_value)?;
+            if new_state != self.state {
+                self.state = new_state;
+                self.state_changed = true;
+            }
+        }
+        if !self.dry_run {
+            sandbox::set_last_good(&targets);
         }
+        Ok(targets)
+    }
+}
+
+/// Compiles `source` and runs it once, independently of the control loop's script.
+/// Used to check a script before it is saved. An empty script is valid and yields `defaults`.
+/// The running script's last good targets and quarantine record are left alone, also if this
+/// run faults.
+pub fn dry_run(
+    source: &str,
+    sensors: &SensorData,
+    defaults: &TargetState,
+    local_time: Option<DateTime<FixedOffset>>,
+    days_since_start: u32,
+    state: ScriptState,
+) -> Result<TargetState, ScriptError> {
+    if source.len() > SCRIPT_SOURCE_MAX {
+        return Err(ScriptError::Init(SourceError {
+            msg: alloc::format!("script is {} bytes, the limit is {}", source.len(), SCRIPT_SOURCE_MAX),
+            line: 0,
+            column: 0,
+        }));
+    }
+
+    let mut script = UserScript { dry_run: true, ..UserScript::new() };
+    script.set_state(state);
+    script.update_script(source)?;
+    if script.is_empty() {
+        return Ok(defaults.clone());
+    }
+    script.calculate_targets(sensors, defaults, local_time, days_since_start)
+}
+
+/// `'(["a" 1] ["b" 2])`
+fn format_state(state: &ScriptState) -> String {
+    let mut out = String::from("'(");
+    for (key, value) in state.entries.iter() {
+        out.push_str(&alloc::format!("[\"{}\" {}] ", key, value));
+    }
+    out.push(')');
+    out
+}
+
+fn is_state_pair(item: &ScriptValue) -> bool {
+    matches!(
+        item,
+        ScriptValue::List(pair) if pair.len() == 2
+            && matches!(pair[0], ScriptValue::Str(_))
+            && matches!(pair[1], ScriptValue::Number(_))
+    )
+}
+
+/// Splits a `[targets state]` result. Anything else is targets only.
+fn split_state(value: &ScriptValue) -> (&ScriptValue, Option<&ScriptValue>) {
+    if let ScriptValue::List(items) = value
+        && items.len() == 2
+    {
+        // A record entry (["temp" 22]) starts with a string, a targets value never does
+        let first_is_targets = match &items[0] {
+            ScriptValue::List(inner) => !matches!(inner.first(), Some(ScriptValue::Str(_))),
+            _ => false,
+        };
+        let second_is_state = match &items[1] {
+            ScriptValue::List(inner) => inner.iter().all(is_state_pair),
+            _ => false,
+        };
+        if first_is_targets && second_is_state {
+            return (&items[0], Some(&items[1]));
+        }
+    }
+    (value, None)
+}
+
+fn extract_state(value: &ScriptValue) -> Result<ScriptState, ScriptError> {
+    let mut state = ScriptState::default();
+    if let ScriptValue::List(items) = value {
+        for item in items {
+            if let ScriptValue::List(pair) = item
+                && let (ScriptValue::Str(key), ScriptValue::Number(n)) = (&pair[0], &pair[1])
+            {
+                let key = heapless::String::try_from(key.as_str()).map_err(|_| ScriptError::Type(
+                    alloc::format!("state key \"{}\" longer than {} bytes", key, SCRIPT_STATE_MAX_KEY)
+                ))?;
+                state.entries.push((key, *n as i32)).map_err(|_| ScriptError::Type(
+                    alloc::format!("state has more than {} entries", SCRIPT_STATE_MAX_ENTRIES)
+                ))?;
+            }
+        }
+    }
+    Ok(state)
+}
+
+/// Where the script defines the entry point: (exported, byte offset of the definition).
+fn find_entry_point(source: &str) -> Option<(bool, usize)> {
+    ["(export ", "(defun "].iter().find_map(|kw| {
+        source.match_indices(kw).find_map(|(i, _)| {
+            let rest = source[i + kw.len()..].trim_start();
+            rest.strip_prefix(ENTRY_POINT)
+                .is_some_and(|after| after.starts_with(|c: char| c.is_whitespace() || c == '('))
+                .then_some((*kw == "(export ", i))
+        })
+    })
+}
+
+/// (bytes in use, total bytes ever allocated)
+fn heap_counters() -> (usize, u64) {
+    let talc = crate::ALLOCATOR.inner().lock();
+    let counters = talc.get_counters();
+    (counters.allocated_bytes, counters.total_allocated_bytes)
+}
+
+/// Field order for the positional form `[temp humidity vent light]`
+const FIELDS: [&str; 4] = ["temp", "humidity", "vent", "light"];
+
+/// Record-only field: temperature in tenths of °C, for recipes that need half degrees
+const FIELD_TEMP10: &str = "temp10";
+
+/// Record-only field: leaf VPD target in hundredths of kPa (`["vpd100" 110]` = 1.1 kPa)
+const FIELD_VPD100: &str = "vpd100";
+
+fn extract_targets(value: &ScriptValue, defaults: &TargetState) -> Result<TargetState, ScriptError> {
+    let mut targets = defaults.clone();
+
+    let items = match value {
+        ScriptValue::Number(_) => {
+            apply_field(&mut targets, "temp", value)?;
+            return Ok(targets);
+        }
+        ScriptValue::List(items) => items,
+        other => {
+            return Err(ScriptError::Type(alloc::format!(
+                "expected a number, list or record, got {}", other.type_name()
+            )));
+        }
+    };
+
+    let is_record = !items.is_empty() && items.iter().all(|item| matches!(
+        item,
+        ScriptValue::List(pair) if pair.len() == 2 && matches!(pair[0], ScriptValue::Str(_))
+    ));
+
+    if is_record {
+        for item in items {
+            if let ScriptValue::List(pair) = item
+                && let ScriptValue::Str(key) = &pair[0]
+            {
+                if !FIELDS.contains(&key.as_str()) && key != FIELD_TEMP10 && key != FIELD_VPD100 {
+                    return Err(ScriptError::Type(alloc::format!("unknown field \"{}\"", key)));
+                }
+                apply_field(&mut targets, key, &pair[1])?;
+            }
+        }
+    } else {
+        if items.len() > FIELDS.len() {
+            return Err(ScriptError::Type(alloc::format!(
+                "expected at most {} values [temp humidity vent light], got {}", FIELDS.len(), items.len()
+            )));
+        }
+        for (field, item) in FIELDS.iter().zip(items.iter()) {
+            apply_field(&mut targets, field, item)?;
+        }
+    }
+
+    Ok(targets)
+}
+
+fn apply_field(targets: &mut TargetState, field: &str, value: &ScriptValue) -> Result<(), ScriptError> {
+    // `None` leaves the default in place
+    let value = match value.unwrap_option() {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    match field {
+        "temp" | FIELD_TEMP10 => {
+            let mut t = expect_number(field, value)?;
+            if field == FIELD_TEMP10 {
+                t /= 10.0;
+            }
+            if !(0.0..=50.0).contains(&t) {
+                return Err(ScriptError::Type(alloc::format!("temp {} out of range 0..50", t)));
+            }
+            targets.temp = Number::from_num(t);
+        }
+        FIELD_VPD100 => {
+            let v = expect_number(field, value)? / 100.0;
+            if !(0.2..=3.0).contains(&v) {
+                return Err(ScriptError::Type(alloc::format!("vpd {} kPa out of range 0.2..3.0", v)));
+            }
+            targets.vpd = Some(Number::from_num(v));
+        }
+        "humidity" => {
+            let h = expect_number(field, value)?;
+            if !(0.0..=100.0).contains(&h) {
+                return Err(ScriptError::Type(alloc::format!("humidity {} out of range 0..100", h)));
+            }
+            targets.humidity = h as u8;
+        }
+        "vent" => {
+            targets.vent_on = match value {
+                ScriptValue::Bool(b) => *b,
+                ScriptValue::Number(n) => *n != 0.0,
+                other => {
+                    return Err(ScriptError::Type(alloc::format!("vent must be a bool, got {}", other.type_name())));
+                }
+            };
+        }
+        "light" => {
+            let l = expect_number(field, value)?;
+            if !(0.0..=255.0).contains(&l) {
+                return Err(ScriptError::Type(alloc::format!("light {} out of range 0..255", l)));
+            }
+            targets.light_intensity = l as u8;
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+fn expect_number(field: &str, value: &ScriptValue) -> Result<f32, ScriptError> {
+    match value {
+        ScriptValue::Number(n) => Ok(*n),
+        other => Err(ScriptError::Type(alloc::format!("{} must be a number, got {}", field, other.type_name()))),
     }
 }
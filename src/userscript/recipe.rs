@@ -0,0 +1,198 @@
+//! Runs a script over a sensor time series on the host, the way the control loop would,
+//! so grow recipes can be checked on a PC before they go on the device.
+//!
+//! Simulated time starts on a Monday at day 0, in UTC. Each sample is one control cycle.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+
+use super::{ScriptError, ScriptStats, UserScript};
+use crate::config_types::{PlantConfiguration, ScriptState};
+use crate::control::{Number, TargetState};
+use crate::sensor_manager::{SensorData, TempHumReading};
+
+/// Sensor values at one point in simulated time.
+#[derive(Clone, Debug, Default)]
+pub struct SensorSample {
+    pub day: u32,
+    pub hour: u8,
+    pub minute: u8,
+    pub sensors: SensorData,
+}
+
+/// What the controller would have been given for one sample.
+#[derive(Clone, Debug)]
+pub struct RecipeStep {
+    pub day: u32,
+    pub hour: u8,
+    pub minute: u8,
+    pub targets: TargetState,
+    /// False if `targets` came from the schedule or the last good script targets
+    pub from_script: bool,
+    pub error: Option<ScriptError>,
+    pub stats: ScriptStats,
+}
+
+/// Parses sensor samples from CSV.
+///
+/// The header names the columns: `day`, `hour` and `minute` are required, `temp`, `humidity`,
+/// `soil`, `ec` and `co2` are optional. An empty cell is a missing reading. Blank lines and
+/// lines starting with `#` are skipped.
+///
+/// ```text
+/// day,hour,minute,temp,humidity,soil,ec,co2
+/// 0,6,0,21.5,70,180,700,
+/// ```
+pub fn parse_sensor_csv(text: &str) -> Result<Vec<SensorSample>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'));
+
+    let (_, header) = lines.next().ok_or_else(|| String::from("empty CSV"))?;
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    for required in ["day", "hour", "minute"] {
+        if !columns.contains(&required) {
+            return Err(alloc::format!("missing column `{}`", required));
+        }
+    }
+    if let Some(unknown) = columns
+        .iter()
+        .find(|c| !matches!(**c, "day" | "hour" | "minute" | "temp" | "humidity" | "soil" | "ec" | "co2"))
+    {
+        return Err(alloc::format!("unknown column `{}`", unknown));
+    }
+
+    let mut samples = Vec::new();
+    for (line_no, line) in lines {
+        let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+        if cells.len() != columns.len() {
+            return Err(alloc::format!("line {}: expected {} cells, got {}", line_no, columns.len(), cells.len()));
+        }
+
+        let mut sample = SensorSample::default();
+        let mut temp = None;
+        let mut humidity = None;
+        for (column, cell) in columns.iter().zip(cells) {
+            if cell.is_empty() {
+                continue;
+            }
+            let value: f32 = cell
+                .parse()
+                .map_err(|_| alloc::format!("line {}: `{}` is not a number ({})", line_no, cell, column))?;
+            match *column {
+                "day" => sample.day = value as u32,
+                "hour" => sample.hour = value as u8,
+                "minute" => sample.minute = value as u8,
+                "temp" => temp = Some(value),
+                "humidity" => humidity = Some(value),
+                "soil" => sample.sensors.soil_moisture = Some(Number::from_num(value)),
+                "ec" => sample.sensors.ec_level = Some(Number::from_num(value)),
+                "co2" => sample.sensors.co2_level = Some(Number::from_num(value)),
+                _ => {}
+            }
+        }
+        if sample.hour > 23 || sample.minute > 59 {
+            return Err(alloc::format!("line {}: invalid time {}:{}", line_no, sample.hour, sample.minute));
+        }
+        // The internal sensor reports both or neither
+        if let (Some(t), Some(h)) = (temp, humidity) {
+            sample.sensors.internal = Some(TempHumReading {
+                temp: Number::from_num(t),
+                hum: h.clamp(0.0, 100.0) as u8,
+            });
+            sample.sensors.update_derived();
+        }
+        samples.push(sample);
+    }
+    Ok(samples)
+}
+
+/// A script running against simulated sensors, with the control loop's fallback rules.
+pub struct RecipeRun {
+    script: UserScript,
+    plant: PlantConfiguration,
+    last_good: Option<TargetState>,
+}
+
+impl RecipeRun {
+    /// Compiles `source`. `plant` provides the schedule used as defaults and fallback.
+    pub fn new(source: &str, plant: PlantConfiguration) -> Result<Self, ScriptError> {
+        let mut script = UserScript::new();
+        script.update_script(source)?;
+        Ok(Self { script, plant, last_good: None })
+    }
+
+    /// Starts from a saved key/value store instead of an empty one.
+    pub fn with_state(mut self, state: ScriptState) -> Self {
+        self.script.set_state(state);
+        self
+    }
+
+    pub fn state(&self) -> &ScriptState {
+        self.script.state()
+    }
+
+    /// One control cycle.
+    pub fn step(&mut self, sample: &SensorSample) -> RecipeStep {
+        let schedule_targets = self.plant.scheduled_targets(sample.hour);
+        let local_time = simulated_time(sample);
+
+        let (targets, from_script, error) = if self.script.is_empty() {
+            (schedule_targets, false, None)
+        } else {
+            match self.script.calculate_targets(&sample.sensors, &schedule_targets, local_time, sample.day) {
+                Ok(t) => {
+                    self.last_good = Some(t.clone());
+                    (t, true, None)
+                }
+                Err(e @ ScriptError::LimitExceeded(_)) => {
+                    (self.last_good.clone().unwrap_or(schedule_targets), false, Some(e))
+                }
+                Err(e) => (schedule_targets, false, Some(e)),
+            }
+        };
+
+        RecipeStep {
+            day: sample.day,
+            hour: sample.hour,
+            minute: sample.minute,
+            targets,
+            from_script,
+            error,
+            stats: self.script.stats(),
+        }
+    }
+
+    /// Runs every sample in order. With `days`, the series is repeated (shifted by its own
+    /// length in days) until `days` simulated days are covered.
+    pub fn run(&mut self, samples: &[SensorSample], days: Option<u32>) -> Vec<RecipeStep> {
+        let period = samples.iter().map(|s| s.day + 1).max().unwrap_or(1);
+        let end_day = days.unwrap_or(period);
+
+        let mut steps = Vec::new();
+        let mut offset = 0;
+        while offset < end_day && !samples.is_empty() {
+            for sample in samples {
+                let day = sample.day + offset;
+                if day >= end_day {
+                    break;
+                }
+                let shifted = SensorSample { day, ..sample.clone() };
+                steps.push(self.step(&shifted));
+            }
+            offset += period;
+        }
+        steps
+    }
+}
+
+/// Day 0 is Monday 2024-01-01.
+fn simulated_time(sample: &SensorSample) -> Option<DateTime<FixedOffset>> {
+    let naive = NaiveDate::from_ymd_opt(2024, 1, 1)?
+        .and_hms_opt(sample.hour as u32, sample.minute as u32, 0)?
+        .checked_add_signed(Duration::days(sample.day as i64))?;
+    Some(DateTime::from_naive_utc_and_offset(naive, FixedOffset::east_opt(0)?))
+}
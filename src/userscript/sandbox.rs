@@ -0,0 +1,341 @@
+//! Resource limits for script evaluation.
+//!
+//! The interpreter calls [`step`] before every expression it evaluates. The step count, call
+//! depth, deadline and the script's live heap (tracked by the global allocator) are checked
+//! there, and a tripped limit stops the evaluation with a runtime error that
+//! `calculate_targets` reports as `ScriptError::LimitExceeded`.
+//!
+//! An allocation failure in `no_std` always panics, so the allocator only refuses memory as a
+//! last resort, when a single expression allocates far past the heap budget before the next
+//! step. That fault, like any other crash while a script runs, is caught in `HardFault`: the
+//! script is recorded in the watchdog scratch registers (which survive a watchdog reset) and
+//! the chip reboots. On the next boot the script is refused and the controller continues on
+//! the last good targets.
+//!
+//! A dry run (checking a script before it is saved) gets the same limits, but a fault during
+//! it reboots without a record: the quarantine and the last good targets belong to the
+//! control loop's script.
+
+use alloc::string::String;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+
+#[cfg(all(feature = "firmware", feature = "host"))]
+compile_error!("features `firmware` and `host` are mutually exclusive");
+
+#[cfg(feature = "firmware")]
+use embassy_rp::watchdog::Watchdog;
+use portable_atomic::{AtomicBool, AtomicIsize, AtomicU8, AtomicU32, AtomicU64, Ordering};
+
+#[cfg(feature = "firmware")]
+use crate::control::Number;
+use crate::control::TargetState;
+
+/// Which limit stopped a script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "firmware", derive(defmt::Format))]
+#[repr(u8)]
+pub enum LimitKind {
+    Steps = 1,
+    Time = 2,
+    Heap = 3,
+    /// The script crashed the interpreter
+    Fault = 4,
+    Depth = 5,
+}
+
+impl LimitKind {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(LimitKind::Steps),
+            2 => Some(LimitKind::Time),
+            3 => Some(LimitKind::Heap),
+            4 => Some(LimitKind::Fault),
+            5 => Some(LimitKind::Depth),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LimitKind::Steps => "step limit",
+            LimitKind::Time => "time limit",
+            LimitKind::Heap => "heap limit",
+            LimitKind::Fault => "interpreter fault",
+            LimitKind::Depth => "recursion limit",
+        }
+    }
+}
+
+/// Per-evaluation limits.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "firmware", derive(defmt::Format))]
+pub struct ScriptLimits {
+    /// Expressions the interpreter may evaluate
+    pub max_steps: u32,
+    pub max_time_ms: u32,
+    /// Live heap bytes the script may hold at any point
+    pub max_heap_bytes: u32,
+    /// Nesting of expressions being evaluated, bounds the interpreter's stack use
+    pub max_depth: u32,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_steps: 50_000,
+            max_time_ms: 250,
+            max_heap_bytes: 24 * 1024, // of the 160 KB heap
+            max_depth: 128,
+        }
+    }
+}
+
+/// A script that tripped a limit before the last reboot.
+#[derive(Clone, Debug)]
+pub struct Quarantine {
+    pub script_hash: u32,
+    pub reason: LimitKind,
+    pub last_good: Option<TargetState>,
+}
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static TRIPPED: AtomicU8 = AtomicU8::new(0);
+static STEPS: AtomicU32 = AtomicU32::new(0);
+static MAX_STEPS: AtomicU32 = AtomicU32::new(0);
+static HEAP_USED: AtomicIsize = AtomicIsize::new(0);
+static MAX_HEAP: AtomicIsize = AtomicIsize::new(0);
+static MAX_DEPTH: AtomicU32 = AtomicU32::new(0);
+static DEADLINE_US: AtomicU64 = AtomicU64::new(0);
+static SCRIPT_HASH: AtomicU32 = AtomicU32::new(0);
+
+// Last good targets, packed so the fault handler can store them without touching the heap
+static LAST_GOOD_TEMP: AtomicU32 = AtomicU32::new(0);
+static LAST_GOOD_REST: AtomicU32 = AtomicU32::new(0);
+static LAST_GOOD_VPD: AtomicU32 = AtomicU32::new(0);
+
+/// The deadline is checked every this many steps, reading the clock is not free
+const TIME_CHECK_STEPS: u32 = 64;
+/// Past this multiple of the heap budget the allocator refuses memory, see the module docs
+const HARD_HEAP_FACTOR: isize = 4;
+
+/// Microseconds since boot (firmware) or since first use (host).
+#[cfg(feature = "firmware")]
+pub(super) fn now_us() -> u64 {
+    embassy_time::Instant::now().as_micros()
+}
+
+#[cfg(feature = "host")]
+pub(super) fn now_us() -> u64 {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START.get_or_init(std::time::Instant::now).elapsed().as_micros() as u64
+}
+
+// Watchdog scratch layout
+#[cfg(feature = "firmware")]
+const SCRATCH_MAGIC: usize = 0;
+#[cfg(feature = "firmware")]
+const SCRATCH_HASH: usize = 1;
+#[cfg(feature = "firmware")]
+const SCRATCH_TEMP: usize = 2;
+#[cfg(feature = "firmware")]
+const SCRATCH_REST: usize = 3;
+#[cfg(feature = "firmware")]
+const SCRATCH_VPD: usize = 4;
+#[cfg(feature = "firmware")]
+const QUARANTINE_MAGIC: u32 = 0x5C41_B000;
+const LAST_GOOD_VALID: u32 = 1 << 24;
+const LAST_GOOD_VPD_VALID: u32 = 1 << 25;
+
+/// Global allocator wrapper that tracks the heap held by the running script.
+pub struct SandboxedAlloc<A> {
+    inner: A,
+}
+
+impl<A> SandboxedAlloc<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for SandboxedAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if ARMED.load(Ordering::Relaxed) {
+            let used = HEAP_USED.fetch_add(layout.size() as isize, Ordering::Relaxed) + layout.size() as isize;
+            // Going over budget is caught by the next step, this only stops a runaway
+            // expression. On the host a failed allocation would abort the whole run.
+            if cfg!(feature = "firmware") && used > MAX_HEAP.load(Ordering::Relaxed) * HARD_HEAP_FACTOR {
+                trip(LimitKind::Heap);
+                return ptr::null_mut();
+            }
+        }
+        unsafe { self.inner.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ARMED.load(Ordering::Relaxed) {
+            HEAP_USED.fetch_sub(layout.size() as isize, Ordering::Relaxed);
+        }
+        unsafe { self.inner.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if !ARMED.load(Ordering::Relaxed) {
+            return unsafe { self.inner.realloc(ptr, layout, new_size) };
+        }
+        // Go through alloc/dealloc so the growth is charged
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        let new_ptr = unsafe { self.alloc(new_layout) };
+        if !new_ptr.is_null() {
+            unsafe {
+                ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                self.dealloc(ptr, layout);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Keeps the first limit that was hit.
+fn trip(kind: LimitKind) {
+    let _ = TRIPPED.compare_exchange(0, kind as u8, Ordering::Relaxed, Ordering::Relaxed);
+}
+
+fn check(depth: usize) -> Option<LimitKind> {
+    let steps = STEPS.fetch_add(1, Ordering::Relaxed) + 1;
+    if steps > MAX_STEPS.load(Ordering::Relaxed) {
+        return Some(LimitKind::Steps);
+    }
+    if depth > MAX_DEPTH.load(Ordering::Relaxed) as usize {
+        return Some(LimitKind::Depth);
+    }
+    if HEAP_USED.load(Ordering::Relaxed) > MAX_HEAP.load(Ordering::Relaxed) {
+        return Some(LimitKind::Heap);
+    }
+    if steps.is_multiple_of(TIME_CHECK_STEPS) && now_us() > DEADLINE_US.load(Ordering::Relaxed) {
+        return Some(LimitKind::Time);
+    }
+    None
+}
+
+/// Step hook for `blisp::eval_with_step`, called with the call depth before every
+/// expression. Fails once any limit has tripped, which ends the evaluation.
+pub(super) fn step(depth: usize) -> Result<(), String> {
+    if !ARMED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if let Some(kind) = LimitKind::from_u8(TRIPPED.load(Ordering::Relaxed)).or_else(|| check(depth)) {
+        trip(kind);
+        return Err(alloc::format!("{} exceeded", kind.as_str()));
+    }
+    Ok(())
+}
+
+/// Starts checking the limits for the script identified by `script_hash`.
+/// `dry_run` is set when this is not the control loop's script, see the module docs.
+pub(super) fn arm(limits: &ScriptLimits, script_hash: u32, dry_run: bool) {
+    let deadline = now_us() + limits.max_time_ms as u64 * 1000;
+    STEPS.store(0, Ordering::Relaxed);
+    MAX_STEPS.store(limits.max_steps, Ordering::Relaxed);
+    HEAP_USED.store(0, Ordering::Relaxed);
+    MAX_HEAP.store(limits.max_heap_bytes as isize, Ordering::Relaxed);
+    MAX_DEPTH.store(limits.max_depth, Ordering::Relaxed);
+    DEADLINE_US.store(deadline, Ordering::Relaxed);
+    SCRIPT_HASH.store(script_hash, Ordering::Relaxed);
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+    TRIPPED.store(0, Ordering::Relaxed);
+    ARMED.store(true, Ordering::SeqCst);
+}
+
+/// Stops checking. Returns the steps used, or the first limit that tripped during the run
+/// (including a deadline overrun after the last check).
+pub(super) fn disarm() -> Result<u32, LimitKind> {
+    ARMED.store(false, Ordering::SeqCst);
+    if let Some(kind) = LimitKind::from_u8(TRIPPED.load(Ordering::Relaxed)) {
+        return Err(kind);
+    }
+    if now_us() > DEADLINE_US.load(Ordering::Relaxed) {
+        return Err(LimitKind::Time);
+    }
+    Ok(STEPS.load(Ordering::Relaxed))
+}
+
+/// Remembers targets to fall back on if the next evaluation faults.
+/// Only for the control loop's script, never for a dry run.
+pub(super) fn set_last_good(targets: &TargetState) {
+    let rest = targets.humidity as u32
+        | (targets.vent_on as u32) << 8
+        | (targets.light_intensity as u32) << 16
+        | LAST_GOOD_VALID
+        | if targets.vpd.is_some() { LAST_GOOD_VPD_VALID } else { 0 };
+    LAST_GOOD_TEMP.store(targets.temp.to_bits() as u32, Ordering::Relaxed);
+    LAST_GOOD_VPD.store(targets.vpd.map_or(0, |v| v.to_bits() as u32), Ordering::Relaxed);
+    LAST_GOOD_REST.store(rest, Ordering::Relaxed);
+}
+
+/// FNV-1a, to recognise a quarantined script after reboot.
+pub fn script_hash(source: &str) -> u32 {
+    source.bytes().fold(0x811C_9DC5u32, |h, b| (h ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+/// Reads and clears the quarantine record left by a script fault before the last reset.
+#[cfg(feature = "firmware")]
+pub fn take_quarantine(watchdog: &mut Watchdog) -> Option<Quarantine> {
+    let magic = watchdog.get_scratch(SCRATCH_MAGIC);
+    if magic & 0xFFFF_FF00 != QUARANTINE_MAGIC {
+        return None;
+    }
+    watchdog.set_scratch(SCRATCH_MAGIC, 0);
+
+    let reason = LimitKind::from_u8((magic & 0xFF) as u8)?;
+    let rest = watchdog.get_scratch(SCRATCH_REST);
+    let last_good = if rest & LAST_GOOD_VALID != 0 {
+        Some(TargetState {
+            temp: Number::from_bits(watchdog.get_scratch(SCRATCH_TEMP) as i32),
+            humidity: rest as u8,
+            vpd: (rest & LAST_GOOD_VPD_VALID != 0)
+                .then(|| Number::from_bits(watchdog.get_scratch(SCRATCH_VPD) as i32)),
+            vent_on: (rest >> 8) & 1 != 0,
+            light_intensity: (rest >> 16) as u8,
+        })
+    } else {
+        None
+    };
+
+    Some(Quarantine {
+        script_hash: watchdog.get_scratch(SCRATCH_HASH),
+        reason,
+        last_good,
+    })
+}
+
+#[cfg(feature = "firmware")]
+#[cortex_m_rt::exception]
+unsafe fn HardFault(_frame: &cortex_m_rt::ExceptionFrame) -> ! {
+    if ARMED.load(Ordering::Relaxed) {
+        // A runaway allocation surfaces here as a failed allocation -> panic -> udf.
+        // Anything else while the script runs is the script's fault too.
+        let reason = LimitKind::from_u8(TRIPPED.load(Ordering::Relaxed)).unwrap_or(LimitKind::Fault);
+
+        let p = unsafe { embassy_rp::Peripherals::steal() };
+        let mut watchdog = Watchdog::new(p.WATCHDOG);
+        // A dry run leaves the running script's record alone, it is not the one that faulted
+        if !DRY_RUN.load(Ordering::Relaxed) {
+            watchdog.set_scratch(SCRATCH_MAGIC, QUARANTINE_MAGIC | reason as u32);
+            watchdog.set_scratch(SCRATCH_HASH, SCRIPT_HASH.load(Ordering::Relaxed));
+            watchdog.set_scratch(SCRATCH_TEMP, LAST_GOOD_TEMP.load(Ordering::Relaxed));
+            watchdog.set_scratch(SCRATCH_REST, LAST_GOOD_REST.load(Ordering::Relaxed));
+            watchdog.set_scratch(SCRATCH_VPD, LAST_GOOD_VPD.load(Ordering::Relaxed));
+        }
+        watchdog.trigger_reset();
+    }
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,134 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Value printed by blisp for the last evaluated expression.
+///
+/// blisp hands results back as their printed form (`25`, `true`, `'(1 2)`,
+/// `[1 2]`, `"text"`, `(Some 3)`), so we parse that text back into a small tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptValue {
+    Number(f32),
+    Bool(bool),
+    Str(String),
+    Atom(String),
+    List(Vec<ScriptValue>),
+}
+
+impl ScriptValue {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser { bytes: text.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_ws();
+        if parser.pos != parser.bytes.len() {
+            return Err(alloc::format!("unexpected trailing input at {}", parser.pos));
+        }
+        Ok(value)
+    }
+
+    /// Unwraps `(Some x)` into `x` and maps `None` to `Option::None`.
+    /// Any other value is returned as-is.
+    pub fn unwrap_option(&self) -> Option<&ScriptValue> {
+        match self {
+            ScriptValue::Atom(a) if a == "None" => None,
+            ScriptValue::List(items) if items.len() == 2 && items[0] == ScriptValue::Atom("Some".into()) => {
+                items[1].unwrap_option()
+            }
+            other => Some(other),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ScriptValue::Number(_) => "number",
+            ScriptValue::Bool(_) => "bool",
+            ScriptValue::Str(_) => "string",
+            ScriptValue::Atom(_) => "symbol",
+            ScriptValue::List(_) => "list",
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_ws(&mut self) {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn value(&mut self) -> Result<ScriptValue, String> {
+        self.skip_ws();
+        match self.peek() {
+            None => Err(String::from("unexpected end of result")),
+            // Quoted list: '(1 2 3)
+            Some(b'\'') => {
+                self.pos += 1;
+                self.value()
+            }
+            Some(b'(') => self.seq(b')'),
+            Some(b'[') => self.seq(b']'),
+            Some(b'"') => self.string(),
+            Some(_) => self.atom(),
+        }
+    }
+
+    fn seq(&mut self, close: u8) -> Result<ScriptValue, String> {
+        self.pos += 1; // opening bracket
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                None => return Err(String::from("unterminated list in result")),
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    return Ok(ScriptValue::List(items));
+                }
+                Some(_) => items.push(self.value()?),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<ScriptValue, String> {
+        self.pos += 1; // opening quote
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == b'"' {
+                let s = core::str::from_utf8(&self.bytes[start..self.pos]).map_err(|_| String::from("invalid utf-8 in result"))?;
+                self.pos += 1;
+                return Ok(ScriptValue::Str(s.into()));
+            }
+            self.pos += 1;
+        }
+        Err(String::from("unterminated string in result"))
+    }
+
+    fn atom(&mut self) -> Result<ScriptValue, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() || matches!(c, b'(' | b')' | b'[' | b']' | b'"') {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(alloc::format!("unexpected '{}' at {}", self.bytes[start] as char, start));
+        }
+        let token = core::str::from_utf8(&self.bytes[start..self.pos]).map_err(|_| String::from("invalid utf-8 in result"))?;
+        Ok(match token {
+            "true" => ScriptValue::Bool(true),
+            "false" => ScriptValue::Bool(false),
+            _ => match token.parse::<f32>() {
+                Ok(n) => ScriptValue::Number(n),
+                Err(_) => ScriptValue::Atom(token.into()),
+            },
+        })
+    }
+}
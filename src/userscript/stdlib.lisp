@@ -0,0 +1,68 @@
+; Built-in helpers, compiled together with every user script.
+; All values are integers (blisp has no floats).
+
+(defun min (a b) (Pure (-> (Int Int) Int))
+  (if (< a b) a b))
+
+(defun max (a b) (Pure (-> (Int Int) Int))
+  (if (> a b) a b))
+
+(defun clamp (x lo hi) (Pure (-> (Int Int Int) Int))
+  (max lo (min x hi)))
+
+; Linear ramp from `from` (at day 0) to `to` (at day `over_days` and after).
+; Night temperature down 0.5 C/day during weeks 3-5, in tenths of a degree:
+; (ramp (- days 14) 220 115 21) -> 220 until day 14, then -5 per day until day 35.
+(defun ramp (day from to over_days) (Pure (-> (Int Int Int Int) Int))
+  (if (<= day 0)
+    from
+    (if (>= day over_days)
+      to
+      (+ from (/ (* (- to from) day) over_days)))))
+
+; Piecewise-linear lookup in a table of [x y] points sorted by x.
+; Clamps to the first/last y outside the table.
+; (interp days '([0 20] [14 24] [35 18]))
+(defun interp (x table) (Pure (-> (Int '([Int Int])) Int))
+  (match table
+    ((Cons [x0 y0] rest) (interp_from x x0 y0 rest))
+    (_ 0)))
+
+(defun interp_from (x x0 y0 table) (Pure (-> (Int Int Int '([Int Int])) Int))
+  (if (<= x x0)
+    y0
+    (match table
+      ((Cons [x1 y1] rest)
+        (if (<= x x1)
+          (+ y0 (/ (* (- y1 y0) (- x x0)) (max 1 (- x1 x0))))
+          (interp_from x x1 y1 rest)))
+      (_ y0))))
+
+; Sunrise/sunset light curve. Times are minutes since local midnight.
+; 0 outside [sunrise, sunset], ramps up to `peak` over `fade` minutes after sunrise
+; and back down over `fade` minutes before sunset.
+; (daylight (+ (* hour 60) minute) 360 1260 45 255)
+(defun daylight (now sunrise sunset fade peak) (Pure (-> (Int Int Int Int Int) Int))
+  (if (< now sunrise)
+    0
+    (if (>= now sunset)
+      0
+      (min (ramp (- now sunrise) 0 peak (max 1 fade))
+           (ramp (- sunset now) 0 peak (max 1 fade))))))
+
+; Persistent key/value store, passed to `targets` as `state`.
+; Return [targets state] from `targets` to keep an updated store for the next run.
+; (get state "light_min" 0)
+(defun get (state key default) (Pure (-> ('([String Int]) String Int) Int))
+  (match state
+    ((Cons [k v] rest) (if (eq k key) v (get rest key default)))
+    (_ default)))
+
+; (put state "light_min" (+ (get state "light_min" 0) 1))
+(defun put (state key value) (Pure (-> ('([String Int]) String Int) '([String Int])))
+  (match state
+    ((Cons [k v] rest)
+      (if (eq k key)
+        (Cons [key value] rest)
+        (Cons [k v] (put rest key value))))
+    (_ (Cons [key value] Nil))))
@@ -0,0 +1,130 @@
+//! Runs the example recipes in `recipes/` over their whole schedule and checks the targets
+//! the controller would get.
+//!
+//! ```text
+//! cargo test --no-default-features --features host --target x86_64-unknown-linux-gnu
+//! ```
+
+use std::sync::Mutex;
+
+use rp2040_plant_automation::config_types::PlantConfiguration;
+use rp2040_plant_automation::control::Number;
+use rp2040_plant_automation::sensor_manager::{SensorData, TempHumReading};
+use rp2040_plant_automation::userscript::recipe::{RecipeRun, RecipeStep, SensorSample};
+
+const TOMATO: &str = include_str!("../recipes/tomato.lisp");
+const LETTUCE: &str = include_str!("../recipes/lettuce.lisp");
+
+// The sandbox limits are process-wide, so scripts must not run on two test threads at once
+static SCRIPT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Every half hour for `days` days. The room is 22 °C at 60 %, 26 °C in the evening.
+fn samples(days: u32) -> Vec<SensorSample> {
+    let mut samples = Vec::new();
+    for day in 0..days {
+        for hour in 0..24 {
+            for minute in [0, 30] {
+                let temp = if (19..21).contains(&hour) { 26 } else { 22 };
+                let mut sensors = SensorData {
+                    internal: Some(TempHumReading { temp: Number::from_num(temp), hum: 60 }),
+                    ..Default::default()
+                };
+                sensors.update_derived();
+                samples.push(SensorSample { day, hour, minute, sensors });
+            }
+        }
+    }
+    samples
+}
+
+fn run(source: &str, days: u32) -> Vec<RecipeStep> {
+    let _guard = SCRIPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut recipe = RecipeRun::new(source, PlantConfiguration::default()).expect("recipe compiles");
+    let steps = recipe.run(&samples(days), None);
+    if let Some(step) = steps.iter().find(|s| !s.from_script) {
+        panic!("day {} {:02}:{:02} fell back: {:?}", step.day, step.hour, step.minute, step.error);
+    }
+    steps
+}
+
+fn at(steps: &[RecipeStep], day: u32, hour: u8, minute: u8) -> &RecipeStep {
+    steps
+        .iter()
+        .find(|s| s.day == day && s.hour == hour && s.minute == minute)
+        .expect("sample in range")
+}
+
+fn temp(step: &RecipeStep) -> f32 {
+    step.targets.temp.to_num()
+}
+
+fn assert_vpd(step: &RecipeStep, kpa: f32) {
+    let vpd = step.targets.vpd.map(|v| v.to_num::<f32>());
+    assert!(vpd.is_some_and(|v| (v - kpa).abs() < 0.001), "day {}: {:?}, expected {}", step.day, vpd, kpa);
+}
+
+#[test]
+fn tomato_temperature_ramps_down_with_cooler_nights() {
+    let steps = run(TOMATO, 43);
+
+    assert_eq!(temp(at(&steps, 0, 12, 0)), 24.0);
+    assert_eq!(temp(at(&steps, 0, 3, 0)), 21.0);
+    assert_eq!(temp(at(&steps, 21, 12, 0)), 22.5);
+    assert_eq!(temp(at(&steps, 21, 3, 0)), 19.5);
+    assert_eq!(temp(at(&steps, 42, 12, 0)), 21.0);
+    assert_eq!(temp(at(&steps, 42, 23, 30)), 18.0);
+
+    // Never warmer from one day to the next at the same time of day
+    for pair in steps.windows(48 + 1) {
+        assert!(temp(&pair[48]) <= temp(&pair[0]), "day {} {:02}:{:02}", pair[48].day, pair[48].hour, pair[48].minute);
+    }
+}
+
+#[test]
+fn tomato_vpd_follows_the_growth_stages() {
+    let steps = run(TOMATO, 43);
+
+    assert_vpd(at(&steps, 0, 12, 0), 0.8);
+    assert_vpd(at(&steps, 7, 12, 0), 0.9);
+    assert_vpd(at(&steps, 14, 12, 0), 1.0);
+    assert_vpd(at(&steps, 21, 3, 0), 1.1);
+    assert_vpd(at(&steps, 28, 0, 0), 1.2);
+    assert_vpd(at(&steps, 42, 23, 30), 1.2);
+
+    // The VPD target takes over, humidity stays at the schedule's default
+    let default_humidity = PlantConfiguration::default().scheduled_targets(12).humidity;
+    assert!(steps.iter().all(|s| s.targets.humidity == default_humidity));
+}
+
+#[test]
+fn tomato_light_has_sunrise_and_sunset() {
+    let steps = run(TOMATO, 43);
+
+    for day in [0, 20, 42] {
+        assert_eq!(at(&steps, day, 5, 30).targets.light_intensity, 0);
+        assert_eq!(at(&steps, day, 6, 0).targets.light_intensity, 0);
+        assert_eq!(at(&steps, day, 6, 30).targets.light_intensity, 255);
+        assert_eq!(at(&steps, day, 12, 0).targets.light_intensity, 255);
+        assert_eq!(at(&steps, day, 21, 30).targets.light_intensity, 255);
+        assert_eq!(at(&steps, day, 22, 0).targets.light_intensity, 0);
+    }
+
+    for step in &steps {
+        assert_eq!(step.targets.vent_on, step.targets.light_intensity > 0, "day {} {:02}:{:02}", step.day, step.hour, step.minute);
+    }
+}
+
+#[test]
+fn lettuce_keeps_fixed_climate_and_vents_when_hot() {
+    let steps = run(LETTUCE, 14);
+
+    for step in &steps {
+        let t = &step.targets;
+        let lights_on = (7..19).contains(&step.hour);
+        assert_eq!(temp(step), 20.0);
+        assert_eq!(t.humidity, 65);
+        assert_eq!(t.vpd, None);
+        assert_eq!(t.light_intensity, if lights_on { 200 } else { 0 });
+        assert_eq!(t.vent_on, lights_on || (19..21).contains(&step.hour), "day {} {:02}:{:02}", step.day, step.hour, step.minute);
+    }
+}